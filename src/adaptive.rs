@@ -0,0 +1,234 @@
+use crate::circuit::host_key;
+use crate::cli::Args;
+use crate::error::{ErrorCode, RuGetError};
+use crate::output::Logger;
+use crate::retry::{error_condition, RetryCondition};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Value of `Args::jobs` for `--jobs auto`
+pub const AUTO_JOBS: usize = usize::MAX;
+
+/// Connections a host starts with under --jobs auto
+pub const INITIAL_CONNECTIONS: usize = 2;
+
+/// Most connections --jobs auto opens to one host
+pub const MAX_CONNECTIONS: usize = 16;
+
+/// Worker threads for --jobs auto, enough for several hosts at full concurrency
+pub const AUTO_THREADS: usize = 2 * MAX_CONNECTIONS;
+
+/// Throughput is compared between windows of this length
+const WINDOW: Duration = Duration::from_millis(500);
+
+/// Parse --jobs: a number of parallel downloads or `auto`
+pub fn parse_jobs(value: &str) -> std::result::Result<usize, String> {
+    if value.eq_ignore_ascii_case("auto") {
+        return Ok(AUTO_JOBS);
+    }
+    value.parse().map_err(|_| format!("'{}' is not a number of jobs or 'auto'", value))
+}
+
+pub fn is_auto(args: &Args) -> bool {
+    args.jobs == AUTO_JOBS
+}
+
+/// Per-host connection limits for --jobs auto, shared by every worker of a run
+///
+/// Each host starts at [`INITIAL_CONNECTIONS`]. The limit grows by one while
+/// the host's throughput keeps improving and halves when it answers 429 or 503
+/// or a transfer times out.
+#[derive(Debug, Default)]
+pub struct AdaptiveConcurrency {
+    hosts: Mutex<HashMap<String, Arc<HostLimit>>>,
+}
+
+impl AdaptiveConcurrency {
+    /// Wait for a connection slot to `url`'s host; without --jobs auto slots are unlimited
+    pub fn acquire(&self, args: &Args, url: &str) -> Slot {
+        if !is_auto(args) {
+            return Slot(None);
+        }
+        let Some(host) = host_key(url) else {
+            return Slot(None);
+        };
+
+        let limit = Arc::clone(
+            self.hosts.lock().unwrap()
+                .entry(host.clone())
+                .or_insert_with(|| Arc::new(HostLimit::new(host))),
+        );
+        let mut state = limit.state.lock().unwrap();
+        while state.in_flight >= state.limit {
+            state = limit.freed.wait(state).unwrap();
+        }
+        state.in_flight += 1;
+        drop(state);
+        Slot(Some(limit))
+    }
+}
+
+#[derive(Debug)]
+struct HostLimit {
+    host: String,
+    state: Mutex<LimitState>,
+    freed: Condvar,
+}
+
+#[derive(Debug)]
+struct LimitState {
+    limit: usize,
+    in_flight: usize,
+    window_started: Instant,
+    window_bytes: u64,
+    last_rate: f64,
+    last_decrease: Option<Instant>,
+}
+
+impl HostLimit {
+    fn new(host: String) -> Self {
+        Self {
+            host,
+            state: Mutex::new(LimitState {
+                limit: INITIAL_CONNECTIONS,
+                in_flight: 0,
+                window_started: Instant::now(),
+                window_bytes: 0,
+                last_rate: 0.0,
+                last_decrease: None,
+            }),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Additive increase: one more connection each window whose throughput
+    /// beat the previous one while every allowed connection was busy
+    fn record(&self, bytes: usize, logger: &Logger) {
+        let mut state = self.state.lock().unwrap();
+        state.window_bytes += bytes as u64;
+
+        let elapsed = state.window_started.elapsed();
+        if elapsed < WINDOW {
+            return;
+        }
+        let rate = state.window_bytes as f64 / elapsed.as_secs_f64();
+        if rate > state.last_rate * 1.1 && state.in_flight >= state.limit && state.limit < MAX_CONNECTIONS {
+            state.limit += 1;
+            logger.verbose(&format!(
+                "Raised connections to {} to {} ({:.0} B/s)", self.host, state.limit, rate
+            ));
+            self.freed.notify_one();
+        }
+        state.last_rate = rate;
+        state.window_started = Instant::now();
+        state.window_bytes = 0;
+    }
+
+    /// Multiplicative decrease, at most once per window so that a burst of
+    /// rejected requests only halves the limit once
+    fn congested(&self, reason: &str, logger: &Logger) {
+        let mut state = self.state.lock().unwrap();
+        if state.last_decrease.is_some_and(|at| at.elapsed() < WINDOW) {
+            return;
+        }
+        state.limit = (state.limit / 2).max(1);
+        state.last_decrease = Some(Instant::now());
+        logger.verbose(&format!("Lowered connections to {} to {} after {}", self.host, state.limit, reason));
+    }
+}
+
+/// A connection slot to one host, released when dropped
+#[derive(Debug)]
+pub struct Slot(Option<Arc<HostLimit>>);
+
+impl Slot {
+    /// Account for bytes received on this connection
+    pub fn record(&self, bytes: usize, logger: &Logger) {
+        if let Some(limit) = &self.0 {
+            limit.record(bytes, logger);
+        }
+    }
+
+    /// Back off if the server is signalling overload
+    pub fn status(&self, status: StatusCode, logger: &Logger) {
+        if let Some(limit) = &self.0
+            && matches!(status, StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE)
+        {
+            limit.congested(&format!("HTTP {}", status), logger);
+        }
+    }
+
+    /// Back off if the connection timed out
+    pub fn error(&self, error: &RuGetError, logger: &Logger) {
+        if let Some(limit) = &self.0
+            && (error.code == ErrorCode::E404 || error_condition(error) == Some(RetryCondition::Timeout))
+        {
+            limit.congested("a timeout", logger);
+        }
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(limit) = &self.0 {
+            limit.state.lock().unwrap().in_flight -= 1;
+            limit.freed.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    impl AdaptiveConcurrency {
+        fn limit_for(&self, url: &str) -> Option<usize> {
+            let limit = Arc::clone(self.hosts.lock().unwrap().get(&host_key(url)?)?);
+            let limit = limit.state.lock().unwrap().limit;
+            Some(limit)
+        }
+    }
+
+    #[test]
+    fn test_parse_jobs() {
+        assert_eq!(parse_jobs("4"), Ok(4));
+        assert_eq!(parse_jobs("AUTO"), Ok(AUTO_JOBS));
+        assert!(parse_jobs("many").is_err());
+    }
+
+    #[test]
+    fn test_slots_unlimited_without_auto() {
+        let args = Args::parse_from(["ruget", "--jobs", "1", "http://example.com/a"]);
+        let concurrency = AdaptiveConcurrency::default();
+        let _slots: Vec<_> = (0..10).map(|_| concurrency.acquire(&args, "http://example.com/a")).collect();
+        assert_eq!(concurrency.limit_for("http://example.com/a"), None);
+    }
+
+    #[test]
+    fn test_limit_halves_on_congestion_and_grows_with_throughput() {
+        let args = Args::parse_from(["ruget", "--jobs", "auto", "http://example.com/a"]);
+        let logger = Logger::new(true, false);
+        let concurrency = AdaptiveConcurrency::default();
+        let url = "http://example.com/a";
+
+        let first = concurrency.acquire(&args, url);
+        let second = concurrency.acquire(&args, url);
+        assert_eq!(concurrency.limit_for(url), Some(INITIAL_CONNECTIONS));
+
+        // Both connections busy and the first window shows throughput
+        std::thread::sleep(WINDOW);
+        first.record(1_000_000, &logger);
+        assert_eq!(concurrency.limit_for(url), Some(INITIAL_CONNECTIONS + 1));
+
+        second.status(StatusCode::SERVICE_UNAVAILABLE, &logger);
+        second.status(StatusCode::TOO_MANY_REQUESTS, &logger);
+        assert_eq!(concurrency.limit_for(url), Some(1), "a burst halves the limit once");
+
+        // A different host keeps its own limit
+        let _other = concurrency.acquire(&args, "http://other.example.com/b");
+        assert_eq!(concurrency.limit_for("http://other.example.com/b"), Some(INITIAL_CONNECTIONS));
+    }
+}
@@ -0,0 +1,575 @@
+use crate::adaptive;
+use crate::circuit::{host_key, CircuitBreaker};
+use crate::cli::Args;
+use crate::cookie::load_cookies_from_file;
+use crate::download::{
+    append_failure_log, batch_progress, default_headers, finish_batch, in_output_dir, job_args, known_output_path, prints_to_stdout,
+    retry_after_batch, server_file_name, verify_checksum,
+};
+use crate::encoding::identity_encoding;
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError, WithContext};
+use crate::hooks::{DownloadEvent, HookRunner};
+use crate::http::{add_netrc_auth, build_headers};
+use crate::manifest::{DownloadSpec, JobStream};
+use crate::multithreaded_download::{cancelled_error, STDOUT_PATH};
+use crate::output::Logger;
+use crate::probe::RemoteInfo;
+use crate::resume::ResumeState;
+use crate::retry::RetryPolicy;
+use crate::run::RunContext;
+use crate::scheduler;
+use crate::single_stream::{Reply, SingleStream};
+use crate::timeouts::Timeouts;
+use crate::transport::{build_transport, Transport, TransportKind};
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use reqwest::cookie::Jar;
+use reqwest::header::RANGE;
+use reqwest::Client;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, watch, Semaphore};
+use tokio::task::{block_in_place, JoinSet};
+
+#[cfg(feature = "context")]
+use crate::error::AnyhowContextExt;
+
+/// How the jobs of a batch are run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Engine {
+    /// The async engine when --jobs is above 64 and it supports every option given, threads otherwise
+    #[default]
+    Auto,
+    /// One worker thread per job, each blocking on its connection
+    Threads,
+    /// Jobs as tasks on a few threads, at most --jobs of them transferring at once
+    Async,
+}
+
+/// --jobs above which `--engine auto` runs the batch on the async engine
+pub const ASYNC_JOBS: usize = 64;
+
+/// An option the async engine does not implement, if any was given
+pub fn unsupported(args: &Args) -> Option<&'static str> {
+    if adaptive::is_auto(args) {
+        Some("--jobs auto")
+    } else if scheduler::split(args) > 1 {
+        Some("--split")
+    } else if args.extract.is_some() {
+        Some("--extract")
+    } else if args.transport == TransportKind::Native {
+        Some("--transport native")
+    } else if prints_to_stdout(args) || args.output.as_deref() == Some(STDOUT_PATH) {
+        Some("writing to stdout")
+    } else {
+        None
+    }
+}
+
+/// Whether `download()` hands the batch to the async engine
+pub fn use_async_engine(args: &Args, logger: &Logger) -> bool {
+    match args.engine {
+        Engine::Threads => false,
+        Engine::Auto => args.jobs > ASYNC_JOBS && unsupported(args).is_none(),
+        Engine::Async => match unsupported(args) {
+            Some(option) => {
+                logger.warn(&format!("The async engine does not support {}, using threads", option));
+                false
+            }
+            None => true,
+        },
+    }
+}
+
+/// Downloads in flight at once: --jobs (one per CPU for 0), capped by --max-connections
+pub fn concurrency(args: &Args) -> usize {
+    let jobs = match args.jobs {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    match args.max_connections {
+        0 => jobs,
+        cap => jobs.min(cap),
+    }
+}
+
+/// Download every job as a task on a tokio runtime, at most [`concurrency`] at a time
+///
+/// Jobs wait for a permit before their task is spawned, so a batch of 100k
+/// files never holds more than --jobs tasks, and those share a few threads
+/// however many connections they keep open. Each file is fetched over one
+/// connection and named from the response that carries it, saving the HEAD
+/// request the threaded engine sends. Ctrl-C stops new jobs from starting and
+/// running ones at their next read or backoff; what they wrote is kept for --resume.
+pub fn download(args: Args, jobs: JobStream, logger: &Logger) -> Result<()> {
+    let cookie_jar = Arc::new(Jar::default());
+    if let Some(cookie_file) = &args.load_cookies {
+        load_cookies_from_file(&cookie_jar, cookie_file, logger)?;
+    }
+    if args.output.is_some() && jobs.known_specs().is_none_or(|specs| specs.len() > 1) {
+        return Err(RuGetError::parse("Cannot use --output with multiple URLs".into()));
+    }
+
+    let breaker = CircuitBreaker::from_args(&args);
+    // Probes are rare and go through a blocking transport, which has to be
+    // built outside the runtime
+    let probe = match breaker {
+        Some(_) => Some(build_transport(&args, Arc::clone(&cookie_jar), logger)?),
+        None => None,
+    };
+    let (cancel_sender, cancel) = watch::channel(false);
+    let run = Arc::new(Run {
+        client: build_async_client(&args, Arc::clone(&cookie_jar), logger)?,
+        probe,
+        pb: batch_progress(&args, None),
+        hooks: HookRunner::from_args(&args, logger),
+        breaker,
+        logger: logger.clone(),
+        cancel,
+        context: RunContext::default(),
+    });
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| RuGetError::new(ErrorCode::E505, ErrorKind::Io, format!("Failed to start the async engine: {}", e)))?;
+    logger.verbose(&format!("Running up to {} downloads at once on the async engine", concurrency(&args)));
+    let batch_start = Instant::now();
+    let args = Arc::new(args);
+    let (total, final_failures) = runtime.block_on(run_batch(Arc::clone(&run), jobs, Arc::clone(&args), cancel_sender));
+    drop(runtime);
+
+    if let Some(pb) = &run.pb {
+        pb.finish_and_clear();
+    }
+    let result = append_failure_log(&args.log, &final_failures)
+        .and_then(|()| finish_batch(&args, &run.hooks, &cookie_jar, total, final_failures.len(), batch_start, logger));
+    if run.is_cancelled() {
+        return Err(RuGetError::new(ErrorCode::E500, ErrorKind::Network, "Downloads were interrupted".into()));
+    }
+    result
+}
+
+/// The reqwest client behind every task, configured like the blocking one
+pub fn build_async_client(args: &Args, cookie_jar: Arc<Jar>, logger: &Logger) -> Result<Client> {
+    let decode = args.compressed && !args.raw;
+    let timeouts = Timeouts::from_args(args);
+    Client::builder()
+        .gzip(decode)
+        .brotli(decode)
+        .zstd(decode)
+        .deflate(decode)
+        .connect_timeout(timeouts.connect)
+        .read_timeout(timeouts.io_timeout())
+        .cookie_provider(cookie_jar)
+        .default_headers(default_headers(args, logger))
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .build()
+        .with_context(|| "building async HTTP client".to_string())
+}
+
+/// State shared by every task of one run
+struct Run {
+    client: Client,
+    /// Carries the circuit breaker's probes
+    probe: Option<Box<dyn Transport>>,
+    pb: Option<Arc<ProgressBar>>,
+    hooks: HookRunner,
+    breaker: Option<CircuitBreaker>,
+    logger: Logger,
+    cancel: watch::Receiver<bool>,
+    /// Retries spent across the run, for --retry-budget
+    context: RunContext,
+}
+
+impl Run {
+    fn is_cancelled(&self) -> bool {
+        *self.cancel.borrow()
+    }
+
+    /// Resolves once cancellation has been requested
+    async fn cancelled(&self) {
+        let mut cancel = self.cancel.clone();
+        if cancel.wait_for(|cancelled| *cancelled).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Wait out a retry delay, unless the run is cancelled first
+    async fn sleep(&self, delay: Duration, url: &str) -> Result<()> {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => Ok(()),
+            _ = self.cancelled() => Err(cancelled_error(url)),
+        }
+    }
+}
+
+/// Run every job, then give the failed ones one more attempt; returns the
+/// number of jobs and the `(url, error)` of those that failed for good
+async fn run_batch(
+    run: Arc<Run>,
+    jobs: JobStream,
+    args: Arc<Args>,
+    cancel: watch::Sender<bool>,
+) -> (usize, Vec<(String, String)>) {
+    let logger = run.logger.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            logger.warn("Interrupted, stopping downloads; partial files are kept for --resume");
+            let _ = cancel.send(true);
+        }
+    });
+    let logger = &run.logger;
+
+    // Manifests on stdin block while waiting for input, so jobs are read on their own thread
+    let permits = Arc::new(Semaphore::new(concurrency(&args)));
+    let (sender, mut specs) = mpsc::channel(concurrency(&args));
+    std::thread::spawn(move || {
+        for job in jobs {
+            if sender.blocking_send(job).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut tasks = JoinSet::new();
+    let mut failures = vec![];
+    let (mut total, mut succeeded) = (0, 0);
+    let mut collect = |done: std::result::Result<_, tokio::task::JoinError>| match done {
+        Ok((_, _, Ok(()))) => succeeded += 1,
+        Ok((spec, path, Err(err))) => {
+            logger.error_from_ruget_error(&err);
+            failures.push((spec, path, err));
+        }
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    };
+    loop {
+        let permit = tokio::select! {
+            permit = Arc::clone(&permits).acquire_owned() => permit.expect("the semaphore is never closed"),
+            _ = run.cancelled() => break,
+        };
+        let spec = tokio::select! {
+            job = specs.recv() => match job {
+                Some(Ok(spec)) => spec,
+                Some(Err(e)) => {
+                    logger.error_from_ruget_error(&e);
+                    continue;
+                }
+                None => break,
+            },
+            _ = run.cancelled() => break,
+        };
+        // Results are collected as tasks finish, so they do not pile up over a long batch
+        while let Some(done) = tasks.try_join_next() {
+            collect(done);
+        }
+
+        total += 1;
+        let (run, args) = (Arc::clone(&run), job_args(&args, &spec));
+        tasks.spawn(async move {
+            let _permit = permit;
+            let mut path = known_output_path(&spec, &args);
+            let result = download_job(&run, &spec, &mut path, &args).await;
+            (spec, path, result)
+        });
+    }
+    while let Some(done) = tasks.join_next().await {
+        collect(done);
+    }
+    logger.summary(succeeded, total);
+
+    let retry_budget = RetryPolicy::from_args(&args, &run.context).budget;
+    let mut deferred_hosts = HashSet::new();
+    let mut final_failures = vec![];
+    for (spec, mut path, err) in failures {
+        let url = spec.url.clone();
+        if run.is_cancelled() || !retry_after_batch(&err, &retry_budget) {
+            final_failures.push((url, err.to_string()));
+            continue;
+        }
+        // Jobs skipped by an open circuit wait out its cool-down, once per host
+        if let Some(breaker) = &run.breaker
+            && let Some(remaining) = breaker.cooldown_remaining(&url)
+            && deferred_hosts.insert(host_key(&url))
+        {
+            logger.info(&format!("Waiting {:.1}s for the circuit to {} to cool down", remaining.as_secs_f64(), url));
+            if let Err(e) = run.sleep(remaining, &url).await {
+                final_failures.push((url, e.to_string()));
+                continue;
+            }
+        }
+        logger.info(&format!("Retrying: {}", url));
+
+        match download_job(&run, &spec, &mut path, &job_args(&args, &spec)).await {
+            Ok(()) => logger.info(&format!("Retry succeeded: {}", url)),
+            Err(e) => {
+                logger.error_from_ruget_error(&e);
+                final_failures.push((url, e.to_string()));
+            }
+        }
+    }
+    (total, final_failures)
+}
+
+/// Download one job, trying its mirrors in order, then verify it and run its hook
+///
+/// `path` is filled in from the first response when the job's name comes from the server.
+async fn download_job(run: &Run, spec: &DownloadSpec, path: &mut Option<String>, args: &Args) -> Result<()> {
+    let logger = &run.logger;
+    let start = Instant::now();
+    if path.is_none() && args.resume {
+        // A partial file can only be found under the name the server gives it
+        *path = Some(probe_path(run, spec, args).await);
+    }
+    if let Some(output_path) = path.as_deref() {
+        logger.download_start(&spec.url, output_path);
+    }
+
+    let mut result = Err(RuGetError::network(format!("No URL to download for {}", spec.url)));
+    let mut source = spec.url.as_str();
+    for (attempt, url) in spec.sources().enumerate() {
+        if attempt > 0 {
+            logger.warn(&format!("Trying mirror {} for {}", url, path.as_deref().unwrap_or(&spec.url)));
+        }
+        source = url;
+        if let (Some(breaker), Some(probe)) = (&run.breaker, &run.probe)
+            && let Err(e) = block_in_place(|| breaker.admit(probe.as_ref(), url, logger))
+        {
+            result = Err(e);
+            continue;
+        }
+        result = fetch(run, spec, url, path, args).await;
+        if run.is_cancelled() {
+            return result;
+        }
+        if let Some(breaker) = &run.breaker {
+            breaker.record(url, &result, logger);
+        }
+        if result.is_ok() {
+            break;
+        }
+    }
+    result?;
+
+    let output_path = path.as_deref().unwrap_or_default();
+    block_in_place(|| {
+        let sha256 = verify_checksum(spec, output_path, logger)?;
+        run.hooks.after_download(&DownloadEvent {
+            url: source.to_string(),
+            path: output_path.to_string(),
+            size: std::fs::metadata(output_path).map(|m| m.len()).unwrap_or(0),
+            duration: start.elapsed(),
+            sha256,
+        }, logger);
+        Ok(())
+    })
+}
+
+/// Name a job from a HEAD request, for a --resume that must find its partial file first
+async fn probe_path(run: &Run, spec: &DownloadSpec, args: &Args) -> String {
+    let remote = run.client.head(&spec.url).header(identity_encoding().0, identity_encoding().1).send().await.ok()
+        .map(|response| RemoteInfo {
+            final_url: Some(response.url().to_string()),
+            ..RemoteInfo::from_headers(None, response.headers())
+        });
+    in_output_dir(spec, args, &server_file_name(spec, remote.as_ref(), args))
+}
+
+/// Fetch `url` into the job's file, retrying as --retry-on allows
+///
+/// Whatever was written is flushed to disk however the transfer ends, so a
+/// retry, the next mirror or a later --resume continues after it.
+async fn fetch(run: &Run, spec: &DownloadSpec, url: &str, path: &mut Option<String>, args: &Args) -> Result<()> {
+    let mut file = None;
+    let result = transfer(run, spec, url, path, args, &mut file).await;
+    close(&mut file, path.as_deref().unwrap_or_default()).await?;
+    if result.is_ok()
+        && let Some(output_path) = path.as_deref()
+    {
+        ResumeState::remove(output_path);
+    }
+    result
+}
+
+async fn transfer(
+    run: &Run,
+    spec: &DownloadSpec,
+    url: &str,
+    path: &mut Option<String>,
+    args: &Args,
+    file: &mut Option<BufWriter<File>>,
+) -> Result<()> {
+    let logger = &run.logger;
+    let mut headers = build_headers(&args.headers, logger);
+    add_netrc_auth(&mut headers, url).with_context(|| format!("adding netrc auth for {}", url))?;
+
+    let retry_policy = RetryPolicy::from_args(args, &run.context);
+    let mut stream = SingleStream::new(url, &retry_policy);
+    if args.resume
+        && let Some(output_path) = path.as_deref()
+        && let Ok(metadata) = fs::metadata(output_path).await
+        && metadata.len() > 0
+    {
+        stream.resume(metadata.len(), ResumeState::load(output_path), output_path, logger);
+    }
+    let timeouts = Timeouts::from_args(args);
+
+    loop {
+        let mut request_headers = stream.request_headers(&headers)?;
+        // Ranges count bytes of the file on disk, so the unencoded body is asked for
+        if args.compressed && request_headers.contains_key(RANGE) {
+            let (name, value) = identity_encoding();
+            request_headers.insert(name, value);
+        }
+        let response = tokio::select! {
+            response = run.client.get(url).headers(request_headers).send() => response,
+            _ = run.cancelled() => return Err(cancelled_error(url)),
+        };
+
+        let error = match response {
+            Ok(mut resp) => {
+                let output_path = match path {
+                    Some(output_path) => output_path.clone(),
+                    None => {
+                        let remote = RemoteInfo {
+                            final_url: Some(resp.url().to_string()),
+                            ..RemoteInfo::from_headers(None, resp.headers())
+                        };
+                        let output_path = in_output_dir(spec, args, &server_file_name(spec, Some(&remote), args));
+                        logger.download_start(&spec.url, &output_path);
+                        path.insert(output_path).clone()
+                    }
+                };
+
+                match stream.on_response(resp.status(), resp.headers(), &output_path, || None, logger)? {
+                    Reply::Complete => return Ok(()),
+                    Reply::Restart => {
+                        close(file, &output_path).await?;
+                        continue;
+                    }
+                    Reply::Retry(delay) => {
+                        run.sleep(delay, url).await?;
+                        continue;
+                    }
+                    Reply::Body { fresh, truncate } => {
+                        if truncate {
+                            close(file, &output_path).await?;
+                        }
+                        if fresh {
+                            stream.save_validators(&output_path, true)?;
+                        }
+                    }
+                }
+                let writer = match file {
+                    Some(writer) => writer,
+                    None => file.insert(open(&output_path, stream.offset() > 0).await?),
+                };
+
+                // A body that ends early is an interrupted transfer, not a complete one
+                let expected = resp.content_length();
+                let mut watch = timeouts.watch(url);
+                let interrupted = loop {
+                    let chunk = tokio::select! {
+                        chunk = resp.chunk() => chunk,
+                        _ = run.cancelled() => return Err(cancelled_error(url)),
+                    };
+                    let bytes = match chunk {
+                        Ok(Some(bytes)) => bytes,
+                        Ok(None) => break stream.end_of_body(expected),
+                        Err(e) => break Some(RuGetError::from(e).with_context(&format!("reading response data from {}", url))),
+                    };
+                    if let Err(e) = watch.record(bytes.len()) {
+                        break Some(e);
+                    }
+                    writer.write_all(&bytes).await
+                        .with_context(|| format!("writing data to {}", output_path))?;
+                    if let Some(pb) = &run.pb {
+                        pb.inc(bytes.len() as u64);
+                    }
+                    stream.advance(bytes.len());
+                };
+
+                match interrupted {
+                    Some(e) => e,
+                    None => return Ok(()),
+                }
+            }
+            Err(e) => RuGetError::from(e).with_context(&format!("sending GET request to {}", url)),
+        };
+
+        let delay = stream.on_error(error, logger)?;
+        run.sleep(delay, url).await?;
+    }
+}
+
+/// Open the output file, appending to what is there or starting it afresh
+async fn open(output_path: &str, append: bool) -> Result<BufWriter<File>> {
+    if let Some(parent) = Path::new(output_path).parent() {
+        fs::create_dir_all(parent).await
+            .with_context(|| format!("creating output directory for {}", output_path))?;
+    }
+    let file = if append {
+        OpenOptions::new().append(true).open(output_path).await
+            .with_context(|| format!("opening file in append mode for {}", output_path))?
+    } else {
+        File::create(output_path).await
+            .with_context(|| format!("creating new file for {}", output_path))?
+    };
+    Ok(BufWriter::new(file))
+}
+
+/// Flush and close the output file, if it is open
+///
+/// Writes to a tokio file finish in the background, so this must happen
+/// before the file is opened again or the transfer is reported as done.
+async fn close(file: &mut Option<BufWriter<File>>, output_path: &str) -> Result<()> {
+    if let Some(mut writer) = file.take() {
+        writer.flush().await.with_context(|| format!("writing data to {}", output_path))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_auto_engine_follows_jobs() {
+        let engine_for = |argv: &[&str]| {
+            let args = Args::parse_from([&["ruget", "--output-dir", "out", "http://example.com/a"], argv].concat());
+            use_async_engine(&args, &Logger::new(true, false))
+        };
+        assert!(!engine_for(&["--jobs", "8"]));
+        assert!(engine_for(&["--jobs", "500"]));
+        assert!(!engine_for(&["--jobs", "500", "--engine", "threads"]));
+        assert!(engine_for(&["--jobs", "2", "--engine", "async"]));
+        // Options the async engine lacks keep the batch on threads
+        assert!(!engine_for(&["--jobs", "500", "--split", "4"]));
+        assert!(!engine_for(&["--jobs", "auto", "--engine", "async"]));
+        assert!(!engine_for(&["--jobs", "500", "--transport", "native"]));
+    }
+
+    #[test]
+    fn test_jobs_alone_keeps_one_stream_per_file() {
+        // Without --split both engines fetch each file over one connection,
+        // so a large --jobs may move the batch to the async engine
+        for jobs in ["8", "500"] {
+            let args = Args::parse_from(["ruget", "--output-dir", "out", "--jobs", jobs, "--engine", "async", "http://example.com/a"]);
+            assert_eq!(scheduler::split(&args), 1);
+            assert_eq!(unsupported(&args), None);
+        }
+    }
+
+    #[test]
+    fn test_concurrency_is_capped_by_max_connections() {
+        let concurrency_for = |argv: &[&str]| concurrency(&Args::parse_from([&["ruget", "http://example.com/a"], argv].concat()));
+        assert_eq!(concurrency_for(&["--jobs", "500"]), 500);
+        assert_eq!(concurrency_for(&["--jobs", "500", "--max-connections", "100"]), 100);
+        assert!(concurrency_for(&[]) >= 1);
+    }
+}
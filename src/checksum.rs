@@ -0,0 +1,165 @@
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError, WithContext};
+use sha2::Digest;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+#[cfg(feature = "context")]
+use crate::error::AnyhowContextExt;
+
+/// Hash algorithms accepted in `checksum=` manifest options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    /// Parse an algorithm name as written by aria2 (`sha-256`) or coreutils (`sha256`)
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().replace('-', "").as_str() {
+            "md5" => Ok(ChecksumAlgorithm::Md5),
+            "sha1" => Ok(ChecksumAlgorithm::Sha1),
+            "sha224" => Ok(ChecksumAlgorithm::Sha224),
+            "sha256" => Ok(ChecksumAlgorithm::Sha256),
+            "sha384" => Ok(ChecksumAlgorithm::Sha384),
+            "sha512" => Ok(ChecksumAlgorithm::Sha512),
+            _ => Err(RuGetError::parse(format!("Unsupported checksum algorithm: {}", name))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Sha1 => "sha-1",
+            ChecksumAlgorithm::Sha224 => "sha-224",
+            ChecksumAlgorithm::Sha256 => "sha-256",
+            ChecksumAlgorithm::Sha384 => "sha-384",
+            ChecksumAlgorithm::Sha512 => "sha-512",
+        }
+    }
+
+    /// Hex-encoded digest of everything read from `reader`
+    pub fn digest_reader<R: Read>(&self, reader: R) -> std::io::Result<String> {
+        match self {
+            ChecksumAlgorithm::Md5 => hash_reader::<md5::Md5, R>(reader),
+            ChecksumAlgorithm::Sha1 => hash_reader::<sha1::Sha1, R>(reader),
+            ChecksumAlgorithm::Sha224 => hash_reader::<sha2::Sha224, R>(reader),
+            ChecksumAlgorithm::Sha256 => hash_reader::<sha2::Sha256, R>(reader),
+            ChecksumAlgorithm::Sha384 => hash_reader::<sha2::Sha384, R>(reader),
+            ChecksumAlgorithm::Sha512 => hash_reader::<sha2::Sha512, R>(reader),
+        }
+    }
+}
+
+fn hash_reader<D: Digest, R: Read>(mut reader: R) -> std::io::Result<String> {
+    let mut hasher = D::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Expected digest of a download, written as `<algorithm>=<hex digest>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub expected: String,
+}
+
+impl FromStr for Checksum {
+    type Err = RuGetError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (algorithm, expected) = value.split_once('=').ok_or_else(|| {
+            RuGetError::parse(format!("Checksum '{}' must be written as <algorithm>=<digest>", value))
+        })?;
+
+        let expected = expected.trim().to_ascii_lowercase();
+        if expected.is_empty() || !expected.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(RuGetError::parse(format!("Checksum digest '{}' is not hexadecimal", expected)));
+        }
+
+        Ok(Self { algorithm: ChecksumAlgorithm::parse(algorithm)?, expected })
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.algorithm.name(), self.expected)
+    }
+}
+
+impl Checksum {
+    /// Hash the file at `path` and compare it with the expected digest
+    ///
+    /// Returns the computed digest on success and E504 on a mismatch.
+    pub fn verify_file(&self, path: &Path) -> Result<String> {
+        let file = File::open(path)
+            .with_context(|| format!("opening {} for checksum verification", path.display()))?;
+        let actual = self.algorithm.digest_reader(file)
+            .with_context(|| format!("reading {} for checksum verification", path.display()))?;
+
+        if actual != self.expected {
+            return Err(RuGetError::new(
+                ErrorCode::E504,
+                ErrorKind::Io,
+                format!(
+                    "Checksum mismatch for {}: expected {} {}, got {}",
+                    path.display(),
+                    self.algorithm.name(),
+                    self.expected,
+                    actual
+                ),
+            ));
+        }
+        Ok(actual)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_parse_checksum() {
+        let checksum: Checksum = "sha-256=ABCDEF01".parse().unwrap();
+        assert_eq!(checksum.algorithm, ChecksumAlgorithm::Sha256);
+        assert_eq!(checksum.expected, "abcdef01");
+        assert_eq!(checksum.to_string(), "sha-256=abcdef01");
+
+        assert!("sha256".parse::<Checksum>().is_err());
+        assert!("crc32=0000".parse::<Checksum>().is_err());
+        assert!("md5=xyz".parse::<Checksum>().is_err());
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"hello").unwrap();
+
+        let good: Checksum = "sha-256=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+            .parse()
+            .unwrap();
+        assert_eq!(good.verify_file(file.path()).unwrap(), good.expected);
+
+        let md5: Checksum = "md5=5d41402abc4b2a76b9719d911017c592".parse().unwrap();
+        assert!(md5.verify_file(file.path()).is_ok());
+
+        let bad: Checksum = "sha-1=0000000000000000000000000000000000000000".parse().unwrap();
+        assert_eq!(bad.verify_file(file.path()).unwrap_err().code, ErrorCode::E504);
+    }
+}
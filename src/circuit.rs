@@ -0,0 +1,192 @@
+use crate::cli::Args;
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError};
+use crate::output::Logger;
+use crate::retry::error_condition;
+use crate::transport::Transport;
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Breaker state for one host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Requests flow; counts failures in a row
+    Closed { failures: u32 },
+    /// Requests fail fast until the cool-down ends
+    Open { until: Instant },
+    /// One probe is deciding whether to close the breaker
+    HalfOpen,
+}
+
+/// Stops a batch from spending every retry on a host that is down
+///
+/// Shared by all workers of a `download()` run. After `threshold` host-level
+/// failures in a row (server errors, timeouts, refused or reset connections)
+/// the host's breaker opens and its jobs fail fast. Once `cooldown` has passed
+/// the next job sends a HEAD probe, which closes the breaker or re-opens it.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    hosts: Mutex<HashMap<String, State>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold, cooldown, hosts: Mutex::new(HashMap::new()) }
+    }
+
+    /// The breaker configured by --circuit-threshold and --circuit-cooldown, if enabled
+    pub fn from_args(args: &Args) -> Option<Self> {
+        (args.circuit_threshold > 0)
+            .then(|| Self::new(args.circuit_threshold, Duration::from_secs_f64(args.circuit_cooldown)))
+    }
+
+    /// Let a request to `url` through, probing its host first if the cool-down has just ended
+    pub fn admit(&self, client: &dyn Transport, url: &str, logger: &Logger) -> Result<()> {
+        let Some(host) = host_key(url) else {
+            return Ok(());
+        };
+
+        {
+            let mut hosts = self.hosts.lock().unwrap();
+            match hosts.get(&host).copied() {
+                None | Some(State::Closed { .. }) => return Ok(()),
+                Some(State::Open { until }) if Instant::now() < until => {
+                    return Err(self.open_error(url, &host, until - Instant::now()));
+                }
+                Some(State::Open { .. }) => {
+                    hosts.insert(host.clone(), State::HalfOpen);
+                }
+                Some(State::HalfOpen) => return Err(self.open_error(url, &host, Duration::ZERO)),
+            }
+        }
+
+        logger.circuit_change(&host, "half-open", &format!("probing with HEAD {}", url));
+        // Any answer short of a server error shows the host is serving again
+        let healthy = client.head(url, HeaderMap::new()).is_ok_and(|resp| !resp.status().is_server_error());
+
+        let mut hosts = self.hosts.lock().unwrap();
+        if healthy {
+            hosts.insert(host.clone(), State::Closed { failures: 0 });
+            logger.circuit_change(&host, "closed", "probe succeeded");
+            Ok(())
+        } else {
+            hosts.insert(host.clone(), State::Open { until: Instant::now() + self.cooldown });
+            logger.circuit_change(&host, "open", "probe failed");
+            Err(self.open_error(url, &host, self.cooldown))
+        }
+    }
+
+    /// Account for the outcome of a download from `url`
+    pub fn record(&self, url: &str, outcome: &Result<()>, logger: &Logger) {
+        let Some(host) = host_key(url) else {
+            return;
+        };
+
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(host.clone()).or_insert(State::Closed { failures: 0 });
+        match (outcome, *state) {
+            (Err(err), State::Closed { failures }) if is_host_failure(err) => {
+                let failures = failures + 1;
+                if failures >= self.threshold {
+                    *state = State::Open { until: Instant::now() + self.cooldown };
+                    logger.circuit_change(&host, "open", &format!(
+                        "{} failures in a row, cooling down for {:.1}s", failures, self.cooldown.as_secs_f64()
+                    ));
+                } else {
+                    *state = State::Closed { failures };
+                }
+            }
+            // Failures of requests that were already in flight when it opened
+            (Err(err), _) if is_host_failure(err) => {}
+            // The host answered, even if the download failed for another reason
+            (_, State::Closed { .. }) => *state = State::Closed { failures: 0 },
+            (_, _) => {
+                *state = State::Closed { failures: 0 };
+                logger.circuit_change(&host, "closed", &format!("{} succeeded", url));
+            }
+        }
+    }
+
+    /// Time until `url`'s host may be probed again, if its breaker is open
+    pub fn cooldown_remaining(&self, url: &str) -> Option<Duration> {
+        let host = host_key(url)?;
+        match self.hosts.lock().unwrap().get(&host) {
+            Some(State::Open { until }) => Some(until.saturating_duration_since(Instant::now())),
+            _ => None,
+        }
+    }
+
+    fn open_error(&self, url: &str, host: &str, remaining: Duration) -> RuGetError {
+        RuGetError::new(
+            ErrorCode::E402,
+            ErrorKind::Network,
+            format!(
+                "Skipped {}: {} failed {} times in a row (circuit open, next probe in {:.1}s)",
+                url, host, self.threshold, remaining.as_secs_f64()
+            ),
+        )
+    }
+}
+
+/// Server errors and network failures say something about the host;
+/// client errors, checksum mismatches and local I/O problems do not
+fn is_host_failure(err: &RuGetError) -> bool {
+    err.code == ErrorCode::E203 || error_condition(err).is_some()
+}
+
+/// Breakers are kept per scheme, host and port
+pub fn host_key(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    Some(format!("{}://{}:{}", url.scheme(), url.host_str()?, url.port_or_known_default()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure() -> Result<()> {
+        Err(RuGetError::new(ErrorCode::E203, ErrorKind::Http, "HTTP 503".into()))
+    }
+
+    #[test]
+    fn test_opens_after_threshold_failures_in_a_row() {
+        let logger = Logger::new(true, false);
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        let url = "http://down.example.com/a";
+
+        breaker.record(url, &failure(), &logger);
+        breaker.record(url, &failure(), &logger);
+        breaker.record(url, &Ok(()), &logger);
+        breaker.record(url, &failure(), &logger);
+        breaker.record(url, &failure(), &logger);
+        assert_eq!(breaker.cooldown_remaining(url), None);
+
+        breaker.record(url, &failure(), &logger);
+        assert!(breaker.cooldown_remaining(url).is_some());
+        // Other hosts are unaffected
+        assert_eq!(breaker.cooldown_remaining("http://up.example.com/a"), None);
+
+        let err = breaker.admit(&reqwest::blocking::Client::new(), "http://down.example.com/b", &logger).unwrap_err();
+        assert_eq!(err.code, ErrorCode::E402);
+    }
+
+    #[test]
+    fn test_client_errors_do_not_count() {
+        let logger = Logger::new(true, false);
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        let url = "https://example.com/missing";
+
+        breaker.record(url, &Err(RuGetError::new(ErrorCode::E202, ErrorKind::Http, "HTTP 404".into())), &logger);
+        assert_eq!(breaker.cooldown_remaining(url), None);
+    }
+
+    #[test]
+    fn test_host_key() {
+        assert_eq!(host_key("https://example.com/a?b").as_deref(), Some("https://example.com:443"));
+        assert_eq!(host_key("http://example.com:8080/").as_deref(), Some("http://example.com:8080"));
+        assert_eq!(host_key("not a url"), None);
+    }
+}
@@ -1,4 +1,10 @@
-use clap::{Parser, ArgAction, ValueEnum};
+use clap::{Parser, Subcommand, ArgAction, ValueEnum};
+use crate::adaptive::parse_jobs;
+use crate::manifest::DownloadSpec;
+use crate::retry::{parse_factor, BackoffStrategy, RetryCondition};
+use crate::timeouts::parse_seconds;
+use crate::transport::TransportKind;
+use crate::async_engine::Engine;
 
 /// Log output format options
 #[derive(Clone, Debug, ValueEnum)]
@@ -9,6 +15,16 @@ pub enum LogFormat {
     Json,
 }
 
+/// Report format for --spider and --dry-run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Aligned text table
+    #[default]
+    Text,
+    /// JSON document
+    Json,
+}
+
 /// Log level options
 #[derive(Clone, Debug, ValueEnum)]
 pub enum LogLevel {
@@ -24,12 +40,15 @@ pub enum LogLevel {
 
 /// A simple wget-like tool written in Rust
 #[derive(Parser, Debug, Clone)]
-#[command(name = "ruget", version="0.1.5", about = "A simple downloader")]
+#[command(name = "ruget", version="0.1.5", about = "A simple downloader", args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// One or more URLs to fetch
-    pub urls: Vec<String>,
+    pub urls: Vec<DownloadSpec>,
 
-    /// Optional file with a list of URLs
+    /// Optional manifest of URLs (plain text with per-URL options, JSON Lines or CSV); `-` reads stdin
     #[arg(short, long)]
     pub input: Option<String>,
 
@@ -73,10 +92,18 @@ pub struct Args {
     #[arg(long)]
     pub output_dir: Option<String>,
 
-    /// Number of parallel downloads (default: number of CPUs)
-    #[arg(long, default_value = "0")]
+    /// Number of parallel downloads (default: number of CPUs), or `auto` to adapt connections per host
+    #[arg(long, default_value = "0", value_parser = parse_jobs)]
     pub jobs: usize,
 
+    /// Connections per file for servers that accept byte ranges (default: 1, or up to 16 under --jobs auto)
+    #[arg(long, value_name = "N")]
+    pub split: Option<usize>,
+
+    /// Most connections open at once across all files and hosts (0 = no cap)
+    #[arg(long, value_name = "N", default_value = "0")]
+    pub max_connections: usize,
+
     /// Path to log file for failed downloads
     #[arg(long, default_value = "rustget_failures.log")]
     pub log: String,
@@ -93,6 +120,50 @@ pub struct Args {
     #[arg(long, default_value = "60000")]
     pub backoff_max_ms: u64,
 
+    /// Multiplier applied to the backoff delay after each retry
+    #[arg(long, value_name = "F", default_value = "2.0", value_parser = parse_factor)]
+    pub backoff_factor: f64,
+
+    /// How retry delays are randomised
+    #[arg(long, value_enum, default_value = "exponential")]
+    pub backoff_strategy: BackoffStrategy,
+
+    /// Maximum number of retries across the whole run
+    #[arg(long, value_name = "N")]
+    pub retry_budget: Option<u32>,
+
+    /// Statuses (429, 5xx) and failures (timeout, connect, reset) to retry; replaces the default set
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    pub retry_on: Vec<RetryCondition>,
+
+    /// Stop sending jobs to a host after N host-level failures in a row (0 disables)
+    #[arg(long, value_name = "N", default_value = "5")]
+    pub circuit_threshold: u32,
+
+    /// Seconds a host's jobs fail fast before a probe request is sent
+    #[arg(long, value_name = "SECS", default_value = "30", value_parser = parse_seconds)]
+    pub circuit_cooldown: f64,
+
+    /// Seconds allowed to establish a connection (default: 30)
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds)]
+    pub connect_timeout: Option<f64>,
+
+    /// Seconds a transfer may go without receiving data (default: 30)
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds)]
+    pub read_timeout: Option<f64>,
+
+    /// Maximum seconds for a single transfer attempt
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds)]
+    pub max_time: Option<f64>,
+
+    /// Abort and retry transfers slower than BYTES per second over --speed-time
+    #[arg(long, value_name = "BYTES")]
+    pub speed_limit: Option<u64>,
+
+    /// Seconds over which --speed-limit is measured (default: 30)
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds)]
+    pub speed_time: Option<f64>,
+
     /// Load cookies from file
     #[arg(long)]
     pub load_cookies: Option<String>,
@@ -104,4 +175,115 @@ pub struct Args {
     /// Keep session cookies when saving
     #[arg(long)]
     pub keep_session_cookies: bool,
+
+    /// Name files after the final URL when a request is redirected
+    #[arg(long)]
+    pub trust_server_names: bool,
+
+    /// Request a compressed response (gzip, br, zstd, deflate) and decode it
+    #[arg(long)]
+    pub compressed: bool,
+
+    /// Keep the encoded bytes as sent by the server instead of decoding them
+    #[arg(long)]
+    pub raw: bool,
+
+    /// HTTP backend that carries requests
+    #[arg(long, value_enum, default_value = "auto")]
+    pub transport: TransportKind,
+
+    /// Engine that runs the batch: one thread per job, async tasks, or `auto` to choose by --jobs
+    #[arg(long, value_enum, default_value = "auto")]
+    pub engine: Engine,
+
+    /// Unpack .tar, .tar.gz, .tar.xz, .tar.zst and .zip downloads (into DIR if given)
+    #[arg(long, value_name = "DIR", num_args = 0..=1, require_equals = true)]
+    pub extract: Option<Option<String>>,
+
+    /// Strip N leading path components from archive entries when extracting
+    #[arg(long, value_name = "N", default_value = "0")]
+    pub strip_components: usize,
+
+    /// Keep the downloaded archive alongside the extracted files
+    #[arg(long)]
+    pub keep_archive: bool,
+
+    /// Command to run after each successful download ({path} and {url} are substituted)
+    #[arg(long, value_name = "CMD")]
+    pub exec: Option<String>,
+
+    /// Maximum number of --exec commands running at once
+    #[arg(long, value_name = "N", default_value = "1")]
+    pub exec_jobs: usize,
+
+    /// Command to run once the whole batch has finished
+    #[arg(long, value_name = "CMD")]
+    pub on_complete: Option<String>,
+
+    /// Command to run once the batch has finished if any download failed
+    #[arg(long, value_name = "CMD")]
+    pub on_failure: Option<String>,
+
+    /// Treat {} and [] in URLs literally instead of expanding them as globs
+    #[arg(short = 'g', long)]
+    pub globoff: bool,
+
+    /// Check that URLs exist without downloading them (HEAD, falling back to a ranged GET)
+    #[arg(long)]
+    pub spider: bool,
+
+    /// With --spider, follow links in HTML pages on the same host
+    #[arg(short = 'r', long, requires = "spider")]
+    pub recursive: bool,
+
+    /// Maximum link depth for --recursive
+    #[arg(short = 'l', long, value_name = "N", default_value = "5")]
+    pub level: usize,
+
+    /// Show what would be downloaded, resumed or skipped without writing any files
+    #[arg(long, conflicts_with = "spider")]
+    pub dry_run: bool,
+
+    /// Format of the --spider or --dry-run report
+    #[arg(long, value_enum, default_value = "text")]
+    pub report_format: ReportFormat,
+
+    /// Write the --spider or --dry-run report to FILE instead of stdout
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<String>,
+}
+
+/// Subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Run a persistent download daemon with an aria2-compatible JSON-RPC interface
+    Daemon(DaemonArgs),
+}
+
+/// Options for `ruget daemon`
+#[derive(clap::Args, Debug, Clone)]
+pub struct DaemonArgs {
+    /// Port for the JSON-RPC HTTP endpoint on 127.0.0.1
+    #[arg(long, default_value = "6800")]
+    pub rpc_listen_port: u16,
+
+    /// Secret that RPC clients must pass as "token:<secret>"
+    #[arg(long)]
+    pub rpc_secret: Option<String>,
+
+    /// Path of the JSON-RPC Unix socket (default: ~/.ruget/daemon.sock)
+    #[arg(long)]
+    pub socket: Option<String>,
+
+    /// File holding the persistent download queue (default: ~/.ruget/queue.json)
+    #[arg(long)]
+    pub state_file: Option<String>,
+
+    /// Default directory for downloads
+    #[arg(long, default_value = ".")]
+    pub dir: String,
+
+    /// Number of downloads running at the same time
+    #[arg(long, default_value = "5")]
+    pub max_concurrent_downloads: usize,
 }
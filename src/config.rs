@@ -2,6 +2,8 @@ use serde::Deserialize;
 use std::fs;
 use std::path::PathBuf;
 use crate::error::{Result, RuGetError, WithContext};
+use crate::retry::BackoffStrategy;
+use clap::ValueEnum;
 
 #[cfg(feature = "context")]
 use crate::error::AnyhowContextExt;
@@ -13,12 +15,16 @@ pub struct Config {
     pub quiet: Option<bool>,
     pub verbose: Option<bool>,
     pub jobs: Option<usize>,
+    pub split: Option<usize>,
+    pub max_connections: Option<usize>,
     pub output_dir: Option<String>,
     pub headers: Option<Vec<String>>,
     pub log: Option<String>,
+    pub compressed: Option<bool>,
     pub backoff_base_ms: Option<u64>,
     pub backoff_max_ms: Option<u64>,
     pub retry: Option<RetryConfig>,
+    pub timeouts: Option<TimeoutConfig>,
     pub logging: Option<LoggingConfig>,
 }
 
@@ -27,6 +33,19 @@ pub struct RetryConfig {
     pub max: Option<usize>,
     pub base_ms: Option<u64>,
     pub max_ms: Option<u64>,
+    pub factor: Option<f64>,
+    pub strategy: Option<String>,
+    pub budget: Option<u32>,
+    pub retry_on: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct TimeoutConfig {
+    pub connect: Option<f64>,
+    pub read: Option<f64>,
+    pub max_time: Option<f64>,
+    pub speed_limit: Option<u64>,
+    pub speed_time: Option<f64>,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -73,11 +92,26 @@ impl Config {
             if args.max_retries == 0 {
                 args.max_retries = retry_config.max.unwrap_or(3) as u32;
             }
-            if args.backoff_base_ms == 100 && retry_config.base_ms.is_some() {
-                args.backoff_base_ms = retry_config.base_ms.unwrap();
+            if args.backoff_base_ms == 100 && let Some(base_ms) = retry_config.base_ms {
+                args.backoff_base_ms = base_ms;
+            }
+            if args.backoff_max_ms == 60000 && let Some(max_ms) = retry_config.max_ms {
+                args.backoff_max_ms = max_ms;
+            }
+            if args.backoff_factor == 2.0
+                && let Some(factor) = retry_config.factor.filter(|f| f.is_finite() && *f >= 1.0)
+            {
+                args.backoff_factor = factor;
+            }
+            if args.backoff_strategy == BackoffStrategy::default()
+                && let Some(strategy) = retry_config.strategy.as_deref().and_then(|s| BackoffStrategy::from_str(s, true).ok())
+            {
+                args.backoff_strategy = strategy;
             }
-            if args.backoff_max_ms == 60000 && retry_config.max_ms.is_some() {
-                args.backoff_max_ms = retry_config.max_ms.unwrap();
+            args.retry_budget = args.retry_budget.or(retry_config.budget);
+            if args.retry_on.is_empty() {
+                // Unrecognised entries are ignored rather than failing the whole config
+                args.retry_on = retry_config.retry_on.iter().flatten().filter_map(|c| c.parse().ok()).collect();
             }
         } else {
             // Apply old fields only if new section doesn't exist
@@ -105,16 +139,35 @@ impl Config {
         if args.jobs == 0 {
             args.jobs = self.jobs.unwrap_or(0);
         }
+        if args.split.is_none() {
+            args.split = self.split;
+        }
+        if args.max_connections == 0 {
+            args.max_connections = self.max_connections.unwrap_or(0);
+        }
         if args.output_dir.is_none() {
             args.output_dir = self.output_dir.clone();
         }
         if args.headers.is_empty() {
             args.headers = self.headers.clone().unwrap_or_default();
         }
+        if !args.compressed {
+            args.compressed = self.compressed.unwrap_or(false);
+        }
         if args.log.is_empty() {
             args.log = self.log.clone().unwrap_or_else(|| "ruget_failures.log".to_string());
         }
         
+        // Timeouts given on the command line win over the config file
+        if let Some(timeouts) = &self.timeouts {
+            let positive = |secs: Option<f64>| secs.filter(|s| s.is_finite() && *s > 0.0);
+            args.connect_timeout = args.connect_timeout.or(positive(timeouts.connect));
+            args.read_timeout = args.read_timeout.or(positive(timeouts.read));
+            args.max_time = args.max_time.or(positive(timeouts.max_time));
+            args.speed_limit = args.speed_limit.or(timeouts.speed_limit);
+            args.speed_time = args.speed_time.or(positive(timeouts.speed_time));
+        }
+
         // Handle new logging config section
         if let Some(logging_config) = &self.logging {
             if args.log_format.is_none() {
@@ -0,0 +1,616 @@
+use crate::cli::{Args, DaemonArgs};
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError, WithContext};
+use crate::http::get_fallback_filename_with_content_type;
+use crate::multithreaded_download::single_threaded_download;
+use crate::output::Logger;
+use crate::probe::remote_info;
+use crate::rpc::{serve_http, RpcHandler};
+use crate::run::RunContext;
+use crate::transport::{build_transport, Transport};
+use indicatif::ProgressBar;
+use rand::Rng;
+use reqwest::cookie::Jar;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+#[cfg(feature = "context")]
+use crate::error::AnyhowContextExt;
+
+/// Lifecycle of a queued download, named as in aria2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Active,
+    Waiting,
+    Paused,
+    Error,
+    Complete,
+    Removed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Active => "active",
+            JobStatus::Waiting => "waiting",
+            JobStatus::Paused => "paused",
+            JobStatus::Error => "error",
+            JobStatus::Complete => "complete",
+            JobStatus::Removed => "removed",
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        matches!(self, JobStatus::Error | JobStatus::Complete | JobStatus::Removed)
+    }
+}
+
+/// Per-download options accepted by addUri
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobOptions {
+    pub dir: Option<String>,
+    pub out: Option<String>,
+    #[serde(default)]
+    pub headers: Vec<String>,
+}
+
+impl JobOptions {
+    /// Reject a `dir` or `out` that would place the download outside the daemon's --dir
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [("dir", &self.dir), ("out", &self.out)] {
+            if let Some(value) = value
+                && !is_contained(value)
+            {
+                return Err(RuGetError::parse(format!(
+                    "{} must be a relative path inside the download directory: {}", name, value
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether a relative path stays below the directory it is joined onto
+fn is_contained(path: &str) -> bool {
+    Path::new(path).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// A download in the persistent queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub gid: String,
+    pub uris: Vec<String>,
+    pub options: JobOptions,
+    pub status: JobStatus,
+    pub path: Option<String>,
+    pub total_length: u64,
+    pub completed_length: u64,
+    pub error_code: Option<String>,
+    pub error_message: Option<String>,
+}
+
+/// Live view of a job, including progress of an in-flight transfer
+#[derive(Debug, Clone)]
+pub struct JobSnapshot {
+    pub job: Job,
+    pub download_speed: u64,
+}
+
+/// Aggregate counters reported by getGlobalStat
+#[derive(Debug, Clone, Default)]
+pub struct GlobalStat {
+    pub download_speed: u64,
+    pub num_active: usize,
+    pub num_waiting: usize,
+    pub num_stopped: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueFile {
+    jobs: Vec<Job>,
+}
+
+/// Progress and cancellation handle for a job that a worker is running
+#[derive(Clone)]
+struct RunningJob {
+    progress: Arc<ProgressBar>,
+    cancel: Arc<AtomicBool>,
+}
+
+struct QueueState {
+    jobs: Vec<Job>,
+    running: HashMap<String, RunningJob>,
+}
+
+/// Download queue persisted to disk after every change
+pub struct DownloadQueue {
+    state_path: PathBuf,
+    state: Mutex<QueueState>,
+    wake: Condvar,
+}
+
+impl DownloadQueue {
+    /// Load the queue from disk, or start empty if no state file exists
+    ///
+    /// Jobs that were active when the daemon stopped are put back in the queue
+    /// and resume from their partial files.
+    pub fn load(state_path: PathBuf) -> Result<Self> {
+        let mut file = if state_path.exists() {
+            let content = fs::read_to_string(&state_path)
+                .with_context(|| format!("reading daemon state {}", state_path.display()))?;
+            serde_json::from_str::<QueueFile>(&content).map_err(|e| RuGetError::with_source(
+                ErrorCode::E302,
+                ErrorKind::Config,
+                format!("Invalid daemon state file {}", state_path.display()),
+                Box::new(e),
+            ))?
+        } else {
+            QueueFile::default()
+        };
+
+        for job in &mut file.jobs {
+            if job.status == JobStatus::Active {
+                job.status = JobStatus::Waiting;
+            }
+        }
+
+        Ok(Self {
+            state_path,
+            state: Mutex::new(QueueState { jobs: file.jobs, running: HashMap::new() }),
+            wake: Condvar::new(),
+        })
+    }
+
+    /// Queue a new download and return its gid
+    ///
+    /// `position` inserts the job at that index among waiting jobs, as in aria2.
+    pub fn add(&self, uris: Vec<String>, options: JobOptions, position: Option<usize>) -> Result<String> {
+        if uris.is_empty() {
+            return Err(RuGetError::parse("addUri requires at least one URI".into()));
+        }
+        options.validate()?;
+
+        let mut state = self.state.lock().unwrap();
+        let gid = loop {
+            let gid = format!("{:016x}", rand::thread_rng().r#gen::<u64>());
+            if !state.jobs.iter().any(|j| j.gid == gid) {
+                break gid;
+            }
+        };
+
+        let job = Job {
+            gid: gid.clone(),
+            uris,
+            options,
+            status: JobStatus::Waiting,
+            path: None,
+            total_length: 0,
+            completed_length: 0,
+            error_code: None,
+            error_message: None,
+        };
+
+        match position {
+            Some(pos) => {
+                let index = state.jobs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, j)| j.status == JobStatus::Waiting)
+                    .nth(pos)
+                    .map(|(i, _)| i)
+                    .unwrap_or(state.jobs.len());
+                state.jobs.insert(index, job);
+            }
+            None => state.jobs.push(job),
+        }
+
+        self.persist(&state)?;
+        self.wake.notify_all();
+        Ok(gid)
+    }
+
+    /// Pause a job; an active transfer is aborted and resumes later from its partial file
+    pub fn pause(&self, gid: &str) -> Result<()> {
+        self.transition(gid, |job| match job.status {
+            JobStatus::Active | JobStatus::Waiting => Ok(JobStatus::Paused),
+            other => Err(invalid_transition(gid, other, "paused")),
+        })
+    }
+
+    /// Put a paused job back in the queue
+    pub fn unpause(&self, gid: &str) -> Result<()> {
+        self.transition(gid, |job| match job.status {
+            JobStatus::Paused => Ok(JobStatus::Waiting),
+            other => Err(invalid_transition(gid, other, "unpaused")),
+        })
+    }
+
+    /// Remove a job, aborting it if it is running
+    pub fn remove(&self, gid: &str) -> Result<()> {
+        self.transition(gid, |job| match job.status {
+            JobStatus::Active | JobStatus::Waiting | JobStatus::Paused => Ok(JobStatus::Removed),
+            other => Err(invalid_transition(gid, other, "removed")),
+        })
+    }
+
+    fn transition<F>(&self, gid: &str, next: F) -> Result<()>
+    where
+        F: FnOnce(&Job) -> Result<JobStatus>,
+    {
+        let mut state = self.state.lock().unwrap();
+        let index = find_job(&state.jobs, gid)?;
+        let status = next(&state.jobs[index])?;
+        state.jobs[index].status = status;
+
+        // Leaving the active state stops the worker at its next read
+        if status != JobStatus::Active
+            && let Some(running) = state.running.get(gid)
+        {
+            running.cancel.store(true, Ordering::Relaxed);
+        }
+
+        self.persist(&state)?;
+        self.wake.notify_all();
+        Ok(())
+    }
+
+    /// Block until a waiting job is available, then mark it active
+    ///
+    /// A job unpaused while its previous run is still winding down stays
+    /// waiting until that run's `finish`, so two workers never share a job.
+    fn next_job(&self) -> (Job, RunningJob) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let next = state.jobs
+                .iter()
+                .position(|j| j.status == JobStatus::Waiting && !state.running.contains_key(&j.gid));
+            if let Some(index) = next {
+                state.jobs[index].status = JobStatus::Active;
+                state.jobs[index].error_code = None;
+                state.jobs[index].error_message = None;
+
+                let progress = Arc::new(ProgressBar::hidden());
+                progress.set_position(state.jobs[index].completed_length);
+                let running = RunningJob {
+                    progress,
+                    cancel: Arc::new(AtomicBool::new(false)),
+                };
+                let job = state.jobs[index].clone();
+                state.running.insert(job.gid.clone(), running.clone());
+
+                // A failed save is retried on the next change; the job still runs
+                let _ = self.persist(&state);
+                return (job, running);
+            }
+            state = self.wake.wait(state).unwrap();
+        }
+    }
+
+    /// Record the output path and size of a job once it is known
+    fn set_target(&self, gid: &str, path: &str, total_length: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Ok(index) = find_job(&state.jobs, gid) {
+            state.jobs[index].path = Some(path.to_string());
+            state.jobs[index].total_length = total_length;
+            let _ = self.persist(&state);
+        }
+    }
+
+    /// Record the outcome of a worker run
+    ///
+    /// Jobs paused or removed while running keep that status rather than
+    /// being reported as failed.
+    fn finish(&self, gid: &str, completed_length: u64, result: Result<()>) {
+        let mut state = self.state.lock().unwrap();
+        state.running.remove(gid);
+
+        if let Ok(index) = find_job(&state.jobs, gid) {
+            let job = &mut state.jobs[index];
+            job.completed_length = completed_length;
+            if job.status == JobStatus::Active {
+                match result {
+                    Ok(()) => {
+                        job.status = JobStatus::Complete;
+                        job.total_length = job.total_length.max(completed_length);
+                    }
+                    Err(e) => {
+                        job.status = JobStatus::Error;
+                        job.error_code = Some(e.code.to_string());
+                        job.error_message = Some(e.message);
+                    }
+                }
+            }
+        }
+
+        let _ = self.persist(&state);
+        self.wake.notify_all();
+    }
+
+    pub fn status(&self, gid: &str) -> Result<JobSnapshot> {
+        let state = self.state.lock().unwrap();
+        let index = find_job(&state.jobs, gid)?;
+        Ok(snapshot(&state, &state.jobs[index]))
+    }
+
+    pub fn with_status(&self, status: JobStatus) -> Vec<JobSnapshot> {
+        let state = self.state.lock().unwrap();
+        state.jobs
+            .iter()
+            .filter(|j| j.status == status)
+            .map(|j| snapshot(&state, j))
+            .collect()
+    }
+
+    pub fn stopped(&self) -> Vec<JobSnapshot> {
+        let state = self.state.lock().unwrap();
+        state.jobs
+            .iter()
+            .filter(|j| j.status.is_stopped())
+            .map(|j| snapshot(&state, j))
+            .collect()
+    }
+
+    pub fn global_stat(&self) -> GlobalStat {
+        let state = self.state.lock().unwrap();
+        let mut stat = GlobalStat::default();
+        for job in &state.jobs {
+            match job.status {
+                JobStatus::Active => {
+                    stat.num_active += 1;
+                    stat.download_speed += snapshot(&state, job).download_speed;
+                }
+                JobStatus::Waiting | JobStatus::Paused => stat.num_waiting += 1,
+                _ => stat.num_stopped += 1,
+            }
+        }
+        stat
+    }
+
+    /// Write the queue atomically so a crash never leaves a truncated state file
+    fn persist(&self, state: &QueueState) -> Result<()> {
+        let mut jobs = state.jobs.clone();
+        for job in &mut jobs {
+            if let Some(running) = state.running.get(&job.gid) {
+                job.completed_length = running.progress.position();
+            }
+        }
+
+        let content = serde_json::to_string_pretty(&QueueFile { jobs })
+            .map_err(|e| RuGetError::new(ErrorCode::E500, ErrorKind::Parse, format!("Serializing daemon state: {}", e)))?;
+
+        if let Some(parent) = self.state_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("creating state directory {}", parent.display()))?;
+        }
+        let tmp_path = self.state_path.with_extension("tmp");
+        fs::write(&tmp_path, content)
+            .with_context(|| format!("writing daemon state {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &self.state_path)
+            .with_context(|| format!("replacing daemon state {}", self.state_path.display()))
+    }
+}
+
+fn find_job(jobs: &[Job], gid: &str) -> Result<usize> {
+    jobs.iter()
+        .position(|j| j.gid == gid)
+        .ok_or_else(|| RuGetError::parse(format!("GID {} is not found", gid)))
+}
+
+fn snapshot(state: &QueueState, job: &Job) -> JobSnapshot {
+    let mut job = job.clone();
+    let mut download_speed = 0;
+    if let Some(running) = state.running.get(&job.gid) {
+        job.completed_length = running.progress.position();
+        download_speed = running.progress.per_sec() as u64;
+    }
+    JobSnapshot { job, download_speed }
+}
+
+fn invalid_transition(gid: &str, status: JobStatus, action: &str) -> RuGetError {
+    RuGetError::parse(format!("GID {} is {} and cannot be {}", gid, status.as_str(), action))
+}
+
+/// Run the daemon until the process is terminated
+pub fn run_daemon(daemon_args: DaemonArgs, base_args: Args, logger: Arc<Logger>) -> Result<()> {
+    let home = std::env::var("HOME")
+        .with_context(|| "reading HOME environment variable for daemon state".to_string())?;
+    let state_path = daemon_args.state_file.clone()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(&home).join(".ruget").join("queue.json"));
+
+    let queue = Arc::new(DownloadQueue::load(state_path.clone())?);
+    logger.info(&format!("Loaded download queue from {}", state_path.display()));
+
+    let client: Arc<dyn Transport> = Arc::from(build_transport(&base_args, Arc::new(Jar::default()), &logger)?);
+    // Retry budget and connection limits span every job the daemon runs
+    let run = Arc::new(RunContext::default());
+
+    for _ in 0..daemon_args.max_concurrent_downloads.max(1) {
+        let queue = Arc::clone(&queue);
+        let client = Arc::clone(&client);
+        let run = Arc::clone(&run);
+        let logger = Arc::clone(&logger);
+        let base_args = base_args.clone();
+        let dir = daemon_args.dir.clone();
+        thread::spawn(move || loop {
+            let (job, running) = queue.next_job();
+            let result = run_job(&queue, client.as_ref(), &base_args, &run, &dir, &job, &running, &logger);
+            if let Err(e) = &result {
+                logger.error_from_ruget_error(e);
+            }
+            queue.finish(&job.gid, running.progress.position(), result);
+        });
+    }
+
+    let handler = Arc::new(RpcHandler::new(Arc::clone(&queue), daemon_args.rpc_secret.clone()));
+
+    #[cfg(unix)]
+    {
+        let socket_path = daemon_args.socket.clone()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Path::new(&home).join(".ruget").join("daemon.sock"));
+        let handler = Arc::clone(&handler);
+        let logger = Arc::clone(&logger);
+        let listener = crate::rpc::bind_unix_socket(&socket_path)?;
+        logger.info(&format!("JSON-RPC listening on unix:{}", socket_path.display()));
+        thread::spawn(move || crate::rpc::serve_unix(listener, handler, &logger));
+    }
+
+    let addr = format!("127.0.0.1:{}", daemon_args.rpc_listen_port);
+    let listener = std::net::TcpListener::bind(&addr)
+        .with_context(|| format!("binding JSON-RPC endpoint {}", addr))?;
+    logger.info(&format!("JSON-RPC listening on http://{}/jsonrpc", addr));
+    serve_http(listener, handler, &logger);
+
+    Ok(())
+}
+
+/// Download one queued job with the regular single-connection engine
+#[allow(clippy::too_many_arguments)]
+fn run_job(
+    queue: &DownloadQueue,
+    client: &dyn Transport,
+    base_args: &Args,
+    daemon_run: &RunContext,
+    default_dir: &str,
+    job: &Job,
+    running: &RunningJob,
+    logger: &Logger,
+) -> Result<()> {
+    let mut args = base_args.clone();
+    args.urls = job.uris.iter().map(|uri| uri.as_str().into()).collect();
+    args.headers.extend(job.options.headers.iter().cloned());
+    args.resume = true;
+    args.jobs = 1;
+    // Files change between jobs, so each job probes its URIs afresh
+    let run = daemon_run.job(Arc::clone(&running.cancel));
+
+    // Jobs queued before options were validated may still carry unsafe paths
+    job.options.validate()?;
+    let dir = match &job.options.dir {
+        Some(dir) => Path::new(default_dir).join(dir),
+        None => PathBuf::from(default_dir),
+    };
+    let mut last_error = None;
+
+    // Multiple URIs are mirrors of the same file; try them in order
+    for uri in &job.uris {
+        let remote = remote_info(client, uri, &args, &run, logger).ok();
+        let file_name = job.options.out.clone()
+            .or_else(|| job.path.as_ref().and_then(|p| Path::new(p).file_name()).map(|n| n.to_string_lossy().into_owned()))
+            .or_else(|| remote.as_ref()
+                .and_then(|r| r.disposition_name.as_deref())
+                .and_then(|name| Path::new(name).file_name())
+                .map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| get_fallback_filename_with_content_type(
+                uri,
+                remote.as_ref().and_then(|r| r.content_type.as_ref()),
+            ));
+        let output_path = dir.join(file_name).to_string_lossy().into_owned();
+        let total_length = remote.as_ref().and_then(|r| r.content_length).unwrap_or(0);
+
+        queue.set_target(&job.gid, &output_path, total_length);
+        let resumed_from = fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        running.progress.set_length(total_length);
+        running.progress.set_position(resumed_from.min(total_length.max(resumed_from)));
+
+        logger.download_start(uri, &output_path);
+        match single_threaded_download(client, uri, &output_path, &args, &run, Some(Arc::clone(&running.progress)), logger) {
+            Ok(()) => {
+                logger.download_complete(&output_path);
+                return Ok(());
+            }
+            Err(e) if running.cancel.load(Ordering::Relaxed) => return Err(e),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| RuGetError::network("No URI could be downloaded".into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_queue_survives_restart() {
+        let dir = tempdir().unwrap();
+        let state_path = dir.path().join("queue.json");
+
+        let queue = DownloadQueue::load(state_path.clone()).unwrap();
+        let first = queue.add(vec!["https://example.com/a".into()], JobOptions::default(), None).unwrap();
+        let second = queue.add(vec!["https://example.com/b".into()], JobOptions::default(), None).unwrap();
+        queue.pause(&second).unwrap();
+
+        // Simulate a crash while the first job was running
+        let (job, _running) = queue.next_job();
+        assert_eq!(job.gid, first);
+        drop(queue);
+
+        let reloaded = DownloadQueue::load(state_path).unwrap();
+        assert_eq!(reloaded.status(&first).unwrap().job.status, JobStatus::Waiting);
+        assert_eq!(reloaded.status(&second).unwrap().job.status, JobStatus::Paused);
+    }
+
+    #[test]
+    fn test_position_inserts_among_waiting_jobs() {
+        let dir = tempdir().unwrap();
+        let queue = DownloadQueue::load(dir.path().join("queue.json")).unwrap();
+        queue.add(vec!["https://example.com/a".into()], JobOptions::default(), None).unwrap();
+        let urgent = queue.add(vec!["https://example.com/b".into()], JobOptions::default(), Some(0)).unwrap();
+
+        let (job, _running) = queue.next_job();
+        assert_eq!(job.gid, urgent);
+    }
+
+    #[test]
+    fn test_pause_cancels_running_job() {
+        let dir = tempdir().unwrap();
+        let queue = DownloadQueue::load(dir.path().join("queue.json")).unwrap();
+        let gid = queue.add(vec!["https://example.com/a".into()], JobOptions::default(), None).unwrap();
+
+        let (_job, running) = queue.next_job();
+        queue.pause(&gid).unwrap();
+        assert!(running.cancel.load(Ordering::Relaxed));
+
+        queue.finish(&gid, 10, Err(RuGetError::network("cancelled".into())));
+        let status = queue.status(&gid).unwrap().job;
+        assert_eq!(status.status, JobStatus::Paused);
+        assert_eq!(status.completed_length, 10);
+
+        assert!(queue.unpause(&gid).is_ok());
+        assert!(queue.unpause(&gid).is_err());
+    }
+
+    #[test]
+    fn test_unpause_waits_for_cancelled_run() {
+        let dir = tempdir().unwrap();
+        let queue = DownloadQueue::load(dir.path().join("queue.json")).unwrap();
+        let gid = queue.add(vec!["https://example.com/a".into()], JobOptions::default(), None).unwrap();
+
+        let (_job, _running) = queue.next_job();
+        queue.pause(&gid).unwrap();
+        queue.unpause(&gid).unwrap();
+
+        // The old run has not finished yet, so a second worker must skip the job
+        let other = queue.add(vec!["https://example.com/b".into()], JobOptions::default(), None).unwrap();
+        let (job, _other_running) = queue.next_job();
+        assert_eq!(job.gid, other);
+
+        // The stale run ends with its cancel error and must not fail the job
+        queue.finish(&gid, 10, Err(RuGetError::network("cancelled".into())));
+        let status = queue.status(&gid).unwrap().job;
+        assert_eq!(status.status, JobStatus::Waiting);
+        assert_eq!(status.error_code, None);
+
+        let (job, _running) = queue.next_job();
+        assert_eq!(job.gid, gid);
+        assert_eq!(job.completed_length, 10);
+    }
+}
@@ -2,189 +2,456 @@ use crate::cli::Args;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use std::{
-    collections::HashMap,
     fs::OpenOptions,
     io::Write,
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
 use reqwest::{
     blocking::Client,
     cookie::Jar,
+    header::{HeaderMap, HeaderValue, ACCEPT_ENCODING},
 };
 use crate::output::Logger;
-use crate::error::{Result, RuGetError, WithContext};
-use crate::http::{build_headers, extract_filename_from_disposition, get_fallback_filename};
-use crate::multithreaded_download::{multithreaded_download_url, single_threaded_download};
+use crate::error::{ErrorCode, Result, RuGetError, WithContext};
+use crate::http::{build_headers, get_fallback_filename_with_content_type};
+use crate::multithreaded_download::{multithreaded_download_url, single_threaded_download, STDOUT_PATH};
 use crate::cookie::{load_cookies_from_file, save_cookies_to_file};
+use crate::extract::finish_extraction;
+use crate::hooks::{BatchEvent, DownloadEvent, HookRunner};
+use crate::encoding::{configure_decompression, SUPPORTED_ENCODINGS};
+use crate::manifest::{DownloadSpec, JobStream};
+use crate::checksum::ChecksumAlgorithm;
+use crate::timeouts::Timeouts;
+use crate::retry::{RetryBudget, RetryPolicy};
+use crate::circuit::CircuitBreaker;
+use crate::scheduler::{self, file_pool};
+use crate::probe::{remote_info, RemoteInfo};
+use crate::run::RunContext;
+use crate::transport::{build_transport, Transport};
+use crate::async_engine::{self, use_async_engine};
 
 #[cfg(feature = "context")]
 use crate::error::AnyhowContextExt;
 
 
 
-pub fn download(args: Args, logger: &Logger) -> Result<()> {
+pub fn download(mut args: Args, jobs: JobStream, logger: &Logger) -> Result<()> {
     let cookie_jar = Arc::new(Jar::default());
 
+    // Encoded bytes on disk cannot be resumed or split with byte ranges of the
+    // decoded representation, so --raw --compressed always fetches whole bodies
+    let keep_encoded = args.compressed && args.raw;
+    if keep_encoded && args.resume {
+        logger.warn("--resume is not supported with --compressed --raw, downloading from scratch");
+        args.resume = false;
+    }
+    if use_async_engine(&args, logger) {
+        return async_engine::download(args, jobs, logger);
+    }
+
     // Load cookies from file if specified
     if let Some(cookie_file) = &args.load_cookies {
         load_cookies_from_file(&cookie_jar, cookie_file, logger)?;
     }
 
-    let client = Client::builder()
-        .cookie_provider(cookie_jar.clone())
-        .default_headers(build_headers(&args.headers, &logger))
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .build()
-        .with_context(|| "building HTTP client".to_string())?;
+    let transport = build_transport(&args, Arc::clone(&cookie_jar), logger)?;
+    let client = transport.as_ref();
 
-    if args.urls.len() > 1 && args.output.is_some() {
+    let known_specs = jobs.known_specs();
+    if args.output.is_some() && known_specs.as_ref().is_none_or(|specs| specs.len() > 1) {
         return Err(RuGetError::parse("Cannot use --output with multiple URLs".into()));
     }
 
-    let total_size: u64 = args
-        .urls
-        .iter()
-        .filter_map(|url| client.head(url).send().ok()?.content_length())
-        .sum();
-
-    let global_pb = if !args.quiet {
-        let pb = ProgressBar::new(total_size);
-        pb.set_style(
-            ProgressStyle::with_template("[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-                .unwrap()
-                .progress_chars("=> "),
-        );
-        Some(Arc::new(pb))
-    } else {
-        None
+    let pool = file_pool(&args)?;
+    let args = Arc::new(args);
+    let run = RunContext::default();
+
+    // Probed in parallel; the results are reused for naming, planning and resuming.
+    // The total is only known if every job's size is.
+    let total_size: Option<u64> = match known_specs {
+        Some(specs) if !args.quiet => pool.install(|| specs
+            .par_iter()
+            .map(|spec| remote_info(client, &spec.url, &job_args(&args, spec), &run, logger).ok()?.content_length)
+            .sum()),
+        _ => None,
     };
 
-    let urls = args.urls.clone();
-    let args = Arc::new(args);
-    let client = Arc::new(client);
-    let failures = Arc::new(Mutex::new(Vec::new()));
-
-    if args.jobs > 0 {
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(args.jobs)
-            .build_global()
-            .ok();
-    }
-
-    let results: Vec<_> = urls
-        .into_par_iter()
-        .map(|url| {
-            let args = Arc::clone(&args);
-            let client = Arc::clone(&client);
-            let failures = Arc::clone(&failures);
-            let global_pb = global_pb.clone();
-
-            let head_resp = client.head(&url).send().ok();
-            let suggested_name = head_resp
-                .as_ref()
-                .and_then(|resp| extract_filename_from_disposition(resp.headers().get("content-disposition")));
-
-            let final_name = suggested_name.unwrap_or_else(|| get_fallback_filename(&url));
-
-            let output_path = if let Some(ref path) = args.output {
-                path.clone()
-            } else if let Some(dir) = &args.output_dir {
-                let mut path = PathBuf::from(dir);
-                path.push(final_name);
-                path.to_string_lossy().into_owned()
-            } else {
-                final_name
-            };
+    let global_pb = batch_progress(&args, total_size);
 
-            logger.download_start(&url, &output_path);
+    let hooks = HookRunner::from_args(&args, logger);
+    let breaker = CircuitBreaker::from_args(&args);
+    let batch_start = Instant::now();
 
-            let url_clone = url.clone();
-            let result = if args.jobs > 1 {
-                multithreaded_download_url(&client, &url, &output_path, &args, global_pb.clone(), &logger)
-            } else {
-                single_threaded_download(&client, &url, &output_path, &args, global_pb.clone(), &logger)
+    let batch = Batch { client, run: &run, pb: global_pb.clone(), hooks: &hooks, breaker: breaker.as_ref() };
+    let retry_budget = RetryPolicy::from_args(&args, &run).budget;
+    let total = AtomicUsize::new(0);
+    let first_pass_successes = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+
+    // Jobs are pulled as workers free up, so streamed input starts downloading
+    // before the producer has finished. Each failure is retried and logged as
+    // soon as it happens, so nothing is held until the input ends.
+    pool.install(|| jobs
+        .filter_map(|job| match job {
+            Ok(spec) => Some(spec),
+            Err(e) => {
+                logger.error_from_ruget_error(&e);
+                None
+            }
+        })
+        .par_bridge()
+        .for_each(|spec| {
+            total.fetch_add(1, Ordering::Relaxed);
+            let args = job_args(&args, &spec);
+            let output_path = output_path_for(client, &spec, &args, &run, logger);
+
+            logger.download_start(&spec.url, &output_path);
+
+            let err = match download_one(&batch, &spec, &output_path, &args, logger) {
+                Ok(_) => {
+                    first_pass_successes.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                Err(err) => err,
             };
-            
-            match result {
-                Ok(_) => Some(url_clone),
-                Err(err) => {
-                    failures.lock().unwrap().push((url_clone, output_path.clone()));
-                    logger.error_from_ruget_error(&err);
-                    None
+            logger.error_from_ruget_error(&err);
+
+            let err = if retry_after_batch(&err, &retry_budget) {
+                match retry_download(&batch, &spec, &output_path, &args, logger) {
+                    Ok(_) => return,
+                    Err(e) => e,
                 }
+            } else {
+                err
+            };
+
+            failed.fetch_add(1, Ordering::Relaxed);
+            if let Err(e) = append_failure_log(&args.log, &[(spec.url, err.to_string())]) {
+                logger.error_from_ruget_error(&e);
             }
-        })
-        .collect();
+        }));
 
     if let Some(pb) = &global_pb {
         pb.finish_and_clear();
     }
 
-    let num_success = results.iter().filter(|r| r.is_some()).count();
-    let total = results.len();
-    logger.summary(num_success, total);
+    let total = total.into_inner();
+    logger.summary(first_pass_successes.into_inner(), total);
 
-    let failures = Arc::try_unwrap(failures).unwrap().into_inner().unwrap();
-    let mut final_failures = vec![];
-
-    for (url, output_path) in failures {
-        logger.info(&format!("Retrying: {}", url));
+    finish_batch(&args, &hooks, &cookie_jar, total, failed.into_inner(), batch_start, logger)
+}
 
-        let retry_result = if args.jobs > 1 {
-            multithreaded_download_url(&client, &url, &output_path, &args, global_pb.clone(), &logger)
-        } else {
-            single_threaded_download(&client, &url, &output_path, &args, global_pb.clone(), &logger)
-        };
-        
-        match retry_result {
-            Ok(_) => {
-                logger.info(&format!("Retry succeeded: {}", url));
-            }
-            Err(e) => {
-                let mut context = HashMap::new();
-                context.insert("url".to_string(), url.clone());
-                context.insert("attempt".to_string(), "retry".to_string());
-                
-                // Since e is already RuGetError type, use error_from_ruget_error directly
-                logger.error_from_ruget_error(&e);
-                final_failures.push((url, e.to_string()));
-            }
-        }
+/// Give a failed download its one extra attempt
+///
+/// A job skipped by an open circuit first waits out the cool-down, so that the
+/// probe gets a chance to close it.
+fn retry_download(batch: &Batch, spec: &DownloadSpec, output_path: &str, args: &Args, logger: &Logger) -> Result<()> {
+    if let Some(breaker) = batch.breaker
+        && let Some(remaining) = breaker.cooldown_remaining(&spec.url)
+    {
+        logger.info(&format!("Waiting {:.1}s for the circuit to {} to cool down", remaining.as_secs_f64(), spec.url));
+        std::thread::sleep(remaining);
     }
+    logger.info(&format!("Retrying: {}", spec.url));
 
-    if !final_failures.is_empty() {
-        let mut log = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&args.log)
-            .with_context(|| format!("opening log file {}", args.log))?;
-
-        for (url, err) in &final_failures {
-            writeln!(log, "{}\t{}", url, err)
-                .with_context(|| format!("writing failure entry to log file {}", args.log))?;
+    match download_one(batch, spec, output_path, args, logger) {
+        Ok(_) => {
+            logger.info(&format!("Retry succeeded: {}", spec.url));
+            Ok(())
         }
+        Err(e) => {
+            logger.error_from_ruget_error(&e);
+            Err(e)
+        }
+    }
+}
 
+/// Report the downloads that failed for good, wait for --exec hooks, run the batch hooks and save cookies
+///
+/// Failures are already in the --log file. Fails when every download in the batch did.
+pub fn finish_batch(
+    args: &Args,
+    hooks: &HookRunner,
+    cookie_jar: &Arc<Jar>,
+    total: usize,
+    failed: usize,
+    batch_start: Instant,
+    logger: &Logger,
+) -> Result<()> {
+    if failed > 0 {
         logger.warn(&format!(
             "{} downloads permanently failed. See {} for details.",
-            final_failures.len(),
+            failed,
             args.log
         ));
-        
-        // If all downloads failed, return an error
-        if final_failures.len() == total {
-            // Save cookies before returning error
-            if let Some(cookie_file) = &args.save_cookies {
-                save_cookies_to_file(&cookie_jar, cookie_file, args.keep_session_cookies, logger)?;
-            }
-            return Err(RuGetError::network("All downloads failed after retries".into()));
-        }
     }
 
+    // Batch hooks see the effects of every --exec hook
+    hooks.wait();
+    hooks.after_batch(&BatchEvent {
+        total,
+        succeeded: total - failed,
+        failed,
+        duration: batch_start.elapsed(),
+        failure_log: args.log.clone(),
+    }, logger);
+
     // Save cookies to file if specified
     if let Some(cookie_file) = &args.save_cookies {
-        save_cookies_to_file(&cookie_jar, cookie_file, args.keep_session_cookies, logger)?;
+        save_cookies_to_file(cookie_jar, cookie_file, args.keep_session_cookies, logger)?;
+    }
+
+    // If all downloads failed, return an error
+    if failed > 0 && failed == total {
+        return Err(RuGetError::network("All downloads failed after retries".into()));
+    }
+
+    Ok(())
+}
+
+
+/// Progress bar for a whole batch, counting bytes against `total_size` when it is known
+pub fn batch_progress(args: &Args, total_size: Option<u64>) -> Option<Arc<ProgressBar>> {
+    if args.quiet {
+        return None;
+    }
+    let pb = match total_size {
+        Some(total_size) => {
+            let pb = ProgressBar::new(total_size);
+            pb.set_style(
+                ProgressStyle::with_template("[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+                    .unwrap()
+                    .progress_chars("=> "),
+            );
+            pb
+        }
+        None => {
+            // Jobs are expanded or arrive as we go, or a server did not report a
+            // size, so there is no total to measure against
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::with_template("{spinner} {bytes} downloaded ({bytes_per_sec})").unwrap(),
+            );
+            pb.enable_steady_tick(std::time::Duration::from_millis(200));
+            pb
+        }
+    };
+    Some(Arc::new(pb))
+}
+
+/// Whether a failed download gets one more attempt after its own retries
+///
+/// A client error such as 404 will not change on another attempt, and the
+/// extra attempt also counts against --retry-budget.
+pub fn retry_after_batch(err: &RuGetError, budget: &RetryBudget) -> bool {
+    err.code != ErrorCode::E202 && budget.try_spend()
+}
+
+/// Record permanently failed downloads, one `url<TAB>error` line each, in the --log file
+pub fn append_failure_log(path: &str, failures: &[(String, String)]) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("opening log file {}", path))?;
+
+    for (url, err) in failures {
+        writeln!(log, "{}\t{}", url, err)
+            .with_context(|| format!("writing failure entry to log file {}", path))?;
+    }
+    Ok(())
+}
+
+/// Whether the body goes to stdout: a single URL with nowhere on disk to save it,
+/// and nothing that needs a file afterwards
+pub fn prints_to_stdout(args: &Args) -> bool {
+    args.output.is_none()
+        && args.output_dir.is_none()
+        && args.input.is_none()
+        && args.urls.len() == 1
+        && (args.globoff || !args.urls[0].url.contains(['{', '[']))
+        && !args.resume
+        && args.extract.is_none()
+        && args.exec.is_none()
+        && args.on_complete.is_none()
+        && args.on_failure.is_none()
+        && !args.spider
+        && !args.dry_run
+}
+
+/// Headers sent with every request of a run, whichever transport carries it
+pub fn default_headers(args: &Args, logger: &Logger) -> HeaderMap {
+    let mut headers = build_headers(&args.headers, logger);
+    if args.compressed && args.raw {
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(SUPPORTED_ENCODINGS));
+    }
+    headers
+}
+
+/// Build the reqwest client behind the reqwest transport, and behind TLS for the native one
+pub fn build_client(args: &Args, cookie_jar: Arc<Jar>, logger: &Logger) -> Result<Client> {
+    let builder = configure_decompression(Client::builder(), args.compressed && !args.raw);
+    Timeouts::from_args(args).apply(builder)
+        .cookie_provider(cookie_jar)
+        .default_headers(default_headers(args, logger))
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .build()
+        .with_context(|| "building HTTP client".to_string())
+}
+
+/// Arguments for one job: the shared options plus any headers from its manifest entry
+pub fn job_args(args: &Arc<Args>, spec: &DownloadSpec) -> Arc<Args> {
+    if spec.headers.is_empty() {
+        return Arc::clone(args);
+    }
+    let mut job_args = Args::clone(args);
+    job_args.headers.extend(spec.headers.iter().cloned());
+    Arc::new(job_args)
+}
+
+/// Where a job is saved: --output, else the entry's name or the server's, under its directory
+pub fn output_path_for(client: &dyn Transport, spec: &DownloadSpec, args: &Args, run: &RunContext, logger: &Logger) -> String {
+    if prints_to_stdout(args) {
+        return STDOUT_PATH.to_string();
+    }
+    if let Some(path) = known_output_path(spec, args) {
+        return path;
+    }
+    let remote = remote_info(client, &spec.url, args, run, logger).ok();
+    in_output_dir(spec, args, &server_file_name(spec, remote.as_ref(), args))
+}
+
+/// Where a job is saved when the server does not need to be asked: --output or the entry's name
+pub fn known_output_path(spec: &DownloadSpec, args: &Args) -> Option<String> {
+    if let Some(path) = &args.output {
+        return Some(path.clone());
+    }
+    spec.out.as_ref().map(|name| in_output_dir(spec, args, name))
+}
+
+/// `name` under the entry's directory or --output-dir
+pub fn in_output_dir(spec: &DownloadSpec, args: &Args, name: &str) -> String {
+    match spec.dir.as_ref().or(args.output_dir.as_ref()) {
+        Some(dir) => PathBuf::from(dir).join(name).to_string_lossy().into_owned(),
+        None => name.to_string(),
+    }
+}
+
+/// Work out a file name from what the server said about the file, if anything
+pub fn server_file_name(spec: &DownloadSpec, remote: Option<&RemoteInfo>, args: &Args) -> String {
+    if let Some(name) = remote.and_then(|r| r.disposition_name.clone()) {
+        return name;
+    }
+
+    // With --trust-server-names the name comes from the URL we were redirected to
+    let name_url = match (remote.and_then(|r| r.final_url.as_deref()), args.trust_server_names) {
+        (Some(final_url), true) => final_url,
+        _ => spec.url.as_str(),
+    };
+    get_fallback_filename_with_content_type(name_url, remote.and_then(|r| r.content_type.as_ref()))
+}
+
+/// State shared by every job of one `download()` run
+struct Batch<'a> {
+    client: &'a dyn Transport,
+    run: &'a RunContext,
+    pb: Option<Arc<ProgressBar>>,
+    hooks: &'a HookRunner,
+    breaker: Option<&'a CircuitBreaker>,
+}
+
+/// Download a single job with the configured strategy, then run post-download steps
+///
+/// Mirrors are tried in order when the primary URL fails.
+fn download_one(
+    batch: &Batch,
+    spec: &DownloadSpec,
+    output_path: &str,
+    args: &Args,
+    logger: &Logger,
+) -> Result<()> {
+    let Batch { run, pb, hooks, breaker, .. } = batch;
+    let client = batch.client;
+    let start = Instant::now();
+
+    let mut result = Err(RuGetError::network(format!("No URL to download for {}", output_path)));
+    let mut source = spec.url.as_str();
+    for (attempt, url) in spec.sources().enumerate() {
+        if attempt > 0 {
+            logger.warn(&format!("Trying mirror {} for {}", url, output_path));
+        }
+        source = url;
+        if let Some(breaker) = breaker
+            && let Err(e) = breaker.admit(client, url, logger)
+        {
+            result = Err(e);
+            continue;
+        }
+        // Chunks cannot be written out of order to stdout
+        result = if scheduler::split(args) > 1 && output_path != STDOUT_PATH {
+            multithreaded_download_url(client, url, output_path, args, run, pb.clone(), logger)
+        } else {
+            single_threaded_download(client, url, output_path, args, run, pb.clone(), logger)
+        };
+        if let Some(breaker) = breaker {
+            breaker.record(url, &result, logger);
+        }
+        if result.is_ok() {
+            break;
+        }
+    }
+    for url in spec.sources() {
+        run.probes.remove(url, &args.headers);
     }
+    result?;
+
+    let sha256 = verify_checksum(spec, output_path, logger)?;
+
+    finish_extraction(output_path, args, logger)?;
+
+    hooks.after_download(&DownloadEvent {
+        url: source.to_string(),
+        path: output_path.to_string(),
+        size: std::fs::metadata(output_path).map(|m| m.len()).unwrap_or(0),
+        duration: start.elapsed(),
+        sha256,
+    }, logger);
 
     Ok(())
 }
+
+/// Check a finished download against the checksum from its manifest entry
+///
+/// A mismatching file is removed so that a retry starts from scratch.
+/// Returns the digest when it is a SHA-256, for use by --exec hooks.
+pub fn verify_checksum(spec: &DownloadSpec, output_path: &str, logger: &Logger) -> Result<Option<String>> {
+    let Some(checksum) = &spec.checksum else {
+        return Ok(None);
+    };
+
+    let path = std::path::Path::new(output_path);
+    if !path.exists() {
+        // Archives extracted while streaming are never written to disk
+        logger.warn(&format!("Cannot verify checksum of {}: file was not kept (use --keep-archive)", output_path));
+        return Ok(None);
+    }
+
+    match checksum.verify_file(path) {
+        Ok(digest) => {
+            logger.verbose(&format!("Checksum verified for {}: {}", output_path, checksum));
+            Ok((checksum.algorithm == ChecksumAlgorithm::Sha256).then_some(digest))
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(path);
+            Err(e)
+        }
+    }
+}
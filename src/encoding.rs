@@ -0,0 +1,133 @@
+use reqwest::blocking::ClientBuilder;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING};
+use std::io::Read;
+use crate::error::{Result, RuGetError};
+
+/// Accept-Encoding value advertised with --compressed
+pub const SUPPORTED_ENCODINGS: &str = "gzip, br, zstd, deflate";
+
+/// Content codings RuGet knows how to decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Identity,
+    Gzip,
+    Brotli,
+    Zstd,
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Parse a single Content-Encoding token
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => Ok(ContentEncoding::Identity),
+            "gzip" | "x-gzip" => Ok(ContentEncoding::Gzip),
+            "br" => Ok(ContentEncoding::Brotli),
+            "zstd" => Ok(ContentEncoding::Zstd),
+            "deflate" => Ok(ContentEncoding::Deflate),
+            other => Err(RuGetError::parse(format!("Unsupported content encoding: {}", other))),
+        }
+    }
+
+    /// Read the Content-Encoding of a response from its headers
+    ///
+    /// Only a single coding is supported; stacked codings such as `gzip, br` are rejected.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self> {
+        match headers.get(CONTENT_ENCODING).and_then(|v| v.to_str().ok()) {
+            Some(value) if value.contains(',') => Err(RuGetError::parse(format!(
+                "Unsupported stacked content encoding: {}", value
+            ))),
+            Some(value) => Self::parse(value),
+            None => Ok(ContentEncoding::Identity),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == ContentEncoding::Identity
+    }
+}
+
+/// Wrap a reader so that the body is decoded as it streams through
+pub fn decoding_reader<'a, R: Read + 'a>(encoding: ContentEncoding, reader: R) -> Box<dyn Read + 'a> {
+    match encoding {
+        ContentEncoding::Identity => Box::new(reader),
+        ContentEncoding::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        ContentEncoding::Deflate => Box::new(flate2::read::ZlibDecoder::new(reader)),
+        ContentEncoding::Brotli => Box::new(brotli_decompressor::Decompressor::new(reader, 64 * 1024)),
+        ContentEncoding::Zstd => match zstd::stream::read::Decoder::new(reader) {
+            Ok(decoder) => Box::new(decoder),
+            Err(e) => Box::new(FailingReader(Some(e))),
+        },
+    }
+}
+
+/// Reader that reports a decoder setup failure on first read
+struct FailingReader(Option<std::io::Error>);
+
+impl Read for FailingReader {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        Err(self.0.take().unwrap_or_else(|| std::io::Error::other("decoder unavailable")))
+    }
+}
+
+/// Configure transparent decompression on a reqwest client
+///
+/// With `decode` the client advertises all supported codings and decodes
+/// bodies as they stream. Otherwise no Accept-Encoding is sent by reqwest and
+/// bodies are passed through untouched.
+pub fn configure_decompression(builder: ClientBuilder, decode: bool) -> ClientBuilder {
+    builder
+        .gzip(decode)
+        .brotli(decode)
+        .zstd(decode)
+        .deflate(decode)
+}
+
+/// Header value used to request the unencoded representation, so lengths and
+/// ranges refer to the bytes that end up on disk
+pub fn identity_encoding() -> (reqwest::header::HeaderName, HeaderValue) {
+    (ACCEPT_ENCODING, HeaderValue::from_static("identity"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_content_encoding() {
+        assert_eq!(ContentEncoding::parse("gzip").unwrap(), ContentEncoding::Gzip);
+        assert_eq!(ContentEncoding::parse(" BR ").unwrap(), ContentEncoding::Brotli);
+        assert_eq!(ContentEncoding::parse("identity").unwrap(), ContentEncoding::Identity);
+        assert!(ContentEncoding::parse("compress").is_err());
+    }
+
+    #[test]
+    fn test_from_headers_rejects_stacked_encodings() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ContentEncoding::from_headers(&headers).unwrap(), ContentEncoding::Identity);
+
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip, br"));
+        assert!(ContentEncoding::from_headers(&headers).is_err());
+    }
+
+    fn decode(encoding: ContentEncoding, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        decoding_reader(encoding, body).read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_decode_gzip_and_zstd_bodies() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello gzip").unwrap();
+        let gz = encoder.finish().unwrap();
+        assert_eq!(decode(ContentEncoding::Gzip, &gz).unwrap(), b"hello gzip");
+
+        let zst = zstd::stream::encode_all(&b"hello zstd"[..], 0).unwrap();
+        assert_eq!(decode(ContentEncoding::Zstd, &zst).unwrap(), b"hello zstd");
+
+        assert!(decode(ContentEncoding::Gzip, b"not gzip").is_err());
+    }
+}
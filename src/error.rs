@@ -162,7 +162,7 @@ impl ErrorCode {
             
             // E2xx: HTTP errors
             ErrorCode::E200 => "Check URL validity and server status",
-            ErrorCode::E201 => "Check internet connection or use --timeout option",
+            ErrorCode::E201 => "Check internet connection or raise --connect-timeout",
             ErrorCode::E202 => "Verify URL and request parameters",
             ErrorCode::E203 => "Server is experiencing issues, try again later",
             ErrorCode::E204 => "Check URL format and protocol",
@@ -203,6 +203,31 @@ impl std::error::Error for RuGetError {
 
 impl From<std::io::Error> for RuGetError {
     fn from(err: std::io::Error) -> Self {
+        // Socket read timeouts surface as TimedOut or WouldBlock, and reqwest
+        // wraps its own read timeouts in an io::Error when bodies are read
+        let body_error = err.get_ref().and_then(|inner| inner.downcast_ref::<reqwest::Error>());
+        let from_body = body_error.is_some();
+        let timed_out = matches!(err.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)
+            || body_error.is_some_and(|inner| inner.is_timeout());
+        if timed_out {
+            return RuGetError {
+                code: ErrorCode::E404,
+                kind: ErrorKind::Network,
+                message: format!("Timed out waiting for data: {}", err),
+                source: Some(Box::new(err)),
+            };
+        }
+
+        // A connection lost while a body was read is a network failure, not a local one
+        if from_body {
+            return RuGetError {
+                code: ErrorCode::E400,
+                kind: ErrorKind::Network,
+                message: format!("Connection lost while reading data: {}", err),
+                source: Some(Box::new(err)),
+            };
+        }
+
         RuGetError {
             code: ErrorCode::E100,
             kind: ErrorKind::Io,
@@ -214,10 +239,15 @@ impl From<std::io::Error> for RuGetError {
 
 impl From<reqwest::Error> for RuGetError {
     fn from(err: reqwest::Error) -> Self {
+        let (code, kind, message) = match (err.is_timeout(), err.is_connect()) {
+            (true, true) => (ErrorCode::E201, ErrorKind::Network, format!("Connection timed out: {}", err)),
+            (true, false) => (ErrorCode::E404, ErrorKind::Network, format!("Request timed out: {}", err)),
+            _ => (ErrorCode::E200, ErrorKind::Http, format!("HTTP error: {}", err)),
+        };
         RuGetError {
-            code: ErrorCode::E200,
-            kind: ErrorKind::Http,
-            message: format!("HTTP error: {}", err),
+            code,
+            kind,
+            message,
             source: Some(Box::new(err)),
         }
     }
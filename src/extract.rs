@@ -0,0 +1,544 @@
+use crate::cli::Args;
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError, WithContext};
+use crate::output::Logger;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, PipeWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+#[cfg(feature = "context")]
+use crate::error::AnyhowContextExt;
+
+/// Archive formats supported by --extract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Detect the archive format from a file name
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Some(ArchiveFormat::TarXz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(ArchiveFormat::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the archive can be unpacked while the body is still downloading
+    ///
+    /// Zip keeps its index at the end of the file, so it is extracted after download.
+    pub fn is_streamable(&self) -> bool {
+        !matches!(self, ArchiveFormat::Zip)
+    }
+}
+
+/// Where and how to unpack an archive
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    pub dest: PathBuf,
+    pub strip_components: usize,
+}
+
+impl ExtractOptions {
+    /// Build extraction options for a download, or None when --extract was not given
+    ///
+    /// Without an explicit directory, archives are unpacked next to where the
+    /// archive itself would be saved.
+    pub fn from_args(args: &Args, output_path: &str) -> Option<Self> {
+        let dir = args.extract.as_ref()?;
+        let dest = match dir {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(output_path)
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from(".")),
+        };
+
+        Some(Self {
+            dest,
+            strip_components: args.strip_components,
+        })
+    }
+}
+
+/// Archive format to unpack while streaming, if --extract applies to this download
+pub fn streaming_format(args: &Args, output_path: &str) -> Option<ArchiveFormat> {
+    args.extract.as_ref()?;
+    ArchiveFormat::from_name(output_path).filter(ArchiveFormat::is_streamable)
+}
+
+/// Unpacks a tar stream on a background thread as bytes are written into it
+pub struct StreamingExtractor {
+    writer: Option<PipeWriter>,
+    handle: JoinHandle<Result<usize>>,
+}
+
+impl StreamingExtractor {
+    pub fn spawn(format: ArchiveFormat, options: ExtractOptions) -> Result<Self> {
+        let (reader, writer) = io::pipe()
+            .with_context(|| "creating extraction pipe".to_string())?;
+
+        // When extraction fails the thread drops the reader, so the downloader's
+        // next write fails with BrokenPipe instead of blocking on a full pipe
+        let handle = thread::spawn(move || extract_tar(format, reader, &options));
+
+        Ok(Self {
+            writer: Some(writer),
+            handle,
+        })
+    }
+
+    /// Signal end of the body and wait for extraction to complete
+    ///
+    /// Returns the number of entries unpacked.
+    pub fn finish(mut self) -> Result<usize> {
+        drop(self.writer.take());
+        self.join()
+    }
+
+    fn join(self) -> Result<usize> {
+        self.handle
+            .join()
+            .map_err(|_| RuGetError::new(ErrorCode::E500, ErrorKind::FileSystem, "Extraction thread panicked".to_string()))?
+    }
+}
+
+impl Write for StreamingExtractor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.write(buf),
+            None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "extractor already finished")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Surface the extractor's own error when a write into the pipe fails
+///
+/// A broken pipe only means the extraction thread stopped reading; the reason
+/// it stopped is the useful error.
+pub fn extractor_write_error(extractor: StreamingExtractor, err: io::Error) -> RuGetError {
+    match extractor.finish() {
+        Err(extract_err) => extract_err,
+        Ok(_) => RuGetError::from(err).with_context("writing to archive extractor"),
+    }
+}
+
+/// Unpack a tar archive, decoding the outer compression as it streams
+pub fn extract_tar<R: Read>(format: ArchiveFormat, reader: R, options: &ExtractOptions) -> Result<usize> {
+    let decoded: Box<dyn Read + '_> = match format {
+        ArchiveFormat::Tar => Box::new(reader),
+        ArchiveFormat::TarGz => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        ArchiveFormat::TarXz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        ArchiveFormat::TarZst => Box::new(
+            zstd::stream::read::Decoder::new(reader)
+                .with_context(|| "initialising zstd decoder".to_string())?,
+        ),
+        ArchiveFormat::Zip => {
+            return Err(RuGetError::parse("Zip archives cannot be extracted from a stream".into()));
+        }
+    };
+
+    fs::create_dir_all(&options.dest)
+        .with_context(|| format!("creating extraction directory {}", options.dest.display()))?;
+    let root = options.dest.canonicalize()
+        .with_context(|| format!("resolving extraction directory {}", options.dest.display()))?;
+
+    let mut archive = tar::Archive::new(decoded);
+    let mut links = HashSet::new();
+    let mut count = 0;
+
+    for entry in archive.entries().with_context(|| "reading tar archive".to_string())? {
+        let mut entry = entry.with_context(|| "reading tar entry".to_string())?;
+        let entry_path = entry.path()
+            .with_context(|| "reading tar entry path".to_string())?
+            .into_owned();
+
+        let relative = match safe_relative_path(&entry_path, options.strip_components)? {
+            Some(relative) => relative,
+            None => continue,
+        };
+        check_not_through_link(&entry_path, &relative, &links)?;
+        let target = root.join(&relative);
+        let entry_type = entry.header().entry_type();
+
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let link = entry.link_name()
+                .with_context(|| format!("reading link target of {}", entry_path.display()))?
+                .ok_or_else(|| unsafe_entry(&entry_path, "link without a target"))?;
+            // Hard link targets are archive paths; symlink targets are relative to the link
+            let link_base = if entry_type.is_hard_link() {
+                Path::new("")
+            } else {
+                relative.parent().unwrap_or(Path::new(""))
+            };
+            if escapes_root(link_base, &link, &links) {
+                return Err(unsafe_entry(&entry_path, "link points outside the extraction directory"));
+            }
+            if entry_type.is_hard_link() {
+                // Hard links are resolved against the extraction root after stripping
+                let link_relative = safe_relative_path(&link, options.strip_components)?
+                    .ok_or_else(|| unsafe_entry(&entry_path, "hard link target was stripped"))?;
+                check_not_through_link(&entry_path, &link_relative, &links)?;
+                ensure_parent_within(&root, &target)?;
+                let _ = fs::remove_file(&target);
+                fs::hard_link(root.join(link_relative), &target)
+                    .with_context(|| format!("creating hard link {}", target.display()))?;
+                count += 1;
+                continue;
+            }
+        } else if !(entry_type.is_file() || entry_type.is_dir() || entry_type == tar::EntryType::Continuous) {
+            // Devices, FIFOs and extension headers are never materialised
+            continue;
+        }
+
+        ensure_parent_within(&root, &target)?;
+        entry.unpack(&target)
+            .with_context(|| format!("unpacking {}", target.display()))?;
+        if entry_type.is_symlink() {
+            links.insert(relative);
+        }
+        count += 1;
+    }
+
+    // Consume trailing padding so the writer side never sees a closed pipe
+    io::copy(&mut archive.into_inner(), &mut io::sink())
+        .with_context(|| "reading end of tar archive".to_string())?;
+
+    Ok(count)
+}
+
+/// Run the post-download part of --extract for a finished download
+///
+/// Zip archives are unpacked here; streamed tar archives were already unpacked
+/// while downloading. The archive is removed afterwards unless --keep-archive.
+pub fn finish_extraction(output_path: &str, args: &Args, logger: &Logger) -> Result<()> {
+    let options = match ExtractOptions::from_args(args, output_path) {
+        Some(options) => options,
+        None => return Ok(()),
+    };
+
+    match ArchiveFormat::from_name(output_path) {
+        Some(ArchiveFormat::Zip) => {
+            let count = extract_zip(output_path, &options)?;
+            logger.info(&format!(
+                "Extracted {} entries from {} into {}", count, output_path, options.dest.display()
+            ));
+            if !args.keep_archive {
+                fs::remove_file(output_path)
+                    .with_context(|| format!("removing archive {}", output_path))?;
+            }
+        }
+        Some(_) => {}
+        None => {
+            logger.warn(&format!("{} is not a supported archive, leaving it unextracted", output_path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Unpack a zip archive that has already been downloaded
+pub fn extract_zip(archive_path: &str, options: &ExtractOptions) -> Result<usize> {
+    let file = File::open(archive_path)
+        .with_context(|| format!("opening zip archive {}", archive_path))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| RuGetError::with_source(
+            ErrorCode::E504,
+            ErrorKind::Parse,
+            format!("Invalid zip archive {}", archive_path),
+            Box::new(e),
+        ))?;
+
+    fs::create_dir_all(&options.dest)
+        .with_context(|| format!("creating extraction directory {}", options.dest.display()))?;
+    let root = options.dest.canonicalize()
+        .with_context(|| format!("resolving extraction directory {}", options.dest.display()))?;
+
+    let mut links = HashSet::new();
+    let mut count = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)
+            .map_err(|e| RuGetError::with_source(
+                ErrorCode::E504,
+                ErrorKind::Parse,
+                format!("Reading entry {} of {}", i, archive_path),
+                Box::new(e),
+            ))?;
+
+        let entry_path = PathBuf::from(entry.name());
+        let relative = match safe_relative_path(&entry_path, options.strip_components)? {
+            Some(relative) => relative,
+            None => continue,
+        };
+        check_not_through_link(&entry_path, &relative, &links)?;
+        let target = root.join(&relative);
+
+        if entry.is_dir() {
+            ensure_parent_within(&root, &target)?;
+            fs::create_dir_all(&target)
+                .with_context(|| format!("creating directory {}", target.display()))?;
+        } else if entry.is_symlink() {
+            let mut link = String::new();
+            entry.read_to_string(&mut link)
+                .with_context(|| format!("reading link target of {}", entry_path.display()))?;
+            let link_base = relative.parent().unwrap_or(Path::new(""));
+            if escapes_root(link_base, Path::new(&link), &links) {
+                return Err(unsafe_entry(&entry_path, "link points outside the extraction directory"));
+            }
+            ensure_parent_within(&root, &target)?;
+            create_symlink(Path::new(&link), &target)?;
+            links.insert(relative);
+        } else {
+            ensure_parent_within(&root, &target)?;
+            let mut out = File::create(&target)
+                .with_context(|| format!("creating {}", target.display()))?;
+            io::copy(&mut entry, &mut out)
+                .with_context(|| format!("unpacking {}", target.display()))?;
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Validate an archive entry path and apply --strip-components
+///
+/// Returns None for entries that are stripped away entirely.
+fn safe_relative_path(path: &Path, strip_components: usize) -> Result<Option<PathBuf>> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(unsafe_entry(path, "path escapes the extraction directory"));
+            }
+        }
+    }
+
+    if parts.len() <= strip_components {
+        return Ok(None);
+    }
+
+    Ok(Some(parts[strip_components..].iter().collect()))
+}
+
+/// Whether a link target, resolved lexically from `base`, leaves the root
+///
+/// Symlinks created earlier in the same archive are not followed lexically, so
+/// a target that passes through one of them counts as escaping.
+fn escapes_root(base: &Path, link: &Path, links: &HashSet<PathBuf>) -> bool {
+    if link.has_root() {
+        return true;
+    }
+
+    let mut resolved: PathBuf = base.components().filter(|c| matches!(c, Component::Normal(_))).collect();
+    for component in link.components() {
+        if links.contains(&resolved) {
+            return true;
+        }
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return true;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return true,
+        }
+    }
+    false
+}
+
+/// Refuse entries whose path runs through a symlink unpacked from the same archive
+fn check_not_through_link(entry_path: &Path, relative: &Path, links: &HashSet<PathBuf>) -> Result<()> {
+    if relative.ancestors().skip(1).any(|ancestor| links.contains(ancestor)) {
+        return Err(unsafe_entry(entry_path, "path runs through a symlink in the archive"));
+    }
+    Ok(())
+}
+
+/// Create the parent of `target` and make sure it physically resolves inside
+/// the root, so a symlink unpacked earlier cannot redirect later entries
+fn ensure_parent_within(root: &Path, target: &Path) -> Result<()> {
+    let parent = target.parent().unwrap_or(root);
+    fs::create_dir_all(parent)
+        .with_context(|| format!("creating directory {}", parent.display()))?;
+    let resolved = parent.canonicalize()
+        .with_context(|| format!("resolving directory {}", parent.display()))?;
+
+    if !resolved.starts_with(root) {
+        return Err(unsafe_entry(target, "path escapes the extraction directory through a symlink"));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(link: &Path, target: &Path) -> Result<()> {
+    let _ = fs::remove_file(target);
+    std::os::unix::fs::symlink(link, target)
+        .with_context(|| format!("creating symlink {}", target.display()))
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_link: &Path, target: &Path) -> Result<()> {
+    Err(RuGetError::file_system(format!(
+        "Symlinks are not supported on this platform: {}", target.display()
+    )))
+}
+
+fn unsafe_entry(path: &Path, reason: &str) -> RuGetError {
+    RuGetError::new(
+        ErrorCode::E504,
+        ErrorKind::FileSystem,
+        format!("Refusing to extract {}: {}", path.display(), reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn tar_with(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_archive_format_from_name() {
+        assert_eq!(ArchiveFormat::from_name("data.tar.gz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_name("DATA.TGZ"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_name("data.tar.xz"), Some(ArchiveFormat::TarXz));
+        assert_eq!(ArchiveFormat::from_name("data.tar.zst"), Some(ArchiveFormat::TarZst));
+        assert_eq!(ArchiveFormat::from_name("data.tar"), Some(ArchiveFormat::Tar));
+        assert_eq!(ArchiveFormat::from_name("data.zip"), Some(ArchiveFormat::Zip));
+        assert_eq!(ArchiveFormat::from_name("data.nc"), None);
+    }
+
+    #[test]
+    fn test_safe_relative_path() {
+        assert_eq!(
+            safe_relative_path(Path::new("pkg-1.0/src/main.rs"), 1).unwrap(),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        assert_eq!(safe_relative_path(Path::new("pkg-1.0/"), 1).unwrap(), None);
+        assert!(safe_relative_path(Path::new("../etc/passwd"), 0).is_err());
+        assert!(safe_relative_path(Path::new("/etc/passwd"), 0).is_err());
+    }
+
+    #[test]
+    fn test_escapes_root() {
+        let links = HashSet::new();
+        assert!(!escapes_root(Path::new("a/b"), Path::new("../c"), &links));
+        assert!(escapes_root(Path::new("a"), Path::new("../../c"), &links));
+        assert!(escapes_root(Path::new(""), Path::new("/etc/passwd"), &links));
+
+        // a/l -> .. makes "a/l/.." the parent of the root on disk
+        let links = HashSet::from([PathBuf::from("a/l")]);
+        assert!(escapes_root(Path::new(""), Path::new("a/l/.."), &links));
+        assert!(!escapes_root(Path::new(""), Path::new("a/l"), &links));
+    }
+
+    #[test]
+    fn test_extract_tar_with_strip_components() {
+        let dir = tempdir().unwrap();
+        let data = tar_with(&[("pkg/README", b"hello"), ("pkg/src/lib.rs", b"fn main() {}")]);
+        let options = ExtractOptions { dest: dir.path().to_path_buf(), strip_components: 1 };
+
+        let count = extract_tar(ArchiveFormat::Tar, data.as_slice(), &options).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(fs::read(dir.path().join("README")).unwrap(), b"hello");
+        assert_eq!(fs::read(dir.path().join("src/lib.rs")).unwrap(), b"fn main() {}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_tar_rejects_symlink_escape() {
+        let dir = tempdir().unwrap();
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_cksum();
+        builder.append_link(&mut header, "escape", "../../outside").unwrap();
+        let data = builder.into_inner().unwrap();
+
+        let options = ExtractOptions { dest: dir.path().join("out"), strip_components: 0 };
+        let err = extract_tar(ArchiveFormat::Tar, data.as_slice(), &options).unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::E504);
+        assert!(!dir.path().join("out/escape").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_tar_rejects_symlink_chain_escape() {
+        let dir = tempdir().unwrap();
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, target) in [("a/l", ".."), ("a/l/x", "../..")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            header.set_cksum();
+            builder.append_link(&mut header, name, target).unwrap();
+        }
+        let data = builder.into_inner().unwrap();
+
+        let options = ExtractOptions { dest: dir.path().join("out"), strip_components: 0 };
+        let err = extract_tar(ArchiveFormat::Tar, data.as_slice(), &options).unwrap_err();
+
+        assert_eq!(err.code, ErrorCode::E504);
+        assert!(fs::symlink_metadata(dir.path().join("out/x")).is_err());
+    }
+
+    #[test]
+    fn test_streaming_extractor_unpacks_gzip() {
+        let dir = tempdir().unwrap();
+        let data = tar_with(&[("file.txt", b"streamed")]);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&data).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        let options = ExtractOptions { dest: dir.path().to_path_buf(), strip_components: 0 };
+        let mut extractor = StreamingExtractor::spawn(ArchiveFormat::TarGz, options).unwrap();
+        for chunk in gz.chunks(7) {
+            extractor.write_all(chunk).unwrap();
+        }
+
+        assert_eq!(extractor.finish().unwrap(), 1);
+        assert_eq!(fs::read(dir.path().join("file.txt")).unwrap(), b"streamed");
+    }
+}
@@ -1,103 +1,87 @@
 use crate::cli::Args;
-use crate::error::{Result, RuGetError};
-use std::fs::File;
-use std::io::{self, Write};
-use std::path::Path;
+use crate::download::{append_failure_log, prints_to_stdout, retry_after_batch};
+use crate::error::Result;
+use crate::multithreaded_download::{single_threaded_download, STDOUT_PATH};
+use crate::output::Logger;
+use crate::retry::RetryPolicy;
+use crate::run::RunContext;
+use crate::scheduler;
+use crate::transport::build_transport;
+use reqwest::cookie::Jar;
+use std::sync::Arc;
 
-/// Ultra-fast download for single files using minimal dependencies
-/// This bypasses most of the heavy infrastructure for simple downloads
-pub fn fast_single_download(url: &str, output_path: Option<&str>, quiet: bool) -> Result<()> {
-    // Check if we can use the fast path
-    if !is_simple_http_url(url) {
-        return Err(RuGetError::network("Fast path only supports simple HTTP/HTTPS URLs".into()));
-    }
+/// Download a single URL without the batch machinery: no size probe, progress
+/// bar, worker pool, circuit breaker or hooks
+///
+/// The transfer itself is the full downloader's, so naming, status checks,
+/// redirects, retries and overwriting behave exactly as they do there.
+pub fn fast_single_download(args: &Args, logger: &Logger) -> Result<()> {
+    let url = &args.urls[0].url;
+    let output_path = args.output.as_deref().unwrap_or(STDOUT_PATH);
+    let transport = build_transport(args, Arc::new(Jar::default()), logger)?;
+    let client = transport.as_ref();
+    let run = RunContext::default();
 
-    match output_path {
-        Some(path) => {
-            // Try ultimate mode first (native HTTP + SIMD + caching) for file output
-            if crate::ultimate_fast::should_use_ultimate_mode(url, path) {
-                crate::ultimate_fast::ultimate_download(url, path)?;
-                if !quiet {
-                    println!("Downloaded {} to {}", url, path);
-                }
-                return Ok(());
-            }
-            
-            // Fall back to ultra-fast mode for file output
-            if crate::ultra_fast::can_use_ultra_fast(url, path) {
-                crate::ultra_fast::ultra_fast_download(url, path)?;
-                if !quiet {
-                    println!("Downloaded {} to {}", url, path);
-                }
-                return Ok(());
-            }
-            
-            // Create output directory if needed
-            if let Some(parent) = Path::new(path).parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+    let mut result = single_threaded_download(client, url, output_path, args, &run, None, logger);
+    // The same second attempt a failed job gets at the end of a batch
+    if let Err(e) = &result
+        && retry_after_batch(e, &RetryPolicy::from_args(args, &run).budget)
+    {
+        logger.error_from_ruget_error(e);
+        logger.info(&format!("Retrying: {}", url));
+        result = single_threaded_download(client, url, output_path, args, &run, None, logger);
+    }
 
-            // Use a minimal HTTP client and write to file
-            let response = simple_http_get(url)?;
-            let mut file = File::create(path)?;
-            file.write_all(&response)?;
-            
-            if !quiet {
-                println!("Downloaded {} to {}", url, path);
+    match result {
+        Ok(()) => {
+            if output_path != STDOUT_PATH {
+                logger.download_complete(output_path);
             }
+            Ok(())
         }
-        None => {
-            // Output to stdout - use minimal HTTP client
-            let response = simple_http_get(url)?;
-            io::stdout().write_all(&response)?;
-            io::stdout().flush()?;
+        Err(e) => {
+            append_failure_log(&args.log, &[(url.clone(), e.to_string())])?;
+            Err(e)
         }
     }
-    
-    Ok(())
-}
-
-/// Check if URL is suitable for fast path
-fn is_simple_http_url(url: &str) -> bool {
-    url.starts_with("http://") || url.starts_with("https://")
-}
-
-/// Minimal HTTP GET implementation using std library when possible
-fn simple_http_get(url: &str) -> Result<Vec<u8>> {
-    use crate::minimal_http::MinimalHttpClient;
-    use std::time::Duration;
-    
-    let client = MinimalHttpClient::with_timeout(Duration::from_secs(30));
-    client.get(url)
 }
 
 /// Determine if we should use fast path based on arguments
+///
+/// Anything the batch does around a download, rather than the download itself,
+/// needs the full path: several jobs, naming files after the server's answer,
+/// resuming, splitting, cookie files, hooks and archives.
 pub fn should_use_fast_path(args: &Args) -> bool {
-    // Use fast path for single URL with minimal options
-    args.urls.len() == 1 
+    args.urls.len() == 1
         && args.input.is_none()
         && !args.resume
         && !args.verbose
-        && args.headers.is_empty()
-        && args.jobs <= 1
+        && scheduler::split(args) <= 1
+        && args.extract.is_none()
+        && args.exec.is_none()
+        && args.on_complete.is_none()
+        && args.on_failure.is_none()
+        && args.load_cookies.is_none()
+        && args.save_cookies.is_none()
+        && !args.spider
+        && !args.dry_run
+        && (args.globoff || !args.urls[0].url.contains(['{', '[']))
+        && (args.output.is_some() || prints_to_stdout(args))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_is_simple_http_url() {
-        assert!(is_simple_http_url("http://example.com"));
-        assert!(is_simple_http_url("https://example.com/file"));
-        assert!(!is_simple_http_url("ftp://example.com"));
-        assert!(!is_simple_http_url("file:///path"));
-    }
+    use crate::cli::ReportFormat;
+    use crate::retry::BackoffStrategy;
+    use crate::transport::TransportKind;
+    use crate::async_engine::Engine;
 
     #[test]
     fn test_should_use_fast_path() {
         let mut args = Args {
-            urls: vec!["https://example.com".to_string()],
+            command: None,
+            urls: vec!["https://example.com".into()],
             input: None,
             output: Some("test.txt".to_string()),
             headers: vec![],
@@ -110,19 +94,60 @@ mod tests {
             quiet: true,
             output_dir: None,
             jobs: 0,
+            split: None,
+            max_connections: 0,
             log: "test.log".to_string(),
             init: false,
             backoff_base_ms: 100,
             backoff_max_ms: 60000,
+            backoff_factor: 2.0,
+            backoff_strategy: BackoffStrategy::Exponential,
+            retry_budget: None,
+            retry_on: vec![],
+            circuit_threshold: 5,
+            circuit_cooldown: 30.0,
+            connect_timeout: None,
+            read_timeout: None,
+            max_time: None,
+            speed_limit: None,
+            speed_time: None,
             load_cookies: None,
             save_cookies: None,
             keep_session_cookies: false,
+            trust_server_names: false,
+            compressed: false,
+            raw: false,
+            transport: TransportKind::Auto,
+            engine: Engine::Auto,
+            extract: None,
+            strip_components: 0,
+            keep_archive: false,
+            exec: None,
+            exec_jobs: 1,
+            on_complete: None,
+            on_failure: None,
+            globoff: false,
+            spider: false,
+            recursive: false,
+            level: 5,
+            dry_run: false,
+            report_format: ReportFormat::Text,
+            report: None,
         };
         
         assert!(should_use_fast_path(&args));
-        
+
+        // Printing to stdout needs no file name either
+        args.output = None;
+        assert!(should_use_fast_path(&args));
+
+        // A name taken from the server's answer needs the full path
+        args.output_dir = Some("downloads".to_string());
+        assert!(!should_use_fast_path(&args));
+        args.output_dir = None;
+
         // Multiple URLs should not use fast path
-        args.urls.push("https://example2.com".to_string());
+        args.urls.push("https://example2.com".into());
         assert!(!should_use_fast_path(&args));
     }
 }
@@ -1,23 +1,35 @@
 use std::fs;
+use std::io::Write;
+use crate::cli::Args;
 use crate::error::{Result, RuGetError, WithContext};
+use crate::manifest::{parse_manifest, DownloadSpec, ManifestFormat};
 
 #[cfg(feature = "context")]
 use crate::error::AnyhowContextExt;
 
-/// Load URLs from an input file
-/// Filters out empty lines and comments (lines starting with #)
-pub fn load_urls_from_file(path: &str) -> Result<Vec<String>> {
+/// Load download specs from an input manifest
+///
+/// Plain-text manifests hold one URL per line, optionally followed by indented
+/// `key=value` option lines; JSON Lines and CSV manifests carry the same fields.
+/// Empty lines and comments (lines starting with #) are ignored.
+pub fn load_urls_from_file(path: &str) -> Result<Vec<DownloadSpec>> {
     let contents = fs::read_to_string(path)
         .with_context(|| format!("reading input file '{}'", path))?;
 
-    let urls: Vec<String> = contents
-        .lines()
-        .map(str::trim)
-        .filter(|line| !line.is_empty() && !line.starts_with('#'))
-        .map(str::to_string)
-        .collect();
+    parse_manifest(&contents, ManifestFormat::detect(path, &contents))
+        .map_err(|e| e.with_context(&format!("parsing input file '{}'", path)))
+}
 
-    Ok(urls)
+/// Write a --spider or --dry-run report to --report, or to stdout unless --quiet
+pub fn write_report(rendered: &str, args: &Args) -> Result<()> {
+    match &args.report {
+        Some(path) => fs::write(path, rendered)
+            .with_context(|| format!("writing report to {}", path)),
+        None if args.quiet => Ok(()),
+        None => std::io::stdout()
+            .write_all(rendered.as_bytes())
+            .with_context(|| "writing report".to_string()),
+    }
 }
 
 /// Tool-specific download command builder
@@ -35,7 +47,6 @@ pub fn download_cmd(tool: &str, url: &str, file: &str) -> Result<String> {
 mod tests {
     use super::*;
     use tempfile::NamedTempFile;
-    use std::io::Write;
 
     #[test]
     fn test_load_urls_from_file() {
@@ -49,9 +60,9 @@ mod tests {
         let urls = load_urls_from_file(temp_file.path().to_str().unwrap()).unwrap();
         
         assert_eq!(urls.len(), 3);
-        assert_eq!(urls[0], "https://example.com/file1.txt");
-        assert_eq!(urls[1], "https://example.com/file2.txt");
-        assert_eq!(urls[2], "https://example.com/file3.txt");
+        assert_eq!(urls[0].url, "https://example.com/file1.txt");
+        assert_eq!(urls[1].url, "https://example.com/file2.txt");
+        assert_eq!(urls[2].url, "https://example.com/file3.txt");
     }
 
     #[test]
@@ -0,0 +1,346 @@
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError};
+use crate::manifest::{DownloadSpec, JobSource};
+
+/// One piece of a globbed URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// `{a,b,c}`
+    Set(Vec<String>),
+    /// `[001-365]` or `[0-100:5]`; `width` is the zero-padded width, if any
+    Numeric { start: u64, end: u64, step: u64, width: usize },
+    /// `[a-z]` or `[A-Z:2]`
+    Alpha { start: u8, end: u8, step: u8 },
+}
+
+impl Segment {
+    /// Number of values the pattern expands to
+    fn len(&self) -> u64 {
+        match self {
+            Segment::Literal(_) => 1,
+            Segment::Set(items) => items.len() as u64,
+            Segment::Numeric { start, end, step, .. } => ((end - start) / step).saturating_add(1),
+            Segment::Alpha { start, end, step } => u64::from((end - start) / step) + 1,
+        }
+    }
+
+    /// The `index`th value, computed on demand so ranges are never materialised
+    fn value(&self, index: u64) -> String {
+        match self {
+            Segment::Literal(text) => text.clone(),
+            Segment::Set(items) => items[index as usize].clone(),
+            Segment::Numeric { start, step, width, .. } => {
+                format!("{:0width$}", start + index * step, width = *width)
+            }
+            Segment::Alpha { start, step, .. } => {
+                char::from(start + (index as u8) * step).to_string()
+            }
+        }
+    }
+}
+
+/// A URL containing curl-style `{set}` and `[range]` patterns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlGlob {
+    segments: Vec<Segment>,
+}
+
+impl UrlGlob {
+    /// Parse a URL, treating `\` as an escape for literal `{`, `}`, `[`, `]`
+    ///
+    /// Bracketed IPv6 hosts such as `http://[::1]:8080/` are left untouched.
+    pub fn parse(url: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = url.char_indices().peekable();
+
+        while let Some((pos, c)) = chars.next() {
+            match c {
+                '\\' if chars.peek().is_some_and(|(_, next)| "{}[]\\".contains(*next)) => {
+                    literal.push(chars.next().unwrap().1);
+                }
+                '{' => {
+                    let body = take_until(&mut chars, '}', url, pos)?;
+                    let items: Vec<String> = body.split(',').map(str::to_string).collect();
+                    flush_literal(&mut segments, &mut literal);
+                    segments.push(Segment::Set(items));
+                }
+                '[' => {
+                    let body = take_until(&mut chars, ']', url, pos)?;
+                    if is_ipv6_host(&literal, &body) {
+                        literal.push('[');
+                        literal.push_str(&body);
+                        literal.push(']');
+                        continue;
+                    }
+                    flush_literal(&mut segments, &mut literal);
+                    segments.push(parse_range(&body, url)?);
+                }
+                '}' | ']' => {
+                    return Err(glob_error(url, &format!("unmatched '{}' at position {}", c, pos)));
+                }
+                c => literal.push(c),
+            }
+        }
+        flush_literal(&mut segments, &mut literal);
+
+        Ok(Self { segments })
+    }
+
+    /// Total number of URLs, or `None` if it does not fit in a u64
+    pub fn count(&self) -> Option<u64> {
+        self.segments.iter().try_fold(1u64, |total, s| total.checked_mul(s.len()))
+    }
+
+    /// Expand lazily into download specs, naming each file from `output` if given
+    pub fn expand(self, output: Option<String>) -> GlobExpansion {
+        let positions = vec![0; self.segments.len()];
+        GlobExpansion { glob: self, output, positions, done: false }
+    }
+}
+
+fn flush_literal(segments: &mut Vec<Segment>, literal: &mut String) {
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(std::mem::take(literal)));
+    }
+}
+
+/// Collect characters up to the closing delimiter, rejecting nested patterns
+fn take_until(
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+    close: char,
+    url: &str,
+    open_pos: usize,
+) -> Result<String> {
+    let mut body = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|(_, next)| "{}[],\\".contains(*next)) => {
+                body.push(chars.next().unwrap().1);
+            }
+            c if c == close => return Ok(body),
+            '{' | '[' => return Err(glob_error(url, "nested patterns are not supported")),
+            c => body.push(c),
+        }
+    }
+    Err(glob_error(url, &format!("unmatched '{}' at position {}", if close == '}' { '{' } else { '[' }, open_pos)))
+}
+
+/// `[...]` right after the scheme or userinfo that contains a colon is an IPv6 literal
+fn is_ipv6_host(preceding: &str, body: &str) -> bool {
+    (preceding.ends_with("://") || preceding.ends_with('@'))
+        && body.contains(':')
+        && body.chars().all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.' || c == '%')
+}
+
+fn parse_range(body: &str, url: &str) -> Result<Segment> {
+    let (range, step) = match body.split_once(':') {
+        Some((range, step)) => {
+            let step: u64 = step.parse()
+                .ok()
+                .filter(|s| *s > 0)
+                .ok_or_else(|| glob_error(url, &format!("invalid step '{}' in [{}]", step, body)))?;
+            (range, step)
+        }
+        None => (body, 1),
+    };
+    let (start, end) = range.split_once('-')
+        .ok_or_else(|| glob_error(url, &format!("range [{}] must be written as [start-end]", body)))?;
+
+    if let (Ok(first), Ok(last)) = (start.parse::<u64>(), end.parse::<u64>()) {
+        if first > last {
+            return Err(glob_error(url, &format!("range [{}] runs backwards", body)));
+        }
+        let width = if start.len() > 1 && start.starts_with('0') { start.len() } else { 0 };
+        return Ok(Segment::Numeric { start: first, end: last, step, width });
+    }
+
+    match (start.as_bytes(), end.as_bytes()) {
+        ([first], [last])
+            if (first.is_ascii_lowercase() && last.is_ascii_lowercase())
+                || (first.is_ascii_uppercase() && last.is_ascii_uppercase()) =>
+        {
+            if first > last {
+                return Err(glob_error(url, &format!("range [{}] runs backwards", body)));
+            }
+            let step = u8::try_from(step).unwrap_or(u8::MAX);
+            Ok(Segment::Alpha { start: *first, end: *last, step })
+        }
+        _ => Err(glob_error(url, &format!("invalid range [{}]", body))),
+    }
+}
+
+fn glob_error(url: &str, message: &str) -> RuGetError {
+    RuGetError::new(
+        ErrorCode::E204,
+        ErrorKind::Parse,
+        format!("Invalid URL pattern '{}': {} (use --globoff for literal brackets)", url, message),
+    )
+}
+
+/// Substitute `#1`, `#2`, ... with the values matched by each pattern
+pub fn expand_output_template(template: &str, values: &[String]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '#' {
+            output.push(c);
+            continue;
+        }
+        let mut digits = String::new();
+        while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+            digits.push(*d);
+            chars.next();
+        }
+        match digits.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| values.get(i)) {
+            Some(value) => output.push_str(value),
+            None => {
+                output.push('#');
+                output.push_str(&digits);
+            }
+        }
+    }
+    output
+}
+
+/// Whether an output name refers to glob values
+pub fn has_output_references(template: &str) -> bool {
+    template.split('#').skip(1).any(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Lazy iterator over the URLs of a glob, rightmost pattern varying fastest
+#[derive(Debug)]
+pub struct GlobExpansion {
+    glob: UrlGlob,
+    output: Option<String>,
+    positions: Vec<u64>,
+    done: bool,
+}
+
+impl Iterator for GlobExpansion {
+    type Item = DownloadSpec;
+
+    fn next(&mut self) -> Option<DownloadSpec> {
+        if self.done {
+            return None;
+        }
+
+        let mut url = String::new();
+        let mut values = Vec::new();
+        for (segment, &position) in self.glob.segments.iter().zip(&self.positions) {
+            let value = segment.value(position);
+            url.push_str(&value);
+            if !matches!(segment, Segment::Literal(_)) {
+                values.push(value);
+            }
+        }
+
+        // Advance the odometer
+        self.done = true;
+        for (segment, position) in self.glob.segments.iter().zip(self.positions.iter_mut()).rev() {
+            *position += 1;
+            if *position < segment.len() {
+                self.done = false;
+                break;
+            }
+            *position = 0;
+        }
+
+        let mut spec = DownloadSpec::new(url);
+        spec.out = self.output.as_deref().map(|template| expand_output_template(template, &values));
+        Some(spec)
+    }
+}
+
+/// Turn command-line URLs into job sources, expanding globs unless `globoff`
+///
+/// An `--output` name containing `#N` references becomes the name template for
+/// every globbed URL and is taken out of `output`.
+pub fn command_line_jobs(urls: &[DownloadSpec], globoff: bool, output: &mut Option<String>) -> Result<Vec<JobSource>> {
+    let mut globs = Vec::new();
+    let mut sources = Vec::with_capacity(urls.len());
+
+    for spec in urls {
+        if globoff {
+            sources.push(JobSource::Spec(spec.clone()));
+            continue;
+        }
+        let glob = UrlGlob::parse(&spec.url)?;
+        if glob.count() == Some(1) {
+            // Plain URLs, and patterns such as `{only}` that match a single URL
+            sources.extend(glob.expand(None).map(JobSource::Spec));
+        } else {
+            globs.push(sources.len());
+            sources.push(JobSource::Glob(glob.expand(None)));
+        }
+    }
+
+    if !globs.is_empty() && output.as_deref().is_some_and(has_output_references) {
+        let template = output.take();
+        for index in globs {
+            if let JobSource::Glob(expansion) = &mut sources[index] {
+                expansion.output = template.clone();
+            }
+        }
+    }
+
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(pattern: &str) -> Vec<String> {
+        UrlGlob::parse(pattern).unwrap().expand(None).map(|s| s.url).collect()
+    }
+
+    #[test]
+    fn test_sets_and_ranges() {
+        assert_eq!(
+            urls("https://host/{2020,2021}/day[001-003].nc"),
+            vec![
+                "https://host/2020/day001.nc",
+                "https://host/2020/day002.nc",
+                "https://host/2020/day003.nc",
+                "https://host/2021/day001.nc",
+                "https://host/2021/day002.nc",
+                "https://host/2021/day003.nc",
+            ]
+        );
+        assert_eq!(urls("img[a-e:2].png"), vec!["imga.png", "imgc.png", "imge.png"]);
+        assert_eq!(urls("t[0-100:50]"), vec!["t0", "t50", "t100"]);
+        assert_eq!(UrlGlob::parse("x[1-1000000]y[a-z]").unwrap().count(), Some(26_000_000));
+    }
+
+    #[test]
+    fn test_literals_and_escapes() {
+        assert_eq!(urls("https://example.com/file.txt"), vec!["https://example.com/file.txt"]);
+
+        assert_eq!(urls(r"https://example.com/\[draft\].txt"), vec!["https://example.com/[draft].txt"]);
+        assert_eq!(urls("http://[::1]:8080/f[1-2]"), vec!["http://[::1]:8080/f1", "http://[::1]:8080/f2"]);
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for bad in ["a{b", "a[1-", "a]b", "a[5-1]", "a[1-5:0]", "a[x-9]", "a{b,[1-2]}"] {
+            let err = UrlGlob::parse(bad).unwrap_err();
+            assert_eq!(err.code, ErrorCode::E204, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_output_template() {
+        let specs: Vec<_> = UrlGlob::parse("https://host/{a,b}/[1-2].nc")
+            .unwrap()
+            .expand(Some("#1_#2.nc".into()))
+            .map(|s| s.out.unwrap())
+            .collect();
+        assert_eq!(specs, vec!["a_1.nc", "a_2.nc", "b_1.nc", "b_2.nc"]);
+
+        assert_eq!(expand_output_template("#3#x", &["v".into()]), "#3#x");
+        assert!(has_output_references("out_#1.nc"));
+        assert!(!has_output_references("out#.nc"));
+    }
+}
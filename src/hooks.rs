@@ -0,0 +1,326 @@
+use crate::cli::Args;
+use crate::output::Logger;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Outcome of a single download, as exposed to --exec hooks
+#[derive(Debug, Clone)]
+pub struct DownloadEvent {
+    pub url: String,
+    pub path: String,
+    pub size: u64,
+    pub duration: Duration,
+    pub sha256: Option<String>,
+}
+
+/// Outcome of a whole batch, as exposed to --on-complete and --on-failure hooks
+#[derive(Debug, Clone)]
+pub struct BatchEvent {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub duration: Duration,
+    pub failure_log: String,
+}
+
+/// Runs user-supplied commands after downloads, with bounded concurrency
+pub struct HookRunner {
+    exec: Option<String>,
+    on_complete: Option<String>,
+    on_failure: Option<String>,
+    executor: Option<HookExecutor>,
+}
+
+impl HookRunner {
+    pub fn from_args(args: &Args, logger: &Logger) -> Self {
+        Self::new(
+            args.exec.clone(),
+            args.on_complete.clone(),
+            args.on_failure.clone(),
+            args.exec_jobs,
+            logger,
+        )
+    }
+
+    fn new(
+        exec: Option<String>,
+        on_complete: Option<String>,
+        on_failure: Option<String>,
+        exec_jobs: usize,
+        logger: &Logger,
+    ) -> Self {
+        let executor = exec.as_ref().map(|_| HookExecutor::spawn(exec_jobs.max(1), logger));
+        Self {
+            exec,
+            on_complete,
+            on_failure,
+            executor,
+        }
+    }
+
+    /// Queue the --exec hook for a successful download
+    ///
+    /// Returns straight away so download workers never wait on a hook; at
+    /// most `--exec-jobs` hooks run at once on the hook executor.
+    pub fn after_download(&self, event: &DownloadEvent, logger: &Logger) {
+        let (Some(template), Some(executor)) = (&self.exec, &self.executor) else {
+            return;
+        };
+
+        let placeholders = [
+            ("{path}", event.path.as_str()),
+            ("{url}", event.url.as_str()),
+        ];
+        let mut env = vec![
+            ("RUGET_URL", event.url.clone()),
+            ("RUGET_PATH", event.path.clone()),
+            ("RUGET_SIZE", event.size.to_string()),
+            ("RUGET_DURATION_MS", event.duration.as_millis().to_string()),
+            ("RUGET_STATUS", "success".to_string()),
+        ];
+        if let Some(sha256) = &event.sha256 {
+            env.push(("RUGET_SHA256", sha256.clone()));
+        }
+
+        let job = HookJob {
+            command: expand_template(template, &placeholders),
+            env,
+        };
+        if let Err(mpsc::SendError(job)) = executor.submit(job) {
+            // The executor already shut down; run the hook here rather than drop it
+            run_hook("exec", &job.command, &job.env, logger);
+        }
+    }
+
+    /// Wait for every queued --exec hook to finish
+    pub fn wait(&self) {
+        if let Some(executor) = &self.executor {
+            executor.shutdown();
+        }
+    }
+
+    /// Run the batch hooks once every download has finished
+    pub fn after_batch(&self, event: &BatchEvent, logger: &Logger) {
+        let status = if event.failed == 0 { "success" } else { "failure" };
+        let total = event.total.to_string();
+        let succeeded = event.succeeded.to_string();
+        let failed = event.failed.to_string();
+
+        let placeholders = [
+            ("{total}", total.as_str()),
+            ("{succeeded}", succeeded.as_str()),
+            ("{failed}", failed.as_str()),
+        ];
+        let env = [
+            ("RUGET_TOTAL", total.clone()),
+            ("RUGET_SUCCEEDED", succeeded.clone()),
+            ("RUGET_FAILED", failed.clone()),
+            ("RUGET_DURATION_MS", event.duration.as_millis().to_string()),
+            ("RUGET_STATUS", status.to_string()),
+            ("RUGET_FAILURE_LOG", event.failure_log.clone()),
+        ];
+
+        if event.failed > 0
+            && let Some(template) = &self.on_failure
+        {
+            run_hook("on-failure", &expand_template(template, &placeholders), &env, logger);
+        }
+        if let Some(template) = &self.on_complete {
+            run_hook("on-complete", &expand_template(template, &placeholders), &env, logger);
+        }
+    }
+}
+
+impl Drop for HookRunner {
+    fn drop(&mut self) {
+        self.wait();
+    }
+}
+
+/// An --exec command with its placeholders already expanded
+struct HookJob {
+    command: String,
+    env: Vec<(&'static str, String)>,
+}
+
+/// A fixed pool of threads running queued --exec hooks
+struct HookExecutor {
+    sender: Mutex<Option<Sender<HookJob>>>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl HookExecutor {
+    fn spawn(threads: usize, logger: &Logger) -> Self {
+        let (sender, receiver) = mpsc::channel::<HookJob>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let logger = logger.clone();
+                thread::spawn(move || run_queued_hooks(&receiver, &logger))
+            })
+            .collect();
+
+        Self {
+            sender: Mutex::new(Some(sender)),
+            workers: Mutex::new(workers),
+        }
+    }
+
+    fn submit(&self, job: HookJob) -> Result<(), mpsc::SendError<HookJob>> {
+        match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => sender.send(job),
+            None => Err(mpsc::SendError(job)),
+        }
+    }
+
+    /// Stop accepting hooks and wait for the queued ones to run
+    fn shutdown(&self) {
+        drop(self.sender.lock().unwrap().take());
+        for worker in self.workers.lock().unwrap().drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_queued_hooks(receiver: &Mutex<Receiver<HookJob>>, logger: &Logger) {
+    loop {
+        // Only hold the lock while taking a job, so the others can run in parallel
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(job) => run_hook("exec", &job.command, &job.env, logger),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Run an expanded hook command through the platform shell
+///
+/// Hook failures are reported but never fail the download itself.
+fn run_hook(name: &str, command: &str, env: &[(&str, String)], logger: &Logger) {
+    let mut cmd = shell_command(command);
+    for (key, value) in env {
+        cmd.env(key, value);
+    }
+
+    match cmd.status() {
+        Ok(status) => logger.hook_executed(name, command, status.code()),
+        Err(e) => logger.warn(&format!("Failed to run {} hook '{}': {}", name, command, e)),
+    }
+}
+
+/// Substitute placeholders with shell-quoted values
+///
+/// The template is scanned once from left to right, so text that came from
+/// a substituted value is never searched for further placeholders.
+pub fn expand_template(template: &str, placeholders: &[(&str, &str)]) -> String {
+    let mut command = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(next) = rest.chars().next() {
+        match placeholders.iter().find(|(placeholder, _)| rest.starts_with(placeholder)) {
+            Some((placeholder, value)) => {
+                command.push_str(&shell_quote(value));
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                command.push(next);
+                rest = &rest[next.len_utf8()..];
+            }
+        }
+    }
+    command
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(not(unix))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+/// Quote a value so the shell passes it through as a single argument
+#[cfg(unix)]
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(not(unix))]
+fn shell_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_template_quotes_values() {
+        let command = expand_template(
+            "mv {path} /archive/ && echo {url}",
+            &[("{path}", "my file.nc"), ("{url}", "https://example.com/a?b=1&c='x'")],
+        );
+        assert_eq!(
+            command,
+            r"mv 'my file.nc' /archive/ && echo 'https://example.com/a?b=1&c='\''x'\'''"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_template_does_not_rescan_values() {
+        let command = expand_template(
+            "echo {path} {url}",
+            &[("{path}", "evil{url}.txt"), ("{url}", "http://x/'$(id)'")],
+        );
+        assert_eq!(command, r"echo 'evil{url}.txt' 'http://x/'\''$(id)'\'''");
+
+        // The shell must see both values verbatim, with nothing expanded
+        let output = shell_command(&command).output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "evil{url}.txt http://x/'$(id)'\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exec_hooks_do_not_block_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("exec.log");
+        let hooks = HookRunner::new(
+            Some(format!("sleep 0.3 && echo {{path}} >> {}", log.display())),
+            None,
+            None,
+            1,
+            &Logger::new(true, false),
+        );
+        let event = |path: &str| DownloadEvent {
+            url: format!("http://example.com/{}", path),
+            path: path.to_string(),
+            size: 0,
+            duration: Duration::ZERO,
+            sha256: None,
+        };
+
+        let start = std::time::Instant::now();
+        hooks.after_download(&event("a"), &Logger::new(true, false));
+        hooks.after_download(&event("b"), &Logger::new(true, false));
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        hooks.wait();
+        // One --exec-jobs slot runs the hooks one after the other
+        assert!(start.elapsed() >= Duration::from_millis(600));
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "a\nb\n");
+    }
+}
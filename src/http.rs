@@ -1,7 +1,9 @@
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
 use netrc::Netrc;
 use regex::Regex;
+use percent_encoding::percent_decode_str;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use url::Url;
 use std::{
     fs::File,
     io::BufReader,
@@ -86,29 +88,94 @@ pub fn extract_filename_from_disposition(header: Option<&HeaderValue>) -> Option
 
 /// Get fallback filename from URL
 pub fn get_fallback_filename(url: &str) -> String {
-    let last_segment = url.split('/')
-        .last()
-        .filter(|s| !s.is_empty())
-        .unwrap_or("download.bin");
-    
-    // If the last segment contains a dot, check if it's a filename or domain
-    if last_segment.contains('.') {
-        // Check if it's likely a filename by looking at the extension
-        if let Some(dot_pos) = last_segment.rfind('.') {
-            let extension = &last_segment[dot_pos + 1..];
-            // If the extension is 1-5 chars and alphanumeric, it's likely a file extension
-            if extension.len() >= 1 && extension.len() <= 5 && extension.chars().all(|c| c.is_alphanumeric()) {
-                // Additional check: make sure it's not a domain extension
-                let common_domains = ["com", "org", "net", "edu", "gov", "mil", "int", "co", "uk", "ca", "au", "de", "fr", "jp", "cn", "ru", "br", "in", "it", "es", "nl", "se", "no", "fi", "dk", "pl", "be", "ch", "at", "cz", "hu", "ie", "pt", "gr", "bg", "ro", "hr", "si", "sk", "lt", "lv", "ee", "is", "mt", "cy", "lu", "mc", "li", "ad", "sm", "va", "tv", "cc", "tk", "ml", "ga", "cf", "biz", "info", "name", "pro", "aero", "coop", "museum"];
-                if !common_domains.contains(&extension) {
-                    return last_segment.to_string();
-                }
-            }
-        }
+    get_fallback_filename_with_content_type(url, None)
+}
+
+/// Get fallback filename from URL, inferring an extension from the response
+/// Content-Type when the URL path does not carry one
+pub fn get_fallback_filename_with_content_type(url: &str, content_type: Option<&HeaderValue>) -> String {
+    let parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return "download.bin".to_string(),
+    };
+
+    // The query string and fragment are not part of the path, so `file.nc?token=x`
+    // yields `file.nc`
+    let last_segment = parsed
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or("");
+
+    // An empty last segment means the path ends in a slash (or is just the root)
+    if last_segment.is_empty() {
+        return "index.html".to_string();
+    }
+
+    let decoded = percent_decode_str(last_segment).decode_utf8_lossy();
+    let name = match sanitize_filename(&decoded) {
+        Some(name) => name,
+        None => return "download.bin".to_string(),
+    };
+
+    if name.contains('.') {
+        return name;
+    }
+
+    match content_type.and_then(extension_for_content_type) {
+        Some(ext) => format!("{}.{}", name, ext),
+        None => name,
     }
-    
-    // If it's not a filename, use default
-    "download.bin".to_string()
+}
+
+/// Strip path separators and control characters from a decoded name so it
+/// cannot escape the output directory
+fn sanitize_filename(name: &str) -> Option<String> {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim();
+
+    if cleaned.is_empty() || cleaned == "." || cleaned == ".." {
+        None
+    } else {
+        Some(cleaned.to_string())
+    }
+}
+
+/// Map a Content-Type header to a conventional file extension
+fn extension_for_content_type(content_type: &HeaderValue) -> Option<&'static str> {
+    let mime = content_type.to_str().ok()?;
+    let mime = mime.split(';').next()?.trim().to_ascii_lowercase();
+
+    let ext = match mime.as_str() {
+        "text/html" | "application/xhtml+xml" => "html",
+        "text/plain" => "txt",
+        "text/css" => "css",
+        "text/csv" => "csv",
+        "text/javascript" | "application/javascript" => "js",
+        "application/json" => "json",
+        "application/xml" | "text/xml" => "xml",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "application/gzip" | "application/x-gzip" => "gz",
+        "application/x-tar" => "tar",
+        "application/x-bzip2" => "bz2",
+        "application/x-xz" => "xz",
+        "application/zstd" => "zst",
+        "application/x-netcdf" | "application/netcdf" => "nc",
+        "application/x-hdf5" => "h5",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/svg+xml" => "svg",
+        "image/webp" => "webp",
+        "video/mp4" => "mp4",
+        "audio/mpeg" => "mp3",
+        _ => return None,
+    };
+
+    Some(ext)
 }
 
 #[cfg(test)]
@@ -118,8 +185,29 @@ mod tests {
     #[test]
     fn test_fallback_filename() {
         assert_eq!(get_fallback_filename("https://example.com/file.txt"), "file.txt");
-        assert_eq!(get_fallback_filename("https://example.com/"), "download.bin");
-        assert_eq!(get_fallback_filename("https://example.com"), "download.bin");
+        assert_eq!(get_fallback_filename("https://example.com/"), "index.html");
+        assert_eq!(get_fallback_filename("https://example.com"), "index.html");
+        assert_eq!(get_fallback_filename("https://example.com/data/"), "index.html");
+        assert_eq!(get_fallback_filename("not a url"), "download.bin");
+    }
+
+    #[test]
+    fn test_fallback_filename_strips_query_and_decodes() {
+        assert_eq!(get_fallback_filename("https://example.com/file.nc?token=x"), "file.nc");
+        assert_eq!(get_fallback_filename("https://example.com/a/my%20file.txt#frag"), "my file.txt");
+        assert_eq!(get_fallback_filename("https://example.com/a%2F..%2Fb.txt"), "a_.._b.txt");
+    }
+
+    #[test]
+    fn test_fallback_filename_with_content_type() {
+        let html = HeaderValue::from_static("text/html; charset=utf-8");
+        let nc = HeaderValue::from_static("application/x-netcdf");
+        let unknown = HeaderValue::from_static("application/octet-stream");
+
+        assert_eq!(get_fallback_filename_with_content_type("https://example.com/report", Some(&html)), "report.html");
+        assert_eq!(get_fallback_filename_with_content_type("https://example.com/data?id=3", Some(&nc)), "data.nc");
+        assert_eq!(get_fallback_filename_with_content_type("https://example.com/blob", Some(&unknown)), "blob");
+        assert_eq!(get_fallback_filename_with_content_type("https://example.com/file.txt", Some(&html)), "file.txt");
     }
 
     #[test]
@@ -20,6 +20,12 @@ verbose = false
 # Number of parallel jobs (0 = auto)
 jobs = 0
 
+# Connections per file (defaults to jobs)
+# split = 4
+
+# Most connections open at once across all downloads (0 = no cap)
+max_connections = 0
+
 # Output directory for downloads
 output_dir = "/path/to/save"
 
@@ -29,6 +35,9 @@ headers = [
   "Accept: */*"
 ]
 
+# Request compressed responses (gzip, br, zstd, deflate) and decode them
+compressed = false
+
 # Log file path for failed downloads
 log = "/tmp/ruget_failures.log"
 
@@ -37,6 +46,18 @@ log = "/tmp/ruget_failures.log"
 max = 5           # Maximum number of retries
 base_ms = 500     # Base delay in milliseconds
 max_ms = 10000    # Maximum delay in milliseconds
+factor = 2.0      # Multiplier applied to the delay after each retry
+strategy = "exponential"  # "exponential", "full-jitter" or "decorrelated"
+# budget = 100      # Maximum retries across a whole run
+# retry_on = ["408", "425", "429", "5xx", "timeout", "connect", "reset"]
+
+# Timeouts in seconds; speed_limit is in bytes per second
+[timeouts]
+connect = 30      # Time allowed to establish a connection
+read = 30         # Time allowed without receiving data
+# max_time = 3600   # Maximum time for one transfer attempt
+# speed_limit = 1024  # Abort and retry transfers slower than this...
+# speed_time = 30     # ...averaged over this many seconds
 
 # Logging configuration
 [logging]
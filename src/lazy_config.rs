@@ -20,11 +20,15 @@ pub fn apply_config_if_needed(args: &mut Args) {
         || !args.quiet 
         || !args.verbose 
         || args.jobs == 0 
+        || args.split.is_none()
+        || args.max_connections == 0
         || args.output_dir.is_none() 
         || args.headers.is_empty() 
         || args.log.is_empty()
         || args.log_format.is_none()
-        || args.log_level.is_none();
+        || args.log_level.is_none()
+        || args.connect_timeout.is_none()
+        || args.read_timeout.is_none();
         
     if needs_config {
         let config = get_config();
@@ -45,11 +49,16 @@ pub fn skip_config_for_simple_download(args: &Args) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli::ReportFormat;
+    use crate::retry::BackoffStrategy;
+    use crate::transport::TransportKind;
+    use crate::async_engine::Engine;
 
     #[test]
     fn test_skip_config_for_simple_download() {
         let args = Args {
-            urls: vec!["https://example.com".to_string()],
+            command: None,
+            urls: vec!["https://example.com".into()],
             input: None,
             output: Some("test.txt".to_string()),
             headers: vec![],
@@ -62,13 +71,45 @@ mod tests {
             quiet: true,
             output_dir: None,
             jobs: 0,
+            split: None,
+            max_connections: 0,
             log: "test.log".to_string(),
             init: false,
             backoff_base_ms: 100,
             backoff_max_ms: 60000,
+            backoff_factor: 2.0,
+            backoff_strategy: BackoffStrategy::Exponential,
+            retry_budget: None,
+            retry_on: vec![],
+            circuit_threshold: 5,
+            circuit_cooldown: 30.0,
+            connect_timeout: None,
+            read_timeout: None,
+            max_time: None,
+            speed_limit: None,
+            speed_time: None,
             load_cookies: None,
             save_cookies: None,
             keep_session_cookies: false,
+            trust_server_names: false,
+            compressed: false,
+            raw: false,
+            transport: TransportKind::Auto,
+            engine: Engine::Auto,
+            extract: None,
+            strip_components: 0,
+            keep_archive: false,
+            exec: None,
+            exec_jobs: 1,
+            on_complete: None,
+            on_failure: None,
+            globoff: false,
+            spider: false,
+            recursive: false,
+            level: 5,
+            dry_run: false,
+            report_format: ReportFormat::Text,
+            report: None,
         };
         
         assert!(skip_config_for_simple_download(&args));
@@ -14,8 +14,28 @@ pub mod native_http;
 pub mod simd_ops;
 pub mod ultimate_fast;
 pub mod multithreaded_download;
+pub mod single_stream;
 pub mod retry;
 pub mod cookie;
+pub mod encoding;
+pub mod extract;
+pub mod hooks;
+pub mod daemon;
+pub mod rpc;
+pub mod checksum;
+pub mod manifest;
+pub mod glob;
+pub mod spider;
+pub mod plan;
+pub mod timeouts;
+pub mod circuit;
+pub mod adaptive;
+pub mod scheduler;
+pub mod probe;
+pub mod resume;
+pub mod run;
+pub mod transport;
+pub mod async_engine;
 
 // Re-export commonly used types for convenience
 pub use cli::Args;
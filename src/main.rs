@@ -10,15 +10,31 @@ mod http;
 mod init;
 mod file_utils;
 mod fast_download;
-mod lazy_config;
-mod minimal_http;
-mod ultra_fast;
 mod native_http;
-mod simd_ops;
-mod ultimate_fast;
+mod lazy_config;
 mod multithreaded_download;
+mod single_stream;
 mod retry;
 mod cookie;
+mod encoding;
+mod extract;
+mod hooks;
+mod daemon;
+mod rpc;
+mod checksum;
+mod manifest;
+mod glob;
+mod spider;
+mod plan;
+mod timeouts;
+mod circuit;
+mod adaptive;
+mod scheduler;
+mod probe;
+mod resume;
+mod run;
+mod transport;
+mod async_engine;
 
 use cli::Args;
 use download::download;
@@ -29,12 +45,21 @@ use init::init_config;
 use file_utils::load_urls_from_file;
 use fast_download::{fast_single_download, should_use_fast_path};
 use lazy_config::{apply_config_if_needed, skip_config_for_simple_download};
-use cli::{LogFormat};
+use cli::{Command, LogFormat};
+use daemon::run_daemon;
+use std::io::BufReader;
+use std::sync::Arc;
+use manifest::{JobSource, JobStream, ManifestStream};
+use glob::command_line_jobs;
+use spider::spider;
+use plan::dry_run;
+use download::prints_to_stdout;
+use multithreaded_download::STDOUT_PATH;
 
 fn main() {
     if let Err(e) = run() {
-        // Create a basic logger for error reporting
-        let logger = Logger::new(false, false);
+        // Create a basic logger for error reporting, away from any body on stdout
+        let logger = Logger::new(false, false).with_stderr(true);
         logger.error_from_ruget_error(&e);
         std::process::exit(1);
     }
@@ -51,42 +76,57 @@ fn run() -> Result<()> {
     }
 
     let mut args = raw_args;
-    
-    // Check for fast path BEFORE config loading to avoid config interference
-    let can_use_fast_path = should_use_fast_path(&args);
-    
-    if can_use_fast_path {
-        // Fast path: minimal single URL download
-        let url = &args.urls[0];
-        if let Some(output_path) = args.output.as_deref() {
-            return fast_single_download(url, Some(output_path), args.quiet);
-        } else {
-            return fast_single_download(url, None, args.quiet);
-        }
+
+    // `ruget daemon` serves a persistent queue instead of downloading URLs
+    if let Some(Command::Daemon(daemon_args)) = args.command.take() {
+        apply_config_if_needed(&mut args);
+        let use_json = determine_json_output(&args);
+        let logger = Arc::new(Logger::new_with_json(args.quiet, args.verbose, use_json));
+        return run_daemon(daemon_args, args, logger);
     }
     
-    // Regular path: apply config and continue
+    // Config applies to both paths, so it can never change which one behaves how
     if !skip_config_for_simple_download(&args) {
         apply_config_if_needed(&mut args);
     }
 
-    // Load URLs from --input file if provided
-    if let Some(ref input_path) = args.input {
-        let file_urls = load_urls_from_file(input_path)?;
-        args.urls.extend(file_urls);
+    // Create the logger with JSON option; when the body goes to stdout, the log goes to stderr
+    let use_json = determine_json_output(&args);
+    let logger = Logger::new_with_json(args.quiet, args.verbose, use_json)
+        .with_stderr(prints_to_stdout(&args) || args.output.as_deref() == Some(STDOUT_PATH));
+
+    if should_use_fast_path(&args) {
+        return fast_single_download(&args, &logger);
+    }
+
+    // Globs are only expanded in URLs given on the command line
+    let mut sources = command_line_jobs(&args.urls, args.globoff, &mut args.output)?;
+
+    // Load URLs from --input file if provided; `--input -` streams from stdin
+    match args.input.as_deref() {
+        Some("-") => sources.push(JobSource::Live(ManifestStream::new(BufReader::new(std::io::stdin())))),
+        Some(input_path) => {
+            let file_urls = load_urls_from_file(input_path)?;
+            sources.extend(file_urls.into_iter().map(JobSource::Spec));
+        }
+        None => {}
     }
 
-    if args.urls.is_empty() {
+    if sources.is_empty() {
         eprintln!("Error: No URLs provided via --input or CLI.");
         return Err(RuGetError::parse("No URLs provided".into()));
     }
 
-    // Create the logger with JSON option
-    let use_json = determine_json_output(&args);
-    let logger = Logger::new_with_json(args.quiet, args.verbose, use_json);
+    let jobs = JobStream::new(sources);
+    if args.spider {
+        return spider(args, jobs, &logger);
+    }
+    if args.dry_run {
+        return dry_run(args, jobs, &logger);
+    }
 
     // Fall back to full download functionality
-    download(args, &logger)
+    download(args, jobs, &logger)
 }
 
 /// Determine whether to use JSON output based on configuration
@@ -0,0 +1,541 @@
+use crate::checksum::Checksum;
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError};
+use crate::glob::GlobExpansion;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A single download requested on the command line or in an --input manifest
+///
+/// Fields left unset fall back to the corresponding command-line options.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadSpec {
+    pub url: String,
+    /// File name to save as, relative to `dir`
+    pub out: Option<String>,
+    /// Directory for this download, overriding --output-dir
+    pub dir: Option<String>,
+    /// Extra headers sent only for this download
+    pub headers: Vec<String>,
+    pub checksum: Option<Checksum>,
+    /// Alternative URLs for the same file, tried in order if `url` fails
+    pub mirrors: Vec<String>,
+}
+
+impl DownloadSpec {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into(), ..Default::default() }
+    }
+
+    /// The primary URL followed by its mirrors
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.url.as_str()).chain(self.mirrors.iter().map(String::as_str))
+    }
+
+    /// Apply one `key=value` option from a manifest
+    fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        match key.trim() {
+            "out" => self.out = Some(value.to_string()),
+            "dir" => self.dir = Some(value.to_string()),
+            "header" => self.headers.push(value.to_string()),
+            "checksum" => self.checksum = Some(value.parse()?),
+            "mirror" => self.mirrors.push(value.to_string()),
+            other => return Err(RuGetError::parse(format!("Unknown manifest option '{}'", other))),
+        }
+        Ok(())
+    }
+}
+
+/// URLs given on the command line carry no per-job options
+impl FromStr for DownloadSpec {
+    type Err = Infallible;
+
+    fn from_str(url: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self::new(url))
+    }
+}
+
+impl From<&str> for DownloadSpec {
+    fn from(url: &str) -> Self {
+        Self::new(url)
+    }
+}
+
+impl From<String> for DownloadSpec {
+    fn from(url: String) -> Self {
+        Self::new(url)
+    }
+}
+
+/// Manifest syntaxes accepted by --input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// One URL per line with indented `key=value` option lines, as in aria2
+    Text,
+    JsonLines,
+    Csv,
+}
+
+impl ManifestFormat {
+    /// Pick the format from the file extension, sniffing JSON Lines otherwise
+    pub fn detect(path: &str, contents: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("jsonl") | Some("ndjson") => ManifestFormat::JsonLines,
+            Some("csv") => ManifestFormat::Csv,
+            _ if first_content_line(contents).is_some_and(|l| l.starts_with('{')) => ManifestFormat::JsonLines,
+            _ => ManifestFormat::Text,
+        }
+    }
+}
+
+fn first_content_line(contents: &str) -> Option<&str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Parse a manifest in the given format
+pub fn parse_manifest(contents: &str, format: ManifestFormat) -> Result<Vec<DownloadSpec>> {
+    match format {
+        ManifestFormat::Text => parse_text(contents),
+        ManifestFormat::JsonLines => parse_json_lines(contents),
+        ManifestFormat::Csv => parse_csv(contents),
+    }
+}
+
+/// Parse the aria2-style text format
+///
+/// Non-indented lines start a new download; tab-separated URLs on the same
+/// line are mirrors. Indented `key=value` lines set options for the download
+/// above them.
+pub fn parse_text(contents: &str) -> Result<Vec<DownloadSpec>> {
+    let mut parser = TextParser::default();
+    let mut specs = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        specs.extend(parser.push_line(line, index + 1)?);
+    }
+    specs.extend(parser.finish());
+
+    Ok(specs)
+}
+
+/// Incremental parser for the text format
+///
+/// An entry is complete once the next entry starts or the input ends, since
+/// option lines may still follow its URL.
+#[derive(Debug, Default)]
+struct TextParser {
+    pending: Option<DownloadSpec>,
+}
+
+impl TextParser {
+    /// Feed one line, returning the previous entry if this line starts a new one
+    fn push_line(&mut self, line: &str, line_number: usize) -> Result<Option<DownloadSpec>> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return Ok(None);
+        }
+
+        if line.starts_with([' ', '\t']) && !is_url_line(trimmed) {
+            let spec = self.pending.as_mut().ok_or_else(|| {
+                manifest_error(line_number, "option line does not follow a URL")
+            })?;
+            let (key, value) = trimmed.split_once('=').ok_or_else(|| {
+                manifest_error(line_number, &format!("expected key=value, found '{}'", trimmed))
+            })?;
+            spec.set_option(key, value)
+                .map_err(|e| manifest_error(line_number, &e.message))?;
+            Ok(None)
+        } else {
+            let mut uris = trimmed.split('\t').map(str::trim).filter(|u| !u.is_empty());
+            let mut spec = DownloadSpec::new(uris.next().unwrap_or_default());
+            spec.mirrors.extend(uris.map(str::to_string));
+            Ok(self.pending.replace(spec))
+        }
+    }
+
+    fn finish(&mut self) -> Option<DownloadSpec> {
+        self.pending.take()
+    }
+}
+
+/// Indented lines that are themselves URLs are treated as entries, as the old
+/// one-URL-per-line format trimmed leading whitespace
+fn is_url_line(line: &str) -> bool {
+    line.contains("://") && line.split_once('=').is_none_or(|(key, _)| key.contains("://"))
+}
+
+/// Fields of a JSON Lines manifest entry
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonEntry {
+    url: String,
+    out: Option<String>,
+    dir: Option<String>,
+    #[serde(default, alias = "headers")]
+    header: OneOrMany,
+    checksum: Option<String>,
+    #[serde(default, alias = "mirrors")]
+    mirror: OneOrMany,
+}
+
+/// A field that may be given as a single string or a list of strings
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    #[default]
+    None,
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::None => Vec::new(),
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// Parse one JSON object per line; blank and `#` lines are skipped
+pub fn parse_json_lines(contents: &str) -> Result<Vec<DownloadSpec>> {
+    let mut specs = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        specs.extend(parse_json_line(line, index + 1)?);
+    }
+    Ok(specs)
+}
+
+fn parse_json_line(line: &str, line_number: usize) -> Result<Option<DownloadSpec>> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(None);
+    }
+
+    let entry: JsonEntry = serde_json::from_str(trimmed)
+        .map_err(|e| manifest_error(line_number, &e.to_string()))?;
+    let checksum = entry.checksum
+        .map(|c| c.parse::<Checksum>())
+        .transpose()
+        .map_err(|e| manifest_error(line_number, &e.message))?;
+
+    Ok(Some(DownloadSpec {
+        url: entry.url,
+        out: entry.out,
+        dir: entry.dir,
+        headers: entry.header.into_vec(),
+        checksum,
+        mirrors: entry.mirror.into_vec(),
+    }))
+}
+
+/// Parse a CSV manifest with a header row naming the columns
+///
+/// `header` and `mirror` columns may be repeated to give several values;
+/// empty cells are ignored.
+pub fn parse_csv(contents: &str) -> Result<Vec<DownloadSpec>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+
+    let columns: Vec<String> = reader.headers()
+        .map_err(|e| manifest_error(1, &e.to_string()))?
+        .iter()
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if !columns.iter().any(|c| c == "url") {
+        return Err(manifest_error(1, "CSV manifest needs a 'url' column"));
+    }
+
+    let mut specs = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| {
+            let line = e.position().map(|p| p.line() as usize).unwrap_or(0);
+            manifest_error(line, &e.to_string())
+        })?;
+        let line = record.position().map(|p| p.line() as usize).unwrap_or(0);
+
+        let mut spec = DownloadSpec::default();
+        for (column, value) in columns.iter().zip(record.iter()) {
+            if value.is_empty() {
+                continue;
+            }
+            if column == "url" {
+                spec.url = value.to_string();
+            } else {
+                let key = column.trim_end_matches('s');
+                spec.set_option(key, value).map_err(|e| manifest_error(line, &e.message))?;
+            }
+        }
+
+        if spec.url.is_empty() {
+            return Err(manifest_error(line, "missing url"));
+        }
+        specs.push(spec);
+    }
+
+    Ok(specs)
+}
+
+/// Where download jobs come from
+pub enum JobSource {
+    Spec(DownloadSpec),
+    /// URLs expanded lazily from a command-line glob
+    Glob(GlobExpansion),
+    /// Entries read from `--input -` as they arrive
+    Live(ManifestStream),
+}
+
+/// Download jobs in the order they should start, produced on demand
+pub struct JobStream {
+    sources: VecDeque<JobSource>,
+}
+
+impl JobStream {
+    pub fn new(sources: impl IntoIterator<Item = JobSource>) -> Self {
+        Self { sources: sources.into_iter().collect() }
+    }
+
+    /// Every job, if all of them are known without expanding globs or waiting for input
+    pub fn known_specs(&self) -> Option<Vec<&DownloadSpec>> {
+        self.sources
+            .iter()
+            .map(|source| match source {
+                JobSource::Spec(spec) => Some(spec),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Iterator for JobStream {
+    type Item = Result<DownloadSpec>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.sources.front_mut()? {
+                JobSource::Spec(_) => {}
+                JobSource::Glob(expansion) => {
+                    if let Some(spec) = expansion.next() {
+                        return Some(Ok(spec));
+                    }
+                }
+                JobSource::Live(stream) => {
+                    if let Some(job) = stream.next() {
+                        return Some(job);
+                    }
+                }
+            }
+
+            // A plain spec is used once; an exhausted glob or stream is dropped
+            if let Some(JobSource::Spec(spec)) = self.sources.pop_front() {
+                return Some(Ok(spec));
+            }
+        }
+    }
+}
+
+/// Manifest entries parsed from a reader as lines arrive
+///
+/// Used for `--input -`: each entry is yielded as soon as it is complete, so
+/// downloads start while the producing program is still running. A text entry
+/// is complete when the next URL line arrives or the input ends, since option
+/// lines may follow it at any pace. The format is JSON Lines if the first
+/// entry starts with `{`, text otherwise.
+pub struct ManifestStream {
+    lines: Box<dyn Iterator<Item = std::io::Result<String>> + Send>,
+    format: Option<ManifestFormat>,
+    text: TextParser,
+    line_number: usize,
+    finished: bool,
+}
+
+impl ManifestStream {
+    pub fn new<R: BufRead + Send + 'static>(reader: R) -> Self {
+        Self {
+            lines: Box::new(reader.lines()),
+            format: None,
+            text: TextParser::default(),
+            line_number: 0,
+            finished: false,
+        }
+    }
+}
+
+impl Iterator for ManifestStream {
+    type Item = Result<DownloadSpec>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    self.finished = true;
+                    return Some(Err(RuGetError::from(e).with_context("reading input stream")));
+                }
+                None => {
+                    self.finished = true;
+                    return self.text.finish().map(Ok);
+                }
+            };
+
+            self.line_number += 1;
+            if self.format.is_none() && first_content_line(&line).is_some() {
+                self.format = Some(ManifestFormat::detect("-", &line));
+            }
+            let parsed = match self.format {
+                Some(ManifestFormat::JsonLines) => parse_json_line(&line, self.line_number),
+                _ => self.text.push_line(&line, self.line_number),
+            };
+            match parsed {
+                Ok(Some(spec)) => return Some(Ok(spec)),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+fn manifest_error(line: usize, message: &str) -> RuGetError {
+    RuGetError::new(
+        ErrorCode::E501,
+        ErrorKind::Parse,
+        format!("Invalid input manifest at line {}: {}", line, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::ChecksumAlgorithm;
+
+    #[test]
+    fn test_parse_text_with_options() {
+        let manifest = "\
+# nightly batch
+https://a.example.com/data.nc\thttps://b.example.com/data.nc
+  out=foo.nc
+  dir=/data/raw
+  header=Authorization: Bearer abc
+  checksum=sha-256=00ff
+  mirror=https://c.example.com/data.nc
+
+https://example.com/plain.txt
+    https://example.com/indented.txt
+";
+        let specs = parse_text(manifest).unwrap();
+        assert_eq!(specs.len(), 3);
+
+        let first = &specs[0];
+        assert_eq!(first.url, "https://a.example.com/data.nc");
+        assert_eq!(first.out.as_deref(), Some("foo.nc"));
+        assert_eq!(first.dir.as_deref(), Some("/data/raw"));
+        assert_eq!(first.headers, vec!["Authorization: Bearer abc"]);
+        assert_eq!(first.checksum.as_ref().unwrap().algorithm, ChecksumAlgorithm::Sha256);
+        assert_eq!(
+            first.sources().collect::<Vec<_>>(),
+            vec!["https://a.example.com/data.nc", "https://b.example.com/data.nc", "https://c.example.com/data.nc"]
+        );
+
+        assert_eq!(specs[1], DownloadSpec::new("https://example.com/plain.txt"));
+        assert_eq!(specs[2], DownloadSpec::new("https://example.com/indented.txt"));
+    }
+
+    #[test]
+    fn test_parse_text_errors_name_the_line() {
+        let err = parse_text("  out=orphan.txt\n").unwrap_err();
+        assert!(err.message.contains("line 1"));
+
+        let err = parse_text("https://example.com/a\n  colour=blue\n").unwrap_err();
+        assert!(err.message.contains("line 2"));
+        assert!(err.message.contains("colour"));
+    }
+
+    #[test]
+    fn test_parse_json_lines() {
+        let manifest = r#"
+{"url": "https://example.com/a", "out": "a.bin", "header": "X-Token: 1"}
+{"url": "https://example.com/b", "headers": ["A: 1", "B: 2"], "mirrors": ["https://mirror/b"], "checksum": "md5=00"}
+"#;
+        let specs = parse_json_lines(manifest).unwrap();
+        assert_eq!(specs[0].out.as_deref(), Some("a.bin"));
+        assert_eq!(specs[0].headers, vec!["X-Token: 1"]);
+        assert_eq!(specs[1].headers, vec!["A: 1", "B: 2"]);
+        assert_eq!(specs[1].mirrors, vec!["https://mirror/b"]);
+
+        let err = parse_json_lines("{\"url\": \"x\", \"bogus\": 1}").unwrap_err();
+        assert!(err.message.contains("line 1"));
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let manifest = "\
+url,out,dir,header,header,mirror
+https://example.com/a,a.bin,,X-One: 1,X-Two: 2,https://mirror/a
+# skipped
+https://example.com/b,,/tmp,,,
+";
+        let specs = parse_csv(manifest).unwrap();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].headers, vec!["X-One: 1", "X-Two: 2"]);
+        assert_eq!(specs[0].mirrors, vec!["https://mirror/a"]);
+        assert_eq!(specs[1].dir.as_deref(), Some("/tmp"));
+        assert!(specs[1].out.is_none());
+
+        assert!(parse_csv("out\nfoo\n").is_err());
+    }
+
+    #[test]
+    fn test_manifest_stream() {
+        let input = "# header\nhttps://example.com/a\n\n  out=a.bin\nhttps://example.com/b\n";
+        let specs: Vec<_> = ManifestStream::new(std::io::Cursor::new(input))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].out.as_deref(), Some("a.bin"));
+
+        // Option lines that arrive late still belong to the entry before them
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let mut stream = ManifestStream::new(std::io::BufReader::new(reader));
+        let producer = std::thread::spawn(move || {
+            use std::io::Write;
+            writeln!(writer, "https://example.com/a").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            writeln!(writer, "  out=late.bin").unwrap();
+        });
+        let spec = stream.next().unwrap().unwrap();
+        assert_eq!(spec.out.as_deref(), Some("late.bin"));
+        assert!(stream.next().is_none());
+        producer.join().unwrap();
+
+        let input = "{\"url\": \"https://example.com/a\"}\nnot json\n";
+        let mut stream = ManifestStream::new(std::io::Cursor::new(input));
+        assert_eq!(stream.next().unwrap().unwrap().url, "https://example.com/a");
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(ManifestFormat::detect("jobs.jsonl", ""), ManifestFormat::JsonLines);
+        assert_eq!(ManifestFormat::detect("jobs.CSV", ""), ManifestFormat::Csv);
+        assert_eq!(ManifestFormat::detect("jobs.txt", "# c\n{\"url\":\"x\"}"), ManifestFormat::JsonLines);
+        assert_eq!(ManifestFormat::detect("jobs.txt", "https://example.com"), ManifestFormat::Text);
+    }
+}
@@ -1,145 +1,388 @@
 use crate::cli::Args;
-use crate::error::{Result, RuGetError, WithContext};
+use crate::extract::{extractor_write_error, streaming_format, ExtractOptions, StreamingExtractor};
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError, WithContext};
 use crate::http::{build_headers, add_netrc_auth};
 use crate::output::Logger;
-use crate::retry::{BackoffPolicy, is_transient};
+use crate::probe::{content_range, remote_info};
+use crate::resume::ResumeState;
+use crate::run::RunContext;
+use crate::adaptive;
+use crate::scheduler;
+use crate::retry::{http_status_error, RetryPolicy, StatusClass};
+use crate::single_stream::{Reply, SingleStream};
+use crate::timeouts::Timeouts;
 use indicatif::{ProgressBar};
+use crate::transport::Transport;
 use reqwest::{
-    blocking::Client,
-    header::RANGE,
+    header::{HeaderMap, CONTENT_RANGE, RANGE},
+    StatusCode,
 };
 use std::{
     fs::{File, OpenOptions},
-    io::{Read, Write, Seek, SeekFrom},
+    io::{BufWriter, Write},
     path::Path,
-    sync::{Arc, Mutex},
+    sync::{Arc, atomic::Ordering},
     thread,
-    time::Duration,
 };
-use rayon::prelude::*;
 
 #[cfg(feature = "context")]
 use crate::error::AnyhowContextExt;
 
 /// Represents a chunk of a file to be downloaded
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DownloadChunk {
     pub start_byte: u64,
     pub end_byte: u64,
     pub chunk_id: usize,
+    /// Size of the whole file, which the server's Content-Range must agree with
+    pub total_size: u64,
+}
+
+/// Files smaller than this are never split into ranges
+pub const MIN_CHUNKED_SIZE: u64 = 1024 * 1024;
+
+/// Output path that writes the body to stdout instead of a file
+pub const STDOUT_PATH: &str = "-";
+
+/// What happens to whatever is already at the output path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAction {
+    Create,
+    Overwrite,
+    /// Append the rest of the file, starting at byte `from`
+    Resume { from: u64 },
+    /// The local file is already as long as the remote one
+    Skip,
+}
+
+impl FileAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileAction::Create => "create",
+            FileAction::Overwrite => "overwrite",
+            FileAction::Resume { .. } => "resume",
+            FileAction::Skip => "skip",
+        }
+    }
+}
+
+/// Decide what a single-stream download does with an existing output file
+///
+/// `remote_len` is only called when a partial file could be resumed. When the
+/// server does not report a length a partial file is always resumed.
+pub fn file_action(args: &Args, output_path: &str, remote_len: impl FnOnce() -> Result<Option<u64>>) -> Result<FileAction> {
+    let path = Path::new(output_path);
+    if !path.exists() {
+        return Ok(FileAction::Create);
+    }
+    // Archives unpacked on the fly are always fetched from the start
+    if !args.resume || streaming_format(args, output_path).is_some() {
+        return Ok(FileAction::Overwrite);
+    }
+
+    let downloaded = std::fs::metadata(path)
+        .with_context(|| format!("reading metadata for resume file {}", output_path))?
+        .len();
+    match remote_len()? {
+        Some(len) if downloaded >= len => Ok(FileAction::Skip),
+        _ => Ok(FileAction::Resume { from: downloaded }),
+    }
+}
+
+/// Why a file is fetched as one stream rather than in parallel ranges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingleReason {
+    OneConnection,
+    RawEncoding,
+    StreamingExtract,
+    NoRangeSupport,
+    SmallFile,
+    UnknownLength,
+}
+
+impl SingleReason {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            SingleReason::OneConnection => "--split is 1",
+            SingleReason::RawEncoding => "--compressed --raw keeps encoded bytes",
+            SingleReason::StreamingExtract => "archive is extracted while downloading",
+            SingleReason::NoRangeSupport => "server does not accept byte ranges",
+            SingleReason::SmallFile => "file is smaller than 1 MiB",
+            SingleReason::UnknownLength => "server did not report the file size",
+        }
+    }
+}
+
+/// How a file is transferred
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferPlan {
+    Single(SingleReason),
+    Chunked(Vec<DownloadChunk>),
+}
+
+/// Options that rule out a chunked download before the server is asked anything
+pub fn single_stream_reason(args: &Args, output_path: &str) -> Option<SingleReason> {
+    if scheduler::split(args) <= 1 {
+        Some(SingleReason::OneConnection)
+    } else if args.compressed && args.raw {
+        // Byte ranges of an encoded representation cannot be stitched back together reliably
+        Some(SingleReason::RawEncoding)
+    } else if streaming_format(args, output_path).is_some() {
+        // Streaming extraction needs the body in order
+        Some(SingleReason::StreamingExtract)
+    } else {
+        None
+    }
+}
+
+/// Choose between one stream and parallel ranges for a remote file
+pub fn plan_transfer(args: &Args, content_length: Option<u64>, accepts_ranges: bool) -> TransferPlan {
+    let Some(content_length) = content_length else {
+        // Without a size there is nothing to split; the body is streamed until it ends
+        return TransferPlan::Single(SingleReason::UnknownLength);
+    };
+    if scheduler::split(args) <= 1 {
+        TransferPlan::Single(SingleReason::OneConnection)
+    } else if !accepts_ranges {
+        TransferPlan::Single(SingleReason::NoRangeSupport)
+    } else if content_length < MIN_CHUNKED_SIZE {
+        TransferPlan::Single(SingleReason::SmallFile)
+    } else {
+        TransferPlan::Chunked(plan_chunks(content_length, chunk_count(args, content_length)))
+    }
+}
+
+/// Ranges to split a file into; under --jobs auto without --split one per MiB up
+/// to the per-host maximum, of which only as many as the host's current limit run at once
+fn chunk_count(args: &Args, content_length: u64) -> usize {
+    if adaptive::is_auto(args) && args.split.is_none() {
+        ((content_length / MIN_CHUNKED_SIZE) as usize).clamp(2, adaptive::MAX_CONNECTIONS)
+    } else {
+        scheduler::split(args)
+    }
+}
+
+/// Split `content_length` bytes into `count` ranges, the last taking the remainder
+pub fn plan_chunks(content_length: u64, count: usize) -> Vec<DownloadChunk> {
+    let chunk_size = content_length / count as u64;
+    (0..count)
+        .map(|i| {
+            let start_byte = i as u64 * chunk_size;
+            let end_byte = if i == count - 1 {
+                content_length - 1
+            } else {
+                (start_byte + chunk_size) - 1
+            };
+            DownloadChunk { start_byte, end_byte, chunk_id: i, total_size: content_length }
+        })
+        .collect()
+}
+
+/// How a server answered a ranged request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeReply {
+    /// 206 with the requested bytes
+    Partial,
+    /// 200: the range was ignored and the whole file is on its way
+    Full,
+}
+
+/// Check that a ranged request got the bytes it asked for; `end` and `total`
+/// are only compared when known
+///
+/// Anything other than a matching 206 or a plain 200 is reported as corruption,
+/// since writing it at the requested offset would produce a broken file.
+pub fn check_range_reply(
+    url: &str,
+    status: StatusCode,
+    headers: &HeaderMap,
+    start: u64,
+    end: Option<u64>,
+    total: Option<u64>,
+) -> Result<RangeReply> {
+    if status == StatusCode::OK {
+        return Ok(RangeReply::Full);
+    }
+    let matches = status == StatusCode::PARTIAL_CONTENT
+        && content_range(headers).is_some_and(|(first, last, size)| {
+            first == start
+                && end.is_none_or(|end| last == end)
+                && total.is_none_or(|total| size == Some(total))
+        });
+    if matches {
+        return Ok(RangeReply::Partial);
+    }
+
+    let expected = format!(
+        "bytes {}-{}/{}",
+        start,
+        end.map_or_else(|| "*".to_string(), |end| end.to_string()),
+        total.map_or_else(|| "*".to_string(), |total| total.to_string()),
+    );
+    let got = headers.get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("no Content-Range");
+    Err(RuGetError::new(
+        ErrorCode::E504,
+        ErrorKind::Http,
+        format!("{} answered a request for {} with HTTP {} and {}", url, expected, status.as_u16(), got),
+    ))
+}
+
+/// Abort the transfer if the caller has requested cancellation
+pub fn check_cancelled(run: &RunContext, url: &str) -> Result<()> {
+    match &run.cancel {
+        Some(flag) if flag.load(Ordering::Relaxed) => Err(cancelled_error(url)),
+        _ => Ok(()),
+    }
+}
+
+/// A download stopped because cancellation was requested
+pub fn cancelled_error(url: &str) -> RuGetError {
+    RuGetError::new(ErrorCode::E500, ErrorKind::Network, format!("Download of {} was cancelled", url))
 }
 
 /// Downloads a specific chunk of a file
+///
+/// Returns [`RangeReply::Full`] without writing anything if the server ignored the range.
+#[allow(clippy::too_many_arguments)]
 pub fn download_chunk(
-    client: &Client,
+    client: &dyn Transport,
     url: &str,
     chunk: &DownloadChunk,
     temp_file_path: &str,
     args: &Args,
+    run: &RunContext,
     pb: Option<Arc<ProgressBar>>,
     logger: &Logger,
-) -> Result<()> {
+) -> Result<RangeReply> {
     let mut headers = build_headers(&args.headers, logger);
     add_netrc_auth(&mut headers, url).with_context(|| format!("adding netrc auth for {}", url))?;
-    
-    // Add range header for this specific chunk
-    headers.insert(
-        RANGE,
-        format!("bytes={}-{}", chunk.start_byte, chunk.end_byte)
-            .parse()
-            .with_context(|| format!("creating range header for chunk {}", chunk.chunk_id))?
-    );
 
-    // Create backoff policy from args
-    let backoff_policy = BackoffPolicy::with_params(
-        Duration::from_millis(args.backoff_base_ms),
-        2.0,
-        Duration::from_millis(args.backoff_max_ms),
-        true,
-    );
+    let retry_policy = RetryPolicy::from_args(args, run);
+    let mut retry = retry_policy.start();
+    let timeouts = Timeouts::from_args(args);
+    let chunk_temp_path = format!("{}.chunk.{}", temp_file_path, chunk.chunk_id);
+    let mut file = None;
+    let chunk_len = chunk.end_byte - chunk.start_byte + 1;
+    // Bytes of the chunk already in its file, where a retry continues from
+    let mut bytes_written = 0u64;
 
-    let mut attempt = 0;
     loop {
-        let response = client.get(url).headers(headers.clone()).send()
+        // Add range header for the part of this chunk still missing
+        let start_byte = chunk.start_byte + bytes_written;
+        headers.insert(
+            RANGE,
+            format!("bytes={}-{}", start_byte, chunk.end_byte)
+                .parse()
+                .with_context(|| format!("creating range header for chunk {}", chunk.chunk_id))?
+        );
+
+        let connection = run.connections.acquire(args, url);
+        let response = client.get(url, headers.clone())
             .with_context(|| format!("sending GET request for chunk {} of {}", chunk.chunk_id, url));
 
-        match response {
+        let error = match response {
             Ok(mut resp) => {
                 let status = resp.status();
-                if !status.is_success() && status.as_u16() != 206 {
-                    // Treat HTTP status errors as retryable
-                    attempt += 1;
-                    if attempt > args.max_retries {
-                        return Err(RuGetError::network(format!(
-                            "{}: chunk {} failed with HTTP {} after {} retries", 
-                            url, chunk.chunk_id, status, args.max_retries
-                        )));
+                connection.status(status, logger);
+                match retry_policy.classify(status) {
+                    StatusClass::Success => {}
+                    StatusClass::Fatal => {
+                        return Err(http_status_error(url, status, &format!(" for chunk {}", chunk.chunk_id)));
                     }
-                    
-                    logger.retry_attempt(url, &format!("chunk {} HTTP {}", chunk.chunk_id, status));
-                    let delay = backoff_policy.next_delay(attempt - 1);
-                    thread::sleep(delay);
-                    continue;
+                    StatusClass::Retry => match retry.next(Some(resp.headers())) {
+                        Ok(delay) => {
+                            logger.retry_attempt(url, &format!("chunk {} HTTP {}", chunk.chunk_id, status), delay);
+                            drop(connection);
+                            thread::sleep(delay);
+                            continue;
+                        }
+                        Err(stop) => {
+                            return Err(http_status_error(url, status, &format!(" for chunk {} {}", chunk.chunk_id, stop)));
+                        }
+                    },
+                }
+
+                let reply = check_range_reply(url, status, resp.headers(), start_byte, Some(chunk.end_byte), Some(chunk.total_size))
+                    .map_err(|e| e.with_context(&format!("downloading chunk {}", chunk.chunk_id)))?;
+                if reply == RangeReply::Full {
+                    // Every chunk file would get the whole body, multiplying the file's size
+                    return Ok(reply);
                 }
 
                 // Create a temporary file for this chunk
-                let chunk_temp_path = format!("{}.chunk.{}", temp_file_path, chunk.chunk_id);
-                let mut file = File::create(&chunk_temp_path)
-                    .with_context(|| format!("creating temporary chunk file {}", chunk_temp_path))?;
+                let file = match &mut file {
+                    Some(file) => file,
+                    None => file.insert(File::create(&chunk_temp_path)
+                        .with_context(|| format!("creating temporary chunk file {}", chunk_temp_path))?),
+                };
 
                 let mut buffer = [0u8; 64 * 1024]; // 64KB buffer for better performance
-                let mut bytes_written = 0u64;
-                
-                loop {
-                    let n = resp.read(&mut buffer)
-                        .with_context(|| format!("reading response data for chunk {} from {}", chunk.chunk_id, url))?;
+                let mut watch = timeouts.watch(url);
+                let interrupted = loop {
+                    check_cancelled(run, url)?;
+                    let n = match resp.read_chunk(&mut buffer)
+                        .with_context(|| format!("reading response data for chunk {} from {}", chunk.chunk_id, url))
+                    {
+                        Ok(n) => n,
+                        Err(e) => break Some(e),
+                    };
                     if n == 0 {
-                        break;
+                        // A connection that closes early ends the body without an error
+                        break (bytes_written < chunk_len).then(|| {
+                            truncated_body_error(url, bytes_written, chunk_len)
+                                .with_context(&format!("downloading chunk {}", chunk.chunk_id))
+                        });
                     }
-                    
+                    if let Err(e) = watch.record(n) {
+                        break Some(e);
+                    }
+                    connection.record(n, logger);
+
                     file.write_all(&buffer[..n])
                         .with_context(|| format!("writing chunk {} data to {}", chunk.chunk_id, chunk_temp_path))?;
-                    
+
                     bytes_written += n as u64;
-                    
+
                     if let Some(pb) = &pb {
                         pb.inc(n as u64);
                     }
+                };
+
+                match interrupted {
+                    Some(e) => e,
+                    None => {
+                        logger.info(&format!(
+                            "Chunk {} ({}-{}) downloaded successfully, {} bytes written",
+                            chunk.chunk_id, chunk.start_byte, chunk.end_byte, bytes_written
+                        ));
+                        return Ok(RangeReply::Partial);
+                    }
                 }
-
-                logger.info(&format!(
-                    "Chunk {} ({}-{}) downloaded successfully, {} bytes written",
-                    chunk.chunk_id, chunk.start_byte, chunk.end_byte, bytes_written
-                ));
-
-                return Ok(());
             }
-            Err(e) => {
-                attempt += 1;
-                if attempt > args.max_retries {
-                    let error: RuGetError = e.into();
-                    return Err(error.with_context(&format!(
-                        "downloading chunk {} of {} after {} retries", 
-                        chunk.chunk_id, url, args.max_retries
-                    )));
-                }
+            Err(e) => e,
+        };
 
-                // Check if error is transient - for now, retry all errors
-                // TODO: When we have actual reqwest::Error, check transience properly
-                // if let Some(reqwest_error) = e.source().and_then(|s| s.downcast_ref::<reqwest::Error>()) {
-                //     if !is_transient(reqwest_error) {
-                //         let error: RuGetError = e.into();
-                //         return Err(error.with_context(&format!(
-                //             "downloading chunk {} of {} - non-transient error", 
-                //             chunk.chunk_id, url
-                //         )));
-                //     }
-                // }
-
-                logger.retry_attempt(url, &e.to_string());
-                let delay = backoff_policy.next_delay(attempt - 1);
+        connection.error(&error, logger);
+        drop(connection);
+        if !retry_policy.retries_error(&error) {
+            return Err(error);
+        }
+        match retry.next(None) {
+            Ok(delay) => {
+                logger.retry_attempt(url, &error.to_string(), delay);
                 thread::sleep(delay);
             }
+            Err(stop) => {
+                return Err(error.with_context(&format!("downloading chunk {} of {} {}", chunk.chunk_id, url, stop)));
+            }
+        }
+        if bytes_written > 0 {
+            logger.verbose(&format!(
+                "Continuing chunk {} of {} from byte {}",
+                chunk.chunk_id, url, chunk.start_byte + bytes_written
+            ));
         }
     }
 }
@@ -181,41 +424,39 @@ pub fn combine_chunks(
 
 /// Downloads a file using multiple threads
 pub fn multithreaded_download_url(
-    client: &Client,
+    client: &dyn Transport,
     url: &str,
     output_path: &str,
     args: &Args,
+    run: &RunContext,
     pb: Option<Arc<ProgressBar>>,
     logger: &Logger,
 ) -> Result<()> {
-    // Get file size first
-    let head_response = client.head(url).send()
-        .with_context(|| format!("fetching file info for {}", url))?;
-    
-    let content_length = head_response.content_length()
-        .ok_or_else(|| RuGetError::network("Server did not provide content length".to_string()))?;
-
-    // Check if server supports range requests
-    let accepts_ranges = head_response
-        .headers()
-        .get("accept-ranges")
-        .map(|v| v.to_str().unwrap_or(""))
-        .unwrap_or("");
-
-    if accepts_ranges != "bytes" && args.jobs > 1 {
-        logger.warn("Server does not support range requests, falling back to single-threaded download");
-        // Fall back to single-threaded download
-        return single_threaded_download(client, url, output_path, args, pb, logger);
+    if single_stream_reason(args, output_path).is_some() {
+        return single_threaded_download(client, url, output_path, args, run, pb, logger);
     }
 
-    if content_length < 1024 * 1024 || args.jobs <= 1 {
-        // For small files or single thread requested, use single-threaded download
-        return single_threaded_download(client, url, output_path, args, pb, logger);
-    }
+    let remote = remote_info(client, url, args, run, logger)?;
+    let chunks = match plan_transfer(args, remote.content_length, remote.accepts_ranges) {
+        TransferPlan::Chunked(chunks) => chunks,
+        TransferPlan::Single(reason) => {
+            match reason {
+                SingleReason::NoRangeSupport => {
+                    logger.warn("Server does not support range requests, falling back to single-threaded download");
+                }
+                SingleReason::UnknownLength => {
+                    logger.warn(&format!("Size of {} is unknown, downloading it as a single stream", url));
+                }
+                _ => {}
+            }
+            return single_threaded_download(client, url, output_path, args, run, pb, logger);
+        }
+    };
+    let content_length = remote.content_length.unwrap_or_default();
 
     logger.info(&format!(
         "Starting multi-threaded download of {} bytes using {} threads", 
-        content_length, args.jobs
+        content_length, chunks.len()
     ));
 
     // Create output directory if needed
@@ -223,45 +464,35 @@ pub fn multithreaded_download_url(
         std::fs::create_dir_all(parent)
             .with_context(|| format!("creating output directory for {}", output_path))?;
     }
-
-    // Calculate chunk size
-    let chunk_size = content_length / args.jobs as u64;
-    let mut chunks = Vec::new();
-    
-    for i in 0..args.jobs {
-        let start_byte = i as u64 * chunk_size;
-        let end_byte = if i == args.jobs - 1 {
-            content_length - 1 // Last chunk gets the remainder
-        } else {
-            (start_byte + chunk_size) - 1
-        };
-        
-        chunks.push(DownloadChunk {
-            start_byte,
-            end_byte,
-            chunk_id: i,
-        });
-    }
+    let num_chunks = chunks.len();
 
     // Create temp file path
     let temp_file_path = format!("{}.tmp", output_path);
 
-    // Download chunks in parallel using rayon instead of manual threads    
-    let chunk_results: Vec<Result<()>> = chunks.into_par_iter().map(|chunk| {
-        download_chunk(client, url, &chunk, &temp_file_path, args, pb.clone(), logger)
-    }).collect();
+    // Chunks get their own threads so they never queue behind whole-file jobs
+    // on the file pool; --max-connections and --jobs auto bound how many transfer
+    let chunk_results = scheduler::run_chunks(chunks, |chunk| {
+        download_chunk(client, url, &chunk, &temp_file_path, args, run, pb.clone(), logger)
+    });
 
     // Check if all chunks downloaded successfully
-    for (i, result) in chunk_results.iter().enumerate() {
-        if let Err(e) = result {
-            return Err(RuGetError::network(format!(
-                "Chunk {} failed to download: {}", i, e
-            )));
+    let mut ranges_ignored = false;
+    for (i, result) in chunk_results.into_iter().enumerate() {
+        match result {
+            Ok(reply) => ranges_ignored |= reply == RangeReply::Full,
+            Err(e) => return Err(e.with_context(&format!("chunk {} failed to download", i))),
+        }
+    }
+    if ranges_ignored {
+        logger.warn(&format!("{} ignored the byte range, restarting as a single download", url));
+        for chunk_id in 0..num_chunks {
+            let _ = std::fs::remove_file(format!("{}.chunk.{}", temp_file_path, chunk_id));
         }
+        return single_threaded_download(client, url, output_path, args, run, pb, logger);
     }
 
     // Combine chunks into final file
-    combine_chunks(output_path, &temp_file_path, args.jobs, logger)?;
+    combine_chunks(output_path, &temp_file_path, num_chunks, logger)?;
 
     logger.info(&format!("Multi-threaded download of {} completed successfully", output_path));
     Ok(())
@@ -269,142 +500,204 @@ pub fn multithreaded_download_url(
 
 /// Single-threaded download fallback
 pub fn single_threaded_download(
-    client: &Client,
+    client: &dyn Transport,
     url: &str,
     output_path: &str,
     args: &Args,
+    run: &RunContext,
     pb: Option<Arc<ProgressBar>>,
     logger: &Logger,
 ) -> Result<()> {
     let mut headers = build_headers(&args.headers, logger);
     add_netrc_auth(&mut headers, url).with_context(|| format!("adding netrc auth for {}", url))?;
+    let to_stdout = output_path == STDOUT_PATH;
 
     // Create output directory if needed
-    if let Some(parent) = Path::new(output_path).parent() {
+    if !to_stdout && let Some(parent) = Path::new(output_path).parent() {
         std::fs::create_dir_all(parent)
             .with_context(|| format!("creating output directory for {}", output_path))?;
     }
 
-    // Handle resume logic
-    let mut append_mode = false;
-    if args.resume && Path::new(output_path).exists() {
-        let downloaded = std::fs::metadata(output_path)
-            .with_context(|| format!("reading metadata for resume file {}", output_path))?
-            .len();
+    // Archives unpacked on the fly are always fetched from the start
+    let extract_format = streaming_format(args, output_path);
+    if extract_format.is_some() && args.resume {
+        logger.warn("--resume is not supported when extracting while downloading, starting from scratch");
+    }
 
-        let head_response = client.head(url).send()
-            .with_context(|| format!("fetching remote file length for {}", url))?;
-        
-        // Try both content_length() method and manual header parsing as fallback
-        let auto_len = head_response.content_length();
-        let manual_len = head_response.headers()
-            .get("content-length")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| s.parse::<u64>().ok());
-        
-        // Prefer manual parsing if auto returns 0, otherwise use auto if available
-        let remote_len = if auto_len == Some(0) && manual_len.is_some() {
-            manual_len.unwrap()
-        } else {
-            auto_len.or(manual_len).unwrap_or(0)
-        };
-        
-        
-        if downloaded >= remote_len {
+    // Validators saved by the attempt that wrote the partial file
+    let saved = if to_stdout { None } else { ResumeState::load(output_path) };
+    let action = if to_stdout {
+        FileAction::Create
+    } else {
+        file_action(args, output_path, || remote_info(client, url, args, run, logger).map(|remote| remote.content_length))?
+    };
+    let action = match (action, &saved) {
+        (FileAction::Skip | FileAction::Resume { .. }, Some(saved))
+            if remote_info(client, url, args, run, logger).is_ok_and(|remote| saved.changed(&remote)) =>
+        {
+            logger.download_resume(output_path, 0, "restart", "remote file changed since the partial download");
+            FileAction::Overwrite
+        }
+        (action, _) => action,
+    };
+    let retry_policy = RetryPolicy::from_args(args, run);
+    let mut stream = SingleStream::new(url, &retry_policy);
+    match action {
+        FileAction::Skip => {
             logger.info(&format!("File {} already fully downloaded", output_path));
+            ResumeState::remove(output_path);
             return Ok(());
         }
-
-        headers.insert(RANGE, format!("bytes={}-", downloaded).parse()
-            .with_context(|| format!("creating range header for resume at byte {}", downloaded))?);
-        append_mode = true;
-        logger.info(&format!("Resuming download from byte {}", downloaded));
+        FileAction::Resume { from } => stream.resume(from, saved, output_path, logger),
+        FileAction::Create | FileAction::Overwrite => {}
     }
 
-    let mut file = if append_mode {
-        OpenOptions::new().append(true).open(output_path)
-            .with_context(|| format!("opening file in append mode for {}", output_path))?
-    } else {
-        File::create(output_path)
-            .with_context(|| format!("creating new file for {}", output_path))?
+    let mut extractor = match (extract_format, ExtractOptions::from_args(args, output_path)) {
+        (Some(format), Some(options)) => Some(StreamingExtractor::spawn(format, options)?),
+        _ => None,
     };
 
-    // Create backoff policy from args
-    let backoff_policy = BackoffPolicy::with_params(
-        Duration::from_millis(args.backoff_base_ms),
-        2.0,
-        Duration::from_millis(args.backoff_max_ms),
-        true,
-    );
+    // When extracting, the archive itself is only written with --keep-archive
+    let keep_file = extractor.is_none() || args.keep_archive;
+    // Only opened once a body arrives, so an error reply leaves an existing file as it was
+    let mut file: Option<Box<dyn Write>> = None;
+    if extractor.is_some() {
+        stream.irreversible("extracted");
+    } else if to_stdout {
+        stream.irreversible("written to stdout");
+    }
+
+    let timeouts = Timeouts::from_args(args);
+    let remote_total = || remote_info(client, url, args, run, logger).ok().and_then(|remote| remote.content_length);
 
-    let mut attempt = 0;
     loop {
-        let response = client.get(url).headers(headers.clone()).send()
+        let connection = run.connections.acquire(args, url);
+        let response = client.get(url, stream.request_headers(&headers)?)
             .with_context(|| format!("sending GET request to {}", url));
 
-        match response {
+        let error = match response {
             Ok(mut resp) => {
                 let status = resp.status();
-                if !status.is_success() && status.as_u16() != 206 {
-                    // Treat HTTP status errors as retryable
-                    attempt += 1;
-                    if attempt > args.max_retries {
-                        return Err(RuGetError::network(format!(
-                            "{}: failed with HTTP {} after {} retries", url, status, args.max_retries
-                        )));
+                connection.status(status, logger);
+                match stream.on_response(status, resp.headers(), output_path, remote_total, logger)? {
+                    Reply::Complete => {
+                        ResumeState::remove(output_path);
+                        return Ok(());
+                    }
+                    Reply::Restart => {
+                        // Reopened from the start by the next body
+                        file = None;
+                        continue;
+                    }
+                    Reply::Retry(delay) => {
+                        drop(connection);
+                        thread::sleep(delay);
+                        continue;
+                    }
+                    Reply::Body { fresh, truncate } => {
+                        if truncate {
+                            file = None;
+                        }
+                        if keep_file && file.is_none() {
+                            file = Some(open_output(output_path, stream.offset() > 0)?);
+                        }
+                        // Kept only for If-Range when writing to stdout, nothing is left on disk to resume
+                        if fresh && !to_stdout {
+                            stream.save_validators(output_path, keep_file)?;
+                        }
                     }
-                    
-                    logger.retry_attempt(url, &format!("HTTP {}", status));
-                    let delay = backoff_policy.next_delay(attempt - 1);
-                    thread::sleep(delay);
-                    continue;
                 }
 
-                logger.status(url, &status.to_string());
-                logger.headers(resp.headers());
-
+                // A body that ends early is an interrupted transfer, not a complete one
+                let expected = resp.content_length();
                 let mut buffer = [0u8; 64 * 1024]; // 64KB buffer
-                loop {
-                    let n = resp.read(&mut buffer)
-                        .with_context(|| format!("reading response data from {}", url))?;
+                let mut watch = timeouts.watch(url);
+                let interrupted = loop {
+                    check_cancelled(run, url)?;
+                    let n = match resp.read_chunk(&mut buffer).with_context(|| format!("reading response data from {}", url)) {
+                        Ok(n) => n,
+                        Err(e) => break Some(e),
+                    };
                     if n == 0 {
-                        break;
+                        break stream.end_of_body(expected);
+                    }
+                    // A stalled or overlong transfer is retried like any other interruption
+                    if let Err(e) = watch.record(n) {
+                        break Some(e);
+                    }
+                    connection.record(n, logger);
+                    if let Some(file) = file.as_mut() {
+                        file.write_all(&buffer[..n])
+                            .with_context(|| format!("writing data to {}", output_path))?;
+                    }
+                    if let Some(ex) = extractor.as_mut()
+                        && let Err(e) = ex.write_all(&buffer[..n])
+                    {
+                        return Err(extractor_write_error(extractor.take().unwrap(), e));
                     }
-                    file.write_all(&buffer[..n])
-                        .with_context(|| format!("writing data to {}", output_path))?;
                     if let Some(pb) = &pb {
                         pb.inc(n as u64);
                     }
-                }
+                    stream.advance(n);
+                };
 
-                break;
-            }
-            Err(e) => {
-                attempt += 1;
-                if attempt > args.max_retries {
-                    let error: RuGetError = e.into();
-                    return Err(error.with_context(&format!(
-                        "downloading {} after {} retries", url, args.max_retries
-                    )));
+                match interrupted {
+                    Some(e) => e,
+                    None => break,
                 }
-
-                // Check if error is transient - for now, retry all errors
-                // TODO: When we have actual reqwest::Error, check transience properly
-                // if let Some(reqwest_error) = e.source().and_then(|s| s.downcast_ref::<reqwest::Error>()) {
-                //     if !is_transient(reqwest_error) {
-                //         let error: RuGetError = e.into();
-                //         return Err(error.with_context(&format!(
-                //             "downloading {} - non-transient error", url
-                //         )));
-                //     }
-                // }
-
-                logger.retry_attempt(url, &e.to_string());
-                let delay = backoff_policy.next_delay(attempt - 1);
-                thread::sleep(delay);
             }
-        }
+            Err(e) => e,
+        };
+
+        connection.error(&error, logger);
+        drop(connection);
+        // Continues after the last byte written rather than starting over
+        let delay = stream.on_error(error, logger)?;
+        thread::sleep(delay);
     }
 
+    if let Some(ex) = extractor.take() {
+        let count = ex.finish()?;
+        logger.info(&format!("Extracted {} entries from {}", count, url));
+    }
+
+    if let Some(file) = file.as_mut() {
+        file.flush().with_context(|| format!("writing data to {}", output_path))?;
+    }
+    if !to_stdout {
+        ResumeState::remove(output_path);
+    }
     Ok(())
 }
+
+/// Open the output for a body: stdout, the partial file to append to, or a new file
+fn open_output(output_path: &str, append: bool) -> Result<Box<dyn Write>> {
+    Ok(if output_path == STDOUT_PATH {
+        Box::new(BufWriter::new(std::io::stdout().lock()))
+    } else if append {
+        Box::new(OpenOptions::new().append(true).open(output_path)
+            .with_context(|| format!("opening file in append mode for {}", output_path))?)
+    } else {
+        Box::new(File::create(output_path)
+            .with_context(|| format!("creating new file for {}", output_path))?)
+    })
+}
+
+/// The server sent the whole file again after part of it went somewhere a restart cannot undo
+pub fn restart_error(url: &str, doing: &str) -> RuGetError {
+    RuGetError::new(
+        ErrorCode::E504,
+        ErrorKind::Http,
+        format!("{} sent the whole file again while it was being {}", url, doing),
+    )
+}
+
+/// A body that ended before the length the server announced
+pub fn truncated_body_error(url: &str, received: u64, expected: u64) -> RuGetError {
+    RuGetError::with_source(
+        ErrorCode::E400,
+        ErrorKind::Network,
+        format!("Connection to {} closed after {} of {} bytes", url, received, expected),
+        Box::new(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)),
+    )
+}
@@ -22,19 +22,36 @@ pub enum LogLevel {
     Error,
 }
 
+#[derive(Clone)]
 pub struct Logger {
     pub quiet: bool,
     pub verbose: bool,
     pub json_output: bool,
+    /// Log to stderr, keeping stdout for a downloaded body
+    pub stderr: bool,
 }
 
 impl Logger {
     pub fn new(quiet: bool, verbose: bool) -> Self {
-        Self { quiet, verbose, json_output: false }
+        Self { quiet, verbose, json_output: false, stderr: false }
     }
 
     pub fn new_with_json(quiet: bool, verbose: bool, json_output: bool) -> Self {
-        Self { quiet, verbose, json_output }
+        Self { quiet, verbose, json_output, stderr: false }
+    }
+
+    /// Send log lines to stderr instead of stdout
+    pub fn with_stderr(mut self, stderr: bool) -> Self {
+        self.stderr = stderr;
+        self
+    }
+
+    fn emit(&self, line: &str) {
+        if self.stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
     }
 
     fn log_structured(&self, level: LogLevel, message: &str, context: HashMap<String, String>, code: Option<String>) {
@@ -48,7 +65,7 @@ impl Logger {
 
         if self.json_output {
             if let Ok(json) = serde_json::to_string(&record) {
-                println!("{}", json);
+                self.emit(&json);
             }
         } else {
             self.log_human_readable(&record);
@@ -77,9 +94,9 @@ impl Logger {
         };
 
         if !code_str.is_empty() {
-            println!("[{}][{}][{}] {}{}", code_str, level_str, timestamp, record.message, context_str);
+            self.emit(&format!("[{}][{}][{}] {}{}", code_str, level_str, timestamp, record.message, context_str));
         } else {
-            println!("[{}][{}] {}{}", level_str, timestamp, record.message, context_str);
+            self.emit(&format!("[{}][{}] {}{}", level_str, timestamp, record.message, context_str));
         }
     }
 
@@ -178,12 +195,15 @@ impl Logger {
         }
     }
 
-    pub fn download_resume(&self, output_path: &str, bytes: u64) {
+    /// What --resume decided to do with a partial file, and why
+    pub fn download_resume(&self, output_path: &str, bytes: u64, decision: &str, reason: &str) {
         if !self.quiet {
             let mut context = HashMap::new();
             context.insert("output_path".to_string(), output_path.to_string());
             context.insert("resume_bytes".to_string(), bytes.to_string());
-            self.log_structured(LogLevel::Info, "Download resumed", context, None);
+            context.insert("decision".to_string(), decision.to_string());
+            context.insert("reason".to_string(), reason.to_string());
+            self.log_structured(LogLevel::Info, "Resume decision", context, None);
         }
     }
 
@@ -195,15 +215,45 @@ impl Logger {
         }
     }
 
-    pub fn retry_attempt(&self, url: &str, error: &str) {
+    pub fn retry_attempt(&self, url: &str, error: &str, delay: std::time::Duration) {
         if !self.quiet {
             let mut context = HashMap::new();
             context.insert("url".to_string(), url.to_string());
             context.insert("error".to_string(), error.to_string());
+            context.insert("delay_ms".to_string(), delay.as_millis().to_string());
             self.log_structured(LogLevel::Warn, "Retrying after error", context, None);
         }
     }
 
+    pub fn circuit_change(&self, host: &str, state: &str, reason: &str) {
+        if !self.quiet {
+            let mut context = HashMap::new();
+            context.insert("host".to_string(), host.to_string());
+            context.insert("state".to_string(), state.to_string());
+            context.insert("reason".to_string(), reason.to_string());
+            let level = if state == "open" { LogLevel::Warn } else { LogLevel::Info };
+            self.log_structured(level, "Host circuit changed", context, None);
+        }
+    }
+
+    pub fn hook_executed(&self, hook: &str, command: &str, exit_code: Option<i32>) {
+        let mut context = HashMap::new();
+        context.insert("hook".to_string(), hook.to_string());
+        context.insert("command".to_string(), command.to_string());
+        context.insert(
+            "exit_code".to_string(),
+            exit_code.map(|c| c.to_string()).unwrap_or_else(|| "signal".to_string()),
+        );
+
+        if exit_code == Some(0) {
+            if self.verbose && !self.quiet {
+                self.log_structured(LogLevel::Debug, "Hook finished", context, None);
+            }
+        } else if !self.quiet {
+            self.log_structured(LogLevel::Warn, "Hook failed", context, None);
+        }
+    }
+
     pub fn summary(&self, successful: usize, total: usize) {
         if !self.quiet {
             let mut context = HashMap::new();
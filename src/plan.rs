@@ -0,0 +1,240 @@
+use crate::cli::{Args, ReportFormat};
+use crate::scheduler::file_pool;
+use crate::download::{job_args, output_path_for};
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError};
+use crate::file_utils::write_report;
+use crate::manifest::{DownloadSpec, JobStream};
+use crate::multithreaded_download::{
+    file_action, plan_transfer, single_stream_reason, FileAction, TransferPlan,
+};
+use crate::output::Logger;
+use crate::probe::remote_info;
+use crate::run::RunContext;
+use crate::transport::{build_transport, Transport};
+use rayon::prelude::*;
+use reqwest::cookie::Jar;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+
+/// What `download()` would do for one job, worked out without writing anything
+#[derive(Debug, Clone, Serialize)]
+pub struct JobPlan {
+    pub url: String,
+    pub output_path: String,
+    /// `create`, `overwrite`, `resume` or `skip`
+    pub action: &'static str,
+    /// First byte requested when resuming
+    pub resume_from: Option<u64>,
+    /// Length reported by HEAD
+    pub size: Option<u64>,
+    pub accepts_ranges: Option<bool>,
+    /// `single` or `chunked`
+    pub strategy: &'static str,
+    pub chunks: usize,
+    /// Why the file would be fetched as one stream
+    pub reason: Option<&'static str>,
+    pub mirrors: Vec<String>,
+    pub checksum: Option<String>,
+    /// Problem that would stop the download, such as a failed HEAD request
+    pub error: Option<String>,
+}
+
+impl JobPlan {
+    /// Bytes still to be fetched
+    pub fn transfer_bytes(&self) -> Option<u64> {
+        match self.action {
+            "skip" => Some(0),
+            _ => self.size.map(|size| size - self.resume_from.unwrap_or(0).min(size)),
+        }
+    }
+}
+
+/// Resolve every job the way `download()` would and print the plan
+///
+/// Only HEAD requests are sent; no files, cookies or failure logs are written
+/// apart from the --report file itself.
+pub fn dry_run(args: Args, jobs: JobStream, logger: &Logger) -> Result<()> {
+    let known_specs = jobs.known_specs();
+    if args.output.is_some() && known_specs.is_none_or(|specs| specs.len() > 1) {
+        return Err(RuGetError::parse("Cannot use --output with multiple URLs".into()));
+    }
+
+    let transport = build_transport(&args, Arc::new(Jar::default()), logger)?;
+    let pool = file_pool(&args)?;
+
+    let args = Arc::new(args);
+    let run = RunContext::default();
+    let specs: Vec<DownloadSpec> = jobs
+        .filter_map(|job| match job {
+            Ok(spec) => Some(spec),
+            Err(e) => {
+                logger.error_from_ruget_error(&e);
+                None
+            }
+        })
+        .collect();
+    let plans: Vec<JobPlan> = pool.install(|| specs
+        .par_iter()
+        .map(|spec| plan_job(transport.as_ref(), spec, &job_args(&args, spec), &run, logger))
+        .collect());
+
+    let rendered = match args.report_format {
+        ReportFormat::Json => render_json(&plans)?,
+        ReportFormat::Text => render_text(&plans),
+    };
+    write_report(&rendered, &args)
+}
+
+/// Work out the output path, existing-file action and transfer strategy for one job
+pub fn plan_job(client: &dyn Transport, spec: &DownloadSpec, args: &Args, run: &RunContext, logger: &Logger) -> JobPlan {
+    let output_path = output_path_for(client, spec, args, run, logger);
+    let remote = remote_info(client, &spec.url, args, run, logger);
+
+    let mut plan = JobPlan {
+        url: spec.url.clone(),
+        output_path,
+        action: FileAction::Create.as_str(),
+        resume_from: None,
+        size: None,
+        accepts_ranges: None,
+        strategy: "single",
+        chunks: 1,
+        reason: None,
+        mirrors: spec.mirrors.clone(),
+        checksum: spec.checksum.as_ref().map(ToString::to_string),
+        error: None,
+    };
+
+    let remote = match remote {
+        Ok(remote) => {
+            plan.size = remote.content_length;
+            plan.accepts_ranges = Some(remote.accepts_ranges);
+            Some(remote)
+        }
+        Err(e) => {
+            plan.error = Some(e.message);
+            None
+        }
+    };
+
+    // Mirrors `download_one`: only a --split > 1 run can split a file, and a
+    // split download always rewrites the file from scratch
+    let transfer = match single_stream_reason(args, &plan.output_path) {
+        Some(reason) => Some(TransferPlan::Single(reason)),
+        None => remote.as_ref().map(|remote| plan_transfer(args, remote.content_length, remote.accepts_ranges)),
+    };
+
+    match transfer {
+        Some(TransferPlan::Chunked(chunks)) => {
+            plan.strategy = "chunked";
+            plan.chunks = chunks.len();
+            if Path::new(&plan.output_path).exists() {
+                plan.action = FileAction::Overwrite.as_str();
+            }
+        }
+        Some(TransferPlan::Single(reason)) => {
+            plan.reason = Some(reason.describe());
+            let remote_len = || match &remote {
+                Some(remote) => Ok(remote.content_length),
+                None => Err(RuGetError::network(format!("No file info for {}", spec.url))),
+            };
+            match file_action(args, &plan.output_path, remote_len) {
+                Ok(action) => {
+                    plan.action = action.as_str();
+                    match action {
+                        FileAction::Resume { from } => plan.resume_from = Some(from),
+                        FileAction::Skip => plan.chunks = 0,
+                        FileAction::Create | FileAction::Overwrite => {}
+                    }
+                }
+                Err(e) => plan.error = plan.error.take().or(Some(e.message)),
+            }
+        }
+        None => {}
+    }
+
+    plan
+}
+
+fn render_json(plans: &[JobPlan]) -> Result<String> {
+    let document = serde_json::json!({
+        "jobs": plans.len(),
+        "errors": plans.iter().filter(|p| p.error.is_some()).count(),
+        "transfer_bytes": plans.iter().filter_map(JobPlan::transfer_bytes).sum::<u64>(),
+        "plans": plans,
+    });
+    serde_json::to_string_pretty(&document)
+        .map(|json| json + "\n")
+        .map_err(|e| RuGetError::new(ErrorCode::E500, ErrorKind::Parse, format!("Serializing dry-run plan: {}", e)))
+}
+
+fn render_text(plans: &[JobPlan]) -> String {
+    let mut out = format!("{:<9} {:>12}  {:<6} {:>6}  {:<30} URL\n", "ACTION", "SIZE", "RANGES", "CHUNKS", "OUTPUT");
+    for plan in plans {
+        let action = match (plan.error.is_some(), plan.resume_from) {
+            (true, _) => "error".to_string(),
+            (false, Some(from)) => format!("resume@{}", from),
+            (false, None) => plan.action.to_string(),
+        };
+        let size = plan.size.map(|s| s.to_string()).unwrap_or_else(|| "?".to_string());
+        let ranges = match plan.accepts_ranges {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "?",
+        };
+        out.push_str(&format!(
+            "{:<9} {:>12}  {:<6} {:>6}  {:<30} {}\n",
+            action, size, ranges, plan.chunks, plan.output_path, plan.url
+        ));
+        if let Some(error) = &plan.error {
+            out.push_str(&format!("          error: {}\n", error));
+        }
+    }
+
+    let unknown = plans.iter().filter(|p| p.transfer_bytes().is_none()).count();
+    let bytes: u64 = plans.iter().filter_map(JobPlan::transfer_bytes).sum();
+    out.push_str(&format!("\n{} jobs, {} bytes to transfer", plans.len(), bytes));
+    if unknown > 0 {
+        out.push_str(&format!(" (+{} of unknown size)", unknown));
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(action: &'static str, size: Option<u64>, resume_from: Option<u64>) -> JobPlan {
+        JobPlan {
+            url: "https://example.com/a.bin".into(),
+            output_path: "a.bin".into(),
+            action,
+            resume_from,
+            size,
+            accepts_ranges: Some(true),
+            strategy: "single",
+            chunks: 1,
+            reason: None,
+            mirrors: Vec::new(),
+            checksum: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_transfer_bytes() {
+        assert_eq!(plan("create", Some(100), None).transfer_bytes(), Some(100));
+        assert_eq!(plan("resume", Some(100), Some(40)).transfer_bytes(), Some(60));
+        assert_eq!(plan("skip", Some(100), None).transfer_bytes(), Some(0));
+        assert_eq!(plan("create", None, None).transfer_bytes(), None);
+    }
+
+    #[test]
+    fn test_render_text_totals() {
+        let text = render_text(&[plan("create", Some(100), None), plan("resume", Some(100), Some(40)), plan("create", None, None)]);
+        assert!(text.contains("resume@40"));
+        assert!(text.ends_with("3 jobs, 160 bytes to transfer (+1 of unknown size)\n"));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::cli::Args;
+use crate::encoding::identity_encoding;
+use crate::error::{Result, WithContext};
+use crate::http::{build_headers, extract_filename_from_disposition};
+use crate::output::Logger;
+use crate::run::RunContext;
+use crate::transport::Transport;
+use reqwest::{
+    header::{
+        HeaderMap, HeaderValue, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+        ETAG, LAST_MODIFIED, RANGE,
+    },
+    StatusCode,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[cfg(feature = "context")]
+use crate::error::AnyhowContextExt;
+
+/// What a HEAD request reports about a remote file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteInfo {
+    pub content_length: Option<u64>,
+    pub accepts_ranges: bool,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// File name suggested by Content-Disposition
+    pub disposition_name: Option<String>,
+    pub content_type: Option<HeaderValue>,
+    /// URL the request ended up at after redirects
+    pub final_url: Option<String>,
+}
+
+impl RemoteInfo {
+    /// Send a HEAD request asking for the unencoded length, so byte offsets
+    /// line up with the bytes written to disk
+    ///
+    /// Object stores and signed URLs often refuse HEAD (403, 405) or leave out
+    /// the length, so those answers are followed by a one-byte ranged GET.
+    pub fn probe(client: &dyn Transport, url: &str, headers: HeaderMap) -> Result<Self> {
+        let (name, value) = identity_encoding();
+        let mut head_headers = headers.clone();
+        head_headers.insert(name, value);
+        let response = client.head(url, head_headers)
+            .with_context(|| format!("fetching file info for {}", url))?;
+        let mut info = Self::from_headers(response.content_length(), response.headers());
+        info.final_url = Some(response.url().to_string());
+
+        let status = response.status();
+        let head_refused = matches!(
+            status,
+            StatusCode::FORBIDDEN | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+        );
+        if head_refused || (status.is_success() && info.content_length.is_none()) {
+            // Without a better answer the download itself reports any error status;
+            // the length of a refusal is that of its error page, not of the file
+            let info = Self { content_length: info.content_length.filter(|_| !head_refused), ..info };
+            return Ok(Self::probe_range(client, url, headers).unwrap_or(info));
+        }
+        Ok(info)
+    }
+
+    /// Ask for the first byte; a 206 carries the full size in Content-Range,
+    /// while a 200 means the server ignores ranges and is sending the whole body
+    fn probe_range(client: &dyn Transport, url: &str, mut headers: HeaderMap) -> Option<Self> {
+        let (name, value) = identity_encoding();
+        headers.insert(name, value);
+        headers.insert(RANGE, HeaderValue::from_static("bytes=0-0"));
+        let response = client.get(url, headers).ok()?;
+        let mut info = match response.status() {
+            StatusCode::PARTIAL_CONTENT => Self {
+                content_length: content_range_total(response.headers()),
+                accepts_ranges: true,
+                ..Self::from_headers(None, response.headers())
+            },
+            status if status.is_success() => Self {
+                accepts_ranges: false,
+                ..Self::from_headers(response.content_length(), response.headers())
+            },
+            _ => return None,
+        };
+        // Dropping the response closes the connection without reading the body
+        info.final_url = Some(response.url().to_string());
+        Some(info)
+    }
+
+    pub fn from_headers(body_length: Option<u64>, headers: &HeaderMap) -> Self {
+        let text = |name| headers.get(name).and_then(|v: &HeaderValue| v.to_str().ok());
+
+        // reqwest can report a zero body length for HEAD responses, so the
+        // Content-Length header itself wins in that case
+        let header_length = text(CONTENT_LENGTH).and_then(|s| s.parse::<u64>().ok());
+        let content_length = match body_length {
+            Some(0) if header_length.is_some() => header_length,
+            _ => body_length.or(header_length),
+        };
+
+        Self {
+            content_length,
+            accepts_ranges: text(ACCEPT_RANGES).is_some_and(|v| v == "bytes"),
+            etag: text(ETAG).map(str::to_string),
+            last_modified: text(LAST_MODIFIED).map(str::to_string),
+            disposition_name: extract_filename_from_disposition(headers.get(CONTENT_DISPOSITION)),
+            content_type: headers.get(CONTENT_TYPE).cloned(),
+            final_url: None,
+        }
+    }
+}
+
+/// Total size from a `Content-Range: bytes 0-0/12345` header, unless it is `*`
+pub fn content_range_total(headers: &HeaderMap) -> Option<u64> {
+    content_range(headers)?.2
+}
+
+/// First byte, last byte and total size (`None` for `*`) from a `Content-Range` header
+pub fn content_range(headers: &HeaderMap) -> Option<(u64, u64, Option<u64>)> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.trim().split_once('-')?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((start.parse().ok()?, end.parse().ok()?, total))
+}
+
+/// Total size from the `Content-Range: bytes */N` of a 416 reply
+pub fn unsatisfied_range_total(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    value.strip_prefix("bytes */")?.trim().parse().ok()
+}
+
+/// Probe results shared by every stage of a job, so that sizing the progress
+/// bar, naming the file, planning chunks and resuming cost one HEAD per URL
+///
+/// Entries are keyed on the URL and the headers sent with the probe, since a
+/// manifest entry's own headers can change what the server answers. Only
+/// successful probes are kept; a failed one is tried again by the next stage.
+#[derive(Debug, Default)]
+pub struct ProbeCache {
+    entries: Mutex<HashMap<(String, Vec<String>), RemoteInfo>>,
+}
+
+impl ProbeCache {
+    pub fn get(&self, url: &str, headers: &[String]) -> Option<RemoteInfo> {
+        self.entries.lock().unwrap().get(&probe_key(url, headers)).cloned()
+    }
+
+    pub fn insert(&self, url: &str, headers: &[String], info: RemoteInfo) {
+        self.entries.lock().unwrap().insert(probe_key(url, headers), info);
+    }
+
+    /// Forget the probe once its job is done, so a long run does not keep every result
+    pub fn remove(&self, url: &str, headers: &[String]) {
+        self.entries.lock().unwrap().remove(&probe_key(url, headers));
+    }
+}
+
+fn probe_key(url: &str, headers: &[String]) -> (String, Vec<String>) {
+    (url.to_string(), headers.to_vec())
+}
+
+/// Metadata for `url`, probing it only if this run has not already
+pub fn remote_info(client: &dyn Transport, url: &str, args: &Args, run: &RunContext, logger: &Logger) -> Result<RemoteInfo> {
+    if let Some(info) = run.probes.get(url, &args.headers) {
+        return Ok(info);
+    }
+    let info = RemoteInfo::probe(client, url, build_headers(&args.headers, logger))?;
+    run.probes.insert(url, &args.headers, info.clone());
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_cache_is_keyed_on_headers() {
+        let cache = ProbeCache::default();
+        let url = "http://example.com/a";
+        let token = vec!["Authorization: Bearer x".to_string()];
+        cache.insert(url, &token, RemoteInfo { content_length: Some(10), ..RemoteInfo::default() });
+
+        assert!(cache.get(url, &[]).is_none());
+        assert_eq!(cache.get(url, &token).unwrap().content_length, Some(10));
+
+        cache.remove(url, &token);
+        assert!(cache.get(url, &token).is_none());
+    }
+
+    #[test]
+    fn test_from_headers_collects_metadata() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, "1234".parse().unwrap());
+        headers.insert(ACCEPT_RANGES, "bytes".parse().unwrap());
+        headers.insert(ETAG, "\"abc\"".parse().unwrap());
+        headers.insert(LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+        headers.insert(CONTENT_DISPOSITION, "attachment; filename=\"report.pdf\"".parse().unwrap());
+
+        let info = RemoteInfo::from_headers(Some(0), &headers);
+        assert_eq!(info.content_length, Some(1234));
+        assert!(info.accepts_ranges);
+        assert_eq!(info.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(info.last_modified.as_deref(), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(info.disposition_name.as_deref(), Some("report.pdf"));
+    }
+
+    #[test]
+    fn test_content_range_total() {
+        let total = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_RANGE, value.parse().unwrap());
+            content_range_total(&headers)
+        };
+        assert_eq!(total("bytes 0-0/12345"), Some(12345));
+        assert_eq!(total("bytes 0-0/*"), None);
+        assert_eq!(total("items 0-0/5"), None);
+        assert_eq!(content_range_total(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_unsatisfied_range_total() {
+        let total = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_RANGE, value.parse().unwrap());
+            unsatisfied_range_total(&headers)
+        };
+        assert_eq!(total("bytes */10"), Some(10));
+        assert_eq!(total("bytes 0-9/10"), None);
+        assert_eq!(unsatisfied_range_total(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_content_range() {
+        let range = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_RANGE, value.parse().unwrap());
+            content_range(&headers)
+        };
+        assert_eq!(range("bytes 100-199/1000"), Some((100, 199, Some(1000))));
+        assert_eq!(range("bytes 100-199/*"), Some((100, 199, None)));
+        assert_eq!(range("bytes */1000"), None);
+        assert_eq!(range("bytes 100-abc/1000"), None);
+    }
+
+    #[test]
+    fn test_from_headers_without_metadata() {
+        let info = RemoteInfo::from_headers(None, &HeaderMap::new());
+        assert_eq!(info, RemoteInfo::default());
+    }
+}
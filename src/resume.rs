@@ -0,0 +1,124 @@
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError, WithContext};
+use crate::probe::RemoteInfo;
+use reqwest::header::{HeaderMap, HeaderValue, ETAG, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[cfg(feature = "context")]
+use crate::error::AnyhowContextExt;
+
+/// Suffix of the sidecar file kept next to a download until it completes
+pub const SIDECAR_SUFFIX: &str = ".ruget-resume";
+
+/// Validators of the remote file a partial download came from
+///
+/// Saved beside the output while it is being written, so that a later --resume
+/// can tell whether the remote file has changed since and must be fetched again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeState {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl ResumeState {
+    /// Validators from the response that started a download
+    pub fn from_headers(url: &str, headers: &HeaderMap) -> Self {
+        let text = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok()).map(str::to_string);
+        Self {
+            url: url.to_string(),
+            etag: text(ETAG),
+            last_modified: text(LAST_MODIFIED),
+        }
+    }
+
+    /// Whether there is anything to check a later resume against
+    pub fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+
+    pub fn load(output_path: &str) -> Option<Self> {
+        let content = fs::read_to_string(sidecar_path(output_path)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, output_path: &str) -> Result<()> {
+        let path = sidecar_path(output_path);
+        let content = serde_json::to_string(self)
+            .map_err(|e| RuGetError::new(ErrorCode::E500, ErrorKind::Parse, format!("Serializing resume state: {}", e)))?;
+        fs::write(&path, content).with_context(|| format!("writing resume state {}", path))
+    }
+
+    /// Forget the validators once the download is complete
+    pub fn remove(output_path: &str) {
+        let _ = fs::remove_file(sidecar_path(output_path));
+    }
+
+    /// Value for an `If-Range` header: a strong ETag, else Last-Modified
+    ///
+    /// Weak ETags are not allowed in `If-Range`.
+    pub fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    /// Whether the probed remote file is known to differ from the one the
+    /// partial download came from; validators the server did not send again
+    /// cannot tell either way
+    pub fn changed(&self, remote: &RemoteInfo) -> bool {
+        let differs = |saved: &Option<String>, now: &Option<String>| {
+            matches!((saved, now), (Some(saved), Some(now)) if saved != now)
+        };
+        differs(&self.etag, &remote.etag) || differs(&self.last_modified, &remote.last_modified)
+    }
+}
+
+pub fn sidecar_path(output_path: &str) -> String {
+    format!("{}{}", output_path, SIDECAR_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn state(etag: Option<&str>, last_modified: Option<&str>) -> ResumeState {
+        ResumeState {
+            url: "http://example.com/a".into(),
+            etag: etag.map(Into::into),
+            last_modified: last_modified.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn test_if_range_prefers_strong_etag() {
+        let date = "Wed, 21 Oct 2015 07:28:00 GMT";
+        assert_eq!(state(Some("\"v1\""), Some(date)).if_range(), Some("\"v1\""));
+        assert_eq!(state(Some("W/\"v1\""), Some(date)).if_range(), Some(date));
+        assert_eq!(state(Some("W/\"v1\""), None).if_range(), None);
+    }
+
+    #[test]
+    fn test_changed_compares_known_validators() {
+        let saved = state(Some("\"v1\""), None);
+        let remote = |etag: Option<&str>| RemoteInfo { etag: etag.map(Into::into), ..Default::default() };
+        assert!(!saved.changed(&remote(Some("\"v1\""))));
+        assert!(saved.changed(&remote(Some("\"v2\""))));
+        assert!(!saved.changed(&remote(None)));
+    }
+
+    #[test]
+    fn test_sidecar_round_trip() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("file.bin");
+        let output = output.to_str().unwrap();
+        let saved = state(Some("\"v1\""), None);
+
+        saved.save(output).unwrap();
+        assert_eq!(ResumeState::load(output), Some(saved));
+        ResumeState::remove(output);
+        assert_eq!(ResumeState::load(output), None);
+    }
+}
@@ -1,6 +1,29 @@
+use std::fmt;
 use std::time::Duration;
 use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use rand::Rng;
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use crate::cli::Args;
+use crate::error::{ErrorCode, ErrorKind, RuGetError};
+use crate::run::RunContext;
+
+/// How successive retry delays are spread out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BackoffStrategy {
+    /// base * factor^n, varied by ±25%
+    #[default]
+    Exponential,
+    /// Uniformly random between zero and base * factor^n
+    FullJitter,
+    /// Uniformly random between base and factor times the previous delay
+    Decorrelated,
+}
 
 /// Exponential backoff policy with jitter
 #[derive(Debug, Clone)]
@@ -9,6 +32,7 @@ pub struct BackoffPolicy {
     pub factor: f64,
     pub max_delay: Duration,
     pub jitter: bool,
+    pub strategy: BackoffStrategy,
 }
 
 impl BackoffPolicy {
@@ -19,6 +43,7 @@ impl BackoffPolicy {
             factor: 2.0,
             max_delay: Duration::from_secs(60),
             jitter: true,
+            strategy: BackoffStrategy::Exponential,
         }
     }
 
@@ -29,28 +54,72 @@ impl BackoffPolicy {
             factor,
             max_delay,
             jitter,
+            strategy: BackoffStrategy::Exponential,
         }
     }
 
+    /// Create the backoff policy configured by --backoff-* flags
+    pub fn from_args(args: &Args) -> Self {
+        Self::with_params(
+            Duration::from_millis(args.backoff_base_ms),
+            args.backoff_factor,
+            Duration::from_millis(args.backoff_max_ms),
+            true,
+        )
+        .with_strategy(args.backoff_strategy)
+    }
+
+    pub fn with_strategy(mut self, strategy: BackoffStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
     /// Calculate the next delay for the given attempt number (0-indexed)
     pub fn next_delay(&self, attempt: u32) -> Duration {
         // Calculate base delay: base_delay * factor^attempt
         let base_millis = self.base_delay.as_millis() as f64;
-    let calculated_delay = base_millis * self.factor.powi(attempt as i32);
-    
-    // Cap at max_delay
-    let capped_delay = calculated_delay.min(self.max_delay.as_millis() as f64);
-    
-    let final_delay = if self.jitter {
-        // Add jitter: randomly vary by ±25%
-        let mut rng = rand::thread_rng();
-        let jitter_factor = rng.gen_range(0.75..=1.25);
-        capped_delay * jitter_factor
-    } else {
-        capped_delay
-    };
+        let calculated_delay = base_millis * self.factor.powi(attempt as i32);
+
+        // Cap at max_delay
+        let capped_delay = calculated_delay.min(self.max_delay.as_millis() as f64);
+
+        let final_delay = match (self.jitter, self.strategy) {
+            (false, _) => capped_delay,
+            // Add jitter: randomly vary by ±25%
+            (true, BackoffStrategy::Exponential) => capped_delay * rand::thread_rng().gen_range(0.75..=1.25),
+            (true, _) => rand::thread_rng().gen_range(0.0..=capped_delay),
+        };
+
+        Duration::from_millis(final_delay as u64)
+    }
+
+    /// Calculate the delay for `attempt` given the delay used before it,
+    /// which only the decorrelated strategy depends on
+    pub fn delay_after(&self, attempt: u32, previous: Option<Duration>) -> Duration {
+        let (BackoffStrategy::Decorrelated, Some(previous)) = (self.strategy, previous) else {
+            return self.next_delay(attempt);
+        };
+
+        let base_millis = self.base_delay.as_millis() as f64;
+        let upper = (previous.as_millis() as f64 * self.factor)
+            .min(self.max_delay.as_millis() as f64)
+            .max(base_millis);
+        let final_delay = if self.jitter && upper > base_millis {
+            rand::thread_rng().gen_range(base_millis..=upper)
+        } else {
+            upper
+        };
+
+        Duration::from_millis(final_delay as u64)
+    }
 
-    Duration::from_millis(final_delay as u64)
+    /// Delay before retrying a response: the server's `Retry-After` if it sent
+    /// one, capped at `max_delay`, otherwise the usual backoff
+    pub fn delay_for_response(&self, attempt: u32, previous: Option<Duration>, headers: Option<&HeaderMap>) -> Duration {
+        match headers.and_then(|h| retry_after(h, Utc::now())) {
+            Some(delay) => delay.min(self.max_delay),
+            None => self.delay_after(attempt, previous),
+        }
     }
 }
 
@@ -60,6 +129,272 @@ impl Default for BackoffPolicy {
     }
 }
 
+/// How a download reacts to an HTTP response status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusClass {
+    Success,
+    /// Timeouts, rate limiting and server errors may succeed later
+    Retry,
+    /// Any other client error will not change on retry
+    Fatal,
+}
+
+fn classify_with(status: StatusCode, retry_on: &[RetryCondition]) -> StatusClass {
+    if status.is_success() {
+        StatusClass::Success
+    } else if retry_on.iter().any(|c| c.matches_status(status)) {
+        StatusClass::Retry
+    } else {
+        StatusClass::Fatal
+    }
+}
+
+/// A response status or class of network failure that is worth retrying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryCondition {
+    /// One status code, e.g. `429`
+    Status(u16),
+    /// Every status in a hundred, e.g. `5xx`
+    StatusClass(u16),
+    /// Connect, read, --max-time and --speed-limit timeouts
+    Timeout,
+    /// Connections that could not be established
+    Connect,
+    /// Connections reset or closed mid-transfer
+    Reset,
+}
+
+/// What is retried when --retry-on is not given
+pub const DEFAULT_RETRY_ON: &[RetryCondition] = &[
+    RetryCondition::Status(408),
+    RetryCondition::Status(425),
+    RetryCondition::Status(429),
+    RetryCondition::StatusClass(5),
+    RetryCondition::Timeout,
+    RetryCondition::Connect,
+    RetryCondition::Reset,
+];
+
+impl RetryCondition {
+    fn matches_status(&self, status: StatusCode) -> bool {
+        match self {
+            RetryCondition::Status(code) => status.as_u16() == *code,
+            RetryCondition::StatusClass(class) => status.as_u16() / 100 == *class,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for RetryCondition {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, String> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "timeout" => return Ok(RetryCondition::Timeout),
+            "connect" => return Ok(RetryCondition::Connect),
+            "reset" => return Ok(RetryCondition::Reset),
+            _ => {}
+        }
+        if let Some(class) = value.strip_suffix("xx")
+            && let Ok(class @ 1..=5) = class.parse::<u16>()
+        {
+            return Ok(RetryCondition::StatusClass(class));
+        }
+        match value.parse::<u16>() {
+            Ok(code @ 100..=599) => Ok(RetryCondition::Status(code)),
+            _ => Err(format!(
+                "'{}' is not a status code, status class (e.g. 5xx), timeout, connect or reset",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RetryCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryCondition::Status(code) => write!(f, "{}", code),
+            RetryCondition::StatusClass(class) => write!(f, "{}xx", class),
+            RetryCondition::Timeout => f.write_str("timeout"),
+            RetryCondition::Connect => f.write_str("connect"),
+            RetryCondition::Reset => f.write_str("reset"),
+        }
+    }
+}
+
+/// The class of network failure behind an error, if it is one that may clear up
+pub fn error_condition(error: &RuGetError) -> Option<RetryCondition> {
+    match error.code {
+        ErrorCode::E404 => return Some(RetryCondition::Timeout),
+        ErrorCode::E201 | ErrorCode::E401 => return Some(RetryCondition::Connect),
+        _ => {}
+    }
+    let source = error.source.as_deref()?;
+    if let Some(reqwest_error) = source.downcast_ref::<reqwest::Error>() {
+        return match (reqwest_error.is_timeout(), reqwest_error.is_connect()) {
+            (true, _) => Some(RetryCondition::Timeout),
+            (false, true) => Some(RetryCondition::Connect),
+            _ if is_transient(reqwest_error) => Some(RetryCondition::Reset),
+            _ => None,
+        };
+    }
+    let io_error = source.downcast_ref::<std::io::Error>()?;
+    io_condition(io_error).or_else(|| {
+        // Bodies read through std::io::Read wrap reqwest's own errors
+        io_error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+            .filter(|inner| is_transient(inner))
+            .map(|_| RetryCondition::Reset)
+    })
+}
+
+fn io_condition(error: &std::io::Error) -> Option<RetryCondition> {
+    use std::io::ErrorKind::*;
+    match error.kind() {
+        TimedOut | WouldBlock => Some(RetryCondition::Timeout),
+        ConnectionRefused | HostUnreachable | NetworkUnreachable => Some(RetryCondition::Connect),
+        ConnectionReset | ConnectionAborted | BrokenPipe | UnexpectedEof => Some(RetryCondition::Reset),
+        _ => None,
+    }
+}
+
+/// Retries shared by every download in a run, so one dead host cannot spend them all
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+    limit: Option<u32>,
+    spent: Arc<AtomicU32>,
+}
+
+impl RetryBudget {
+    pub fn new(limit: Option<u32>, spent: Arc<AtomicU32>) -> Self {
+        Self { limit, spent }
+    }
+
+    /// Take one retry from the budget, or return false once it is used up
+    pub fn try_spend(&self) -> bool {
+        match self.limit {
+            None => {
+                self.spent.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Some(limit) => self.spent
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |spent| (spent < limit).then_some(spent + 1))
+                .is_ok(),
+        }
+    }
+}
+
+/// Everything that decides whether and when a failed request is tried again
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: BackoffPolicy,
+    pub retry_on: Vec<RetryCondition>,
+    pub budget: RetryBudget,
+}
+
+impl RetryPolicy {
+    pub fn from_args(args: &Args, run: &RunContext) -> Self {
+        Self {
+            max_retries: args.max_retries,
+            backoff: BackoffPolicy::from_args(args),
+            retry_on: if args.retry_on.is_empty() { DEFAULT_RETRY_ON.to_vec() } else { args.retry_on.clone() },
+            budget: RetryBudget::new(args.retry_budget, Arc::clone(&run.retries_spent)),
+        }
+    }
+
+    pub fn classify(&self, status: StatusCode) -> StatusClass {
+        classify_with(status, &self.retry_on)
+    }
+
+    /// Whether a failed request should be retried rather than reported straight away
+    pub fn retries_error(&self, error: &RuGetError) -> bool {
+        error_condition(error).is_some_and(|condition| self.retry_on.contains(&condition))
+    }
+
+    /// Start counting attempts for one request
+    pub fn start(&self) -> RetryState<'_> {
+        RetryState { policy: self, attempt: 0, previous: None }
+    }
+}
+
+/// Attempts made so far for one request
+#[derive(Debug)]
+pub struct RetryState<'a> {
+    policy: &'a RetryPolicy,
+    attempt: u32,
+    previous: Option<Duration>,
+}
+
+impl RetryState<'_> {
+    /// Account for a failed attempt, returning the delay before the next one
+    /// or why there will not be one. `headers` of a response supply `Retry-After`.
+    pub fn next(&mut self, headers: Option<&HeaderMap>) -> std::result::Result<Duration, RetryStop> {
+        if self.attempt >= self.policy.max_retries {
+            return Err(RetryStop::Exhausted(self.policy.max_retries));
+        }
+        if !self.policy.budget.try_spend() {
+            return Err(RetryStop::Budget(self.policy.budget.limit.unwrap_or_default()));
+        }
+
+        let delay = self.policy.backoff.delay_for_response(self.attempt, self.previous, headers);
+        self.attempt += 1;
+        self.previous = Some(delay);
+        Ok(delay)
+    }
+}
+
+/// Why a request is not retried again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryStop {
+    /// --max-retries attempts have been made
+    Exhausted(u32),
+    /// The run-wide --retry-budget is used up
+    Budget(u32),
+}
+
+impl fmt::Display for RetryStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryStop::Exhausted(retries) => write!(f, "after {} retries", retries),
+            RetryStop::Budget(limit) => write!(f, "after the retry budget of {} was used up", limit),
+        }
+    }
+}
+
+/// Error for a response that ended a download: E202 for client errors, E203 for server errors
+pub fn http_status_error(url: &str, status: StatusCode, detail: &str) -> RuGetError {
+    let code = if status.is_client_error() {
+        ErrorCode::E202
+    } else if status.is_server_error() {
+        ErrorCode::E203
+    } else {
+        ErrorCode::E200
+    };
+    RuGetError::new(code, ErrorKind::Http, format!("{}: HTTP {}{}", url, status, detail))
+}
+
+/// Parse a `Retry-After` header given either as seconds or as an HTTP-date
+pub fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    // A date in the past means the server is ready now
+    Some((date - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Parse a backoff factor of at least 1
+pub fn parse_factor(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor >= 1.0 => Ok(factor),
+        _ => Err(format!("'{}' is not a backoff factor of at least 1", value)),
+    }
+}
+
 /// Determine if an error is transient and should be retried
 pub fn is_transient(error: &reqwest::Error) -> bool {
     // Check for specific transient conditions
@@ -72,16 +407,22 @@ pub fn is_transient(error: &reqwest::Error) -> bool {
         return status.is_server_error();
     }
 
-    // Check for connection reset and similar network errors
-    if let Some(source) = error.source() {
-        let error_str = source.to_string().to_lowercase();
+    // Check for connection reset and similar network errors anywhere in the chain
+    let mut source = error.source();
+    while let Some(err) = source {
+        if err.downcast_ref::<std::io::Error>().and_then(io_condition).is_some() {
+            return true;
+        }
+        let error_str = err.to_string().to_lowercase();
         if error_str.contains("connection reset") 
+            || error_str.contains("connection closed")
             || error_str.contains("broken pipe")
             || error_str.contains("connection aborted")
             || error_str.contains("network unreachable")
             || error_str.contains("host unreachable") {
             return true;
         }
+        source = err.source();
     }
 
     false
@@ -165,6 +506,113 @@ mod tests {
         assert!(delay <= Duration::from_millis(125));
     }
 
+    #[test]
+    fn test_classify_status() {
+        let classify = |code: u16| classify_with(StatusCode::from_u16(code).unwrap(), DEFAULT_RETRY_ON);
+        assert_eq!(classify(206), StatusClass::Success);
+        for code in [408, 425, 429, 500, 502, 503, 504] {
+            assert_eq!(classify(code), StatusClass::Retry, "{}", code);
+        }
+        for code in [400, 401, 403, 404, 410, 416] {
+            assert_eq!(classify(code), StatusClass::Fatal, "{}", code);
+        }
+        assert_eq!(http_status_error("u", StatusCode::NOT_FOUND, "").code, ErrorCode::E202);
+        assert_eq!(http_status_error("u", StatusCode::BAD_GATEWAY, "").code, ErrorCode::E203);
+
+        let only_404 = [RetryCondition::Status(404)];
+        assert_eq!(classify_with(StatusCode::NOT_FOUND, &only_404), StatusClass::Retry);
+        assert_eq!(classify_with(StatusCode::SERVICE_UNAVAILABLE, &only_404), StatusClass::Fatal);
+    }
+
+    #[test]
+    fn test_parse_retry_condition() {
+        assert_eq!("429".parse(), Ok(RetryCondition::Status(429)));
+        assert_eq!("5XX".parse(), Ok(RetryCondition::StatusClass(5)));
+        assert_eq!(" timeout".parse(), Ok(RetryCondition::Timeout));
+        assert_eq!("connect".parse(), Ok(RetryCondition::Connect));
+        assert_eq!("reset".parse(), Ok(RetryCondition::Reset));
+        for bad in ["6xx", "99", "600", "sometimes", ""] {
+            assert!(bad.parse::<RetryCondition>().is_err(), "{}", bad);
+        }
+        assert_eq!(RetryCondition::StatusClass(4).to_string(), "4xx");
+    }
+
+    #[test]
+    fn test_full_jitter_stays_below_exponential() {
+        let policy = BackoffPolicy::with_params(Duration::from_millis(100), 3.0, Duration::from_secs(60), true)
+            .with_strategy(BackoffStrategy::FullJitter);
+        for _ in 0..50 {
+            assert!(policy.next_delay(2) <= Duration::from_millis(900));
+        }
+    }
+
+    #[test]
+    fn test_decorrelated_grows_from_previous_delay() {
+        let policy = BackoffPolicy::with_params(Duration::from_millis(100), 3.0, Duration::from_secs(2), true)
+            .with_strategy(BackoffStrategy::Decorrelated);
+        for _ in 0..50 {
+            let delay = policy.delay_after(1, Some(Duration::from_millis(400)));
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(1200), "{:?}", delay);
+        }
+        assert!(policy.delay_after(5, Some(Duration::from_secs(10))) <= Duration::from_secs(2));
+
+        let fixed = BackoffPolicy { jitter: false, ..policy };
+        assert_eq!(fixed.delay_after(1, Some(Duration::from_millis(400))), Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn test_retry_state_respects_max_retries_and_budget() {
+        let budget = RetryBudget::new(Some(3), Arc::new(AtomicU32::new(0)));
+        let policy = RetryPolicy {
+            max_retries: 2,
+            backoff: BackoffPolicy::with_params(Duration::from_millis(10), 2.0, Duration::from_secs(1), false),
+            retry_on: DEFAULT_RETRY_ON.to_vec(),
+            budget,
+        };
+
+        let mut first = policy.start();
+        assert_eq!(first.next(None), Ok(Duration::from_millis(10)));
+        assert_eq!(first.next(None), Ok(Duration::from_millis(20)));
+        assert_eq!(first.next(None), Err(RetryStop::Exhausted(2)));
+
+        // The budget is shared with every other request in the run
+        let mut second = policy.start();
+        assert!(second.next(None).is_ok());
+        assert_eq!(second.next(None), Err(RetryStop::Budget(3)));
+    }
+
+    #[test]
+    fn test_error_condition() {
+        let timeout = RuGetError::new(ErrorCode::E404, ErrorKind::Network, "stalled".into());
+        assert_eq!(error_condition(&timeout), Some(RetryCondition::Timeout));
+
+        let reset = RuGetError::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        assert_eq!(error_condition(&reset), Some(RetryCondition::Reset));
+
+        let denied = RuGetError::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert_eq!(error_condition(&denied), None);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT").unwrap().with_timezone(&Utc);
+        let header = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, value.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(retry_after(&header("120"), now), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&header("Wed, 21 Oct 2015 07:28:30 GMT"), now), Some(Duration::from_secs(30)));
+        assert_eq!(retry_after(&header("Wed, 21 Oct 2015 07:00:00 GMT"), now), Some(Duration::ZERO));
+        assert_eq!(retry_after(&header("soon"), now), None);
+        assert_eq!(retry_after(&HeaderMap::new(), now), None);
+
+        let policy = BackoffPolicy::with_params(Duration::from_millis(100), 2.0, Duration::from_secs(5), false);
+        assert_eq!(policy.delay_for_response(0, None, Some(&header("120"))), Duration::from_secs(5));
+        assert_eq!(policy.delay_for_response(1, None, Some(&HeaderMap::new())), Duration::from_millis(200));
+    }
+
     #[test] 
     fn test_is_transient_timeout() {
         // Mock timeout error - we'll use a simple error string check
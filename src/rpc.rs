@@ -0,0 +1,527 @@
+use crate::daemon::{DownloadQueue, JobOptions, JobSnapshot, JobStatus};
+use crate::error::{Result, RuGetError};
+use crate::output::Logger;
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+#[cfg(unix)]
+use crate::error::WithContext;
+#[cfg(all(unix, feature = "context"))]
+use crate::error::AnyhowContextExt;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// aria2 reports every application-level failure with code 1
+const ARIA2_ERROR: i64 = 1;
+
+/// Largest request body accepted over HTTP
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// Error returned from a JSON-RPC method
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: &str) -> Self {
+        Self { code: INVALID_PARAMS, message: message.to_string() }
+    }
+}
+
+impl From<RuGetError> for RpcError {
+    fn from(e: RuGetError) -> Self {
+        Self { code: ARIA2_ERROR, message: e.message }
+    }
+}
+
+/// Dispatches aria2-compatible JSON-RPC calls to the download queue
+pub struct RpcHandler {
+    queue: Arc<DownloadQueue>,
+    secret: Option<String>,
+}
+
+impl RpcHandler {
+    pub fn new(queue: Arc<DownloadQueue>, secret: Option<String>) -> Self {
+        Self { queue, secret }
+    }
+
+    /// Handle a raw JSON-RPC payload, single or batched
+    ///
+    /// Returns `None` when the payload contained only notifications.
+    pub fn handle(&self, payload: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(payload) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &format!("Parse error: {}", e))),
+        };
+
+        match request {
+            Value::Array(batch) if batch.is_empty() => {
+                Some(error_response(Value::Null, INVALID_REQUEST, "Empty batch"))
+            }
+            Value::Array(batch) => {
+                let responses: Vec<Value> = batch.iter().filter_map(|r| self.handle_request(r)).collect();
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            request => self.handle_request(&request),
+        }
+    }
+
+    fn handle_request(&self, request: &Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return Some(error_response(id.unwrap_or(Value::Null), INVALID_REQUEST, "Invalid Request"));
+        };
+
+        let params = match request.get("params") {
+            Some(Value::Array(params)) => params.clone(),
+            None => Vec::new(),
+            Some(_) => {
+                return Some(error_response(id.unwrap_or(Value::Null), INVALID_PARAMS, "params must be an array"));
+            }
+        };
+
+        let result = self.call(method, params);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e.code, &e.message),
+        })
+    }
+
+    fn call(&self, method: &str, mut params: Vec<Value>) -> std::result::Result<Value, RpcError> {
+        // aria2 passes the secret as a "token:" prefixed first parameter
+        let token = match params.first().and_then(Value::as_str) {
+            Some(token) if token.starts_with("token:") => {
+                let token = token["token:".len()..].to_string();
+                params.remove(0);
+                Some(token)
+            }
+            _ => None,
+        };
+        if let Some(secret) = &self.secret
+            && token.as_deref() != Some(secret.as_str())
+        {
+            return Err(RpcError { code: ARIA2_ERROR, message: "Unauthorized".into() });
+        }
+
+        match method {
+            "aria2.addUri" => self.add_uri(&params),
+            "aria2.tellStatus" => {
+                let gid = gid_param(&params, 0)?;
+                let keys = keys_param(&params, 1)?;
+                Ok(status_json(&self.queue.status(gid)?, &keys))
+            }
+            "aria2.tellActive" => {
+                let keys = keys_param(&params, 0)?;
+                Ok(status_list(self.queue.with_status(JobStatus::Active), &keys))
+            }
+            "aria2.tellWaiting" | "aria2.tellStopped" => {
+                let offset = index_param(&params, 0)?;
+                let num = index_param(&params, 1)?;
+                let keys = keys_param(&params, 2)?;
+                let jobs = if method == "aria2.tellWaiting" {
+                    let mut jobs = self.queue.with_status(JobStatus::Waiting);
+                    jobs.extend(self.queue.with_status(JobStatus::Paused));
+                    jobs
+                } else {
+                    self.queue.stopped()
+                };
+                Ok(status_list(jobs.into_iter().skip(offset).take(num).collect(), &keys))
+            }
+            "aria2.pause" | "aria2.forcePause" => {
+                let gid = gid_param(&params, 0)?;
+                self.queue.pause(gid)?;
+                Ok(json!(gid))
+            }
+            "aria2.unpause" => {
+                let gid = gid_param(&params, 0)?;
+                self.queue.unpause(gid)?;
+                Ok(json!(gid))
+            }
+            "aria2.remove" | "aria2.forceRemove" => {
+                let gid = gid_param(&params, 0)?;
+                self.queue.remove(gid)?;
+                Ok(json!(gid))
+            }
+            "aria2.getGlobalStat" => {
+                let stat = self.queue.global_stat();
+                Ok(json!({
+                    "downloadSpeed": stat.download_speed.to_string(),
+                    "uploadSpeed": "0",
+                    "numActive": stat.num_active.to_string(),
+                    "numWaiting": stat.num_waiting.to_string(),
+                    "numStopped": stat.num_stopped.to_string(),
+                    "numStoppedTotal": stat.num_stopped.to_string(),
+                }))
+            }
+            "aria2.getVersion" => Ok(json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "enabledFeatures": ["HTTP", "HTTPS"],
+            })),
+            _ => Err(RpcError { code: METHOD_NOT_FOUND, message: format!("Method not found: {}", method) }),
+        }
+    }
+
+    fn add_uri(&self, params: &[Value]) -> std::result::Result<Value, RpcError> {
+        let uris: Vec<String> = params.first()
+            .and_then(Value::as_array)
+            .map(|uris| uris.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .ok_or_else(|| RpcError::invalid_params("addUri expects an array of URIs"))?;
+
+        let mut options = JobOptions::default();
+        if let Some(opts) = params.get(1).and_then(Value::as_object) {
+            options.dir = opts.get("dir").and_then(Value::as_str).map(str::to_string);
+            options.out = opts.get("out").and_then(Value::as_str).map(str::to_string);
+            // aria2 accepts either a single header string or a list
+            options.headers = match opts.get("header") {
+                Some(Value::String(h)) => vec![h.clone()],
+                Some(Value::Array(hs)) => hs.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+                _ => Vec::new(),
+            };
+        }
+
+        let position = match params.get(2) {
+            Some(Value::Number(n)) => n.as_u64().map(|n| n as usize),
+            _ => None,
+        };
+
+        Ok(json!(self.queue.add(uris, options, position)?))
+    }
+}
+
+fn gid_param(params: &[Value], index: usize) -> std::result::Result<&str, RpcError> {
+    params.get(index)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::invalid_params("Expected a GID"))
+}
+
+fn keys_param(params: &[Value], index: usize) -> std::result::Result<Vec<String>, RpcError> {
+    match params.get(index) {
+        None => Ok(Vec::new()),
+        Some(Value::Array(keys)) => Ok(keys.iter().filter_map(Value::as_str).map(str::to_string).collect()),
+        Some(_) => Err(RpcError::invalid_params("keys must be an array")),
+    }
+}
+
+fn index_param(params: &[Value], index: usize) -> std::result::Result<usize, RpcError> {
+    params.get(index)
+        .and_then(Value::as_u64)
+        .map(|n| n as usize)
+        .ok_or_else(|| RpcError::invalid_params("Expected offset and num"))
+}
+
+fn status_list(jobs: Vec<JobSnapshot>, keys: &[String]) -> Value {
+    Value::Array(jobs.iter().map(|job| status_json(job, keys)).collect())
+}
+
+/// Render a job the way aria2's tellStatus does: numbers as strings, one file entry
+fn status_json(snapshot: &JobSnapshot, keys: &[String]) -> Value {
+    let job = &snapshot.job;
+    let dir = job.options.dir.clone().unwrap_or_default();
+    let path = job.path.clone().unwrap_or_default();
+
+    let mut status = Map::new();
+    status.insert("gid".into(), json!(job.gid));
+    status.insert("status".into(), json!(job.status.as_str()));
+    status.insert("totalLength".into(), json!(job.total_length.to_string()));
+    status.insert("completedLength".into(), json!(job.completed_length.to_string()));
+    status.insert("uploadLength".into(), json!("0"));
+    status.insert("downloadSpeed".into(), json!(snapshot.download_speed.to_string()));
+    status.insert("uploadSpeed".into(), json!("0"));
+    status.insert("connections".into(), json!(if job.status == JobStatus::Active { "1" } else { "0" }));
+    status.insert("dir".into(), json!(dir));
+    status.insert("files".into(), json!([{
+        "index": "1",
+        "path": path,
+        "length": job.total_length.to_string(),
+        "completedLength": job.completed_length.to_string(),
+        "selected": "true",
+        "uris": job.uris.iter().map(|uri| json!({ "uri": uri, "status": "used" })).collect::<Vec<_>>(),
+    }]));
+    if let Some(code) = &job.error_code {
+        status.insert("errorCode".into(), json!(code));
+    }
+    if let Some(message) = &job.error_message {
+        status.insert("errorMessage".into(), json!(message));
+    }
+
+    if !keys.is_empty() {
+        status.retain(|key, _| keys.iter().any(|k| k == key));
+    }
+    Value::Object(status)
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Serve JSON-RPC over HTTP POST at /jsonrpc until the listener fails
+pub fn serve_http(listener: TcpListener, handler: Arc<RpcHandler>, logger: &Logger) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || {
+                    // Connection errors only affect that client
+                    let _ = handle_http_connection(stream, &handler);
+                });
+            }
+            Err(e) => logger.warn(&format!("JSON-RPC accept failed: {}", e)),
+        }
+    }
+}
+
+fn handle_http_connection(stream: TcpStream, handler: &RpcHandler) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    let mut content_length = 0usize;
+    let mut headers = RequestHeaders::default();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim().to_string();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.parse().unwrap_or(0),
+                "content-type" => headers.content_type = Some(value),
+                "origin" => headers.origin = Some(value),
+                "host" => headers.host = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    if path.split('?').next() != Some("/jsonrpc") {
+        return write_http_response(&mut stream, "404 Not Found", "");
+    }
+
+    match method {
+        "POST" if content_length > MAX_BODY_SIZE => {
+            write_http_response(&mut stream, "413 Payload Too Large", "")
+        }
+        "POST" => {
+            if let Err(status) = headers.check() {
+                return write_http_response(&mut stream, status, "");
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body)?;
+            let response = handler.handle(&String::from_utf8_lossy(&body))
+                .map(|r| r.to_string())
+                .unwrap_or_default();
+            write_http_response(&mut stream, "200 OK", &response)
+        }
+        _ => write_http_response(&mut stream, "405 Method Not Allowed", ""),
+    }
+}
+
+/// Request headers that decide whether a browser could have sent the request
+#[derive(Debug, Default)]
+struct RequestHeaders {
+    content_type: Option<String>,
+    origin: Option<String>,
+    host: Option<String>,
+}
+
+impl RequestHeaders {
+    /// Refuse requests a web page could make on the user's behalf
+    ///
+    /// Browsers send form posts without a JSON content type, and a page
+    /// reached through DNS rebinding still carries its own `Host`, so both
+    /// are checked along with any `Origin`. Returns the HTTP status to send.
+    fn check(&self) -> std::result::Result<(), &'static str> {
+        let media_type = self.content_type.as_deref()
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase());
+        if !matches!(media_type.as_deref(), Some("application/json" | "application/json-rpc")) {
+            return Err("415 Unsupported Media Type");
+        }
+
+        let host_allowed = self.host.as_deref().is_some_and(|host| {
+            url::Url::parse(&format!("http://{}", host)).is_ok_and(|url| is_loopback_name(&url))
+        });
+        let origin_allowed = self.origin.as_deref().is_none_or(|origin| {
+            url::Url::parse(origin).is_ok_and(|url| is_loopback_name(&url))
+        });
+        if !host_allowed || !origin_allowed {
+            return Err("403 Forbidden");
+        }
+        Ok(())
+    }
+}
+
+fn is_loopback_name(url: &url::Url) -> bool {
+    matches!(url.host_str(), Some("localhost" | "127.0.0.1"))
+}
+
+fn write_http_response(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json-rpc\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Bind the Unix socket, replacing a stale socket file from a previous run
+#[cfg(unix)]
+pub fn bind_unix_socket(path: &std::path::Path) -> Result<std::os::unix::net::UnixListener> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating socket directory {}", parent.display()))?;
+    }
+    if path.exists() {
+        std::fs::remove_file(path)
+            .with_context(|| format!("removing stale socket {}", path.display()))?;
+    }
+    std::os::unix::net::UnixListener::bind(path)
+        .with_context(|| format!("binding JSON-RPC socket {}", path.display()))
+}
+
+/// Serve newline-delimited JSON-RPC over a Unix socket
+#[cfg(unix)]
+pub fn serve_unix(listener: std::os::unix::net::UnixListener, handler: Arc<RpcHandler>, logger: &Logger) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || {
+                    let Ok(reader) = stream.try_clone() else {
+                        return;
+                    };
+                    let mut writer = stream;
+                    for line in BufReader::new(reader).lines() {
+                        let Ok(line) = line else {
+                            break;
+                        };
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        if let Some(response) = handler.handle(&line)
+                            && writeln!(writer, "{}", response).is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+            Err(e) => logger.warn(&format!("JSON-RPC accept failed: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn handler(secret: Option<&str>) -> (RpcHandler, tempfile::TempDir) {
+        let dir = tempdir().unwrap();
+        let queue = DownloadQueue::load(dir.path().join("queue.json")).unwrap();
+        (RpcHandler::new(Arc::new(queue), secret.map(str::to_string)), dir)
+    }
+
+    fn call(handler: &RpcHandler, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": "1", "method": method, "params": params });
+        handler.handle(&request.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_add_uri_and_tell_status() {
+        let (handler, _dir) = handler(None);
+        let response = call(&handler, "aria2.addUri", json!([["https://example.com/a.bin"], { "out": "a.bin" }]));
+        let gid = response["result"].as_str().unwrap().to_string();
+        assert_eq!(gid.len(), 16);
+
+        let status = call(&handler, "aria2.tellStatus", json!([gid, ["gid", "status", "totalLength"]]));
+        assert_eq!(status["result"], json!({ "gid": gid, "status": "waiting", "totalLength": "0" }));
+    }
+
+    #[test]
+    fn test_secret_token_required() {
+        let (handler, _dir) = handler(Some("s3cret"));
+        let denied = call(&handler, "aria2.getGlobalStat", json!([]));
+        assert_eq!(denied["error"]["code"], json!(ARIA2_ERROR));
+
+        let allowed = call(&handler, "aria2.getGlobalStat", json!(["token:s3cret"]));
+        assert_eq!(allowed["result"]["numActive"], json!("0"));
+    }
+
+    fn headers(content_type: &str, origin: Option<&str>, host: &str) -> RequestHeaders {
+        RequestHeaders {
+            content_type: Some(content_type.to_string()),
+            origin: origin.map(str::to_string),
+            host: Some(host.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_http_accepts_local_json_requests() {
+        assert!(headers("application/json", None, "localhost:6800").check().is_ok());
+        assert!(headers("application/json-rpc; charset=utf-8", None, "127.0.0.1:6800").check().is_ok());
+        assert!(headers("application/json", Some("http://localhost:6801"), "127.0.0.1").check().is_ok());
+    }
+
+    #[test]
+    fn test_http_rejects_non_json_content_type() {
+        let form = headers("application/x-www-form-urlencoded", None, "localhost:6800");
+        assert_eq!(form.check(), Err("415 Unsupported Media Type"));
+        let missing = RequestHeaders { content_type: None, ..headers("", None, "localhost:6800") };
+        assert_eq!(missing.check(), Err("415 Unsupported Media Type"));
+    }
+
+    #[test]
+    fn test_http_rejects_foreign_origin() {
+        let cross_site = headers("application/json", Some("https://evil.example"), "localhost:6800");
+        assert_eq!(cross_site.check(), Err("403 Forbidden"));
+        let opaque = headers("application/json", Some("null"), "localhost:6800");
+        assert_eq!(opaque.check(), Err("403 Forbidden"));
+    }
+
+    #[test]
+    fn test_http_rejects_foreign_host() {
+        // A DNS-rebound page still sends its own host name
+        let rebound = headers("application/json", None, "evil.example:6800");
+        assert_eq!(rebound.check(), Err("403 Forbidden"));
+        let missing = RequestHeaders { host: None, ..headers("application/json", None, "") };
+        assert_eq!(missing.check(), Err("403 Forbidden"));
+    }
+
+    #[test]
+    fn test_errors_and_batches() {
+        let (handler, _dir) = handler(None);
+        assert_eq!(handler.handle("{not json").unwrap()["error"]["code"], json!(PARSE_ERROR));
+        assert_eq!(call(&handler, "aria2.shutdown", json!([]))["error"]["code"], json!(METHOD_NOT_FOUND));
+        assert_eq!(call(&handler, "aria2.pause", json!(["0000000000000000"]))["error"]["code"], json!(ARIA2_ERROR));
+
+        let batch = json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "aria2.getVersion" },
+            { "jsonrpc": "2.0", "method": "aria2.getGlobalStat" },
+        ]);
+        let responses = handler.handle(&batch.to_string()).unwrap();
+        assert_eq!(responses.as_array().unwrap().len(), 1);
+    }
+}
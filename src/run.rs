@@ -0,0 +1,54 @@
+use crate::probe::ProbeCache;
+use crate::scheduler::ConnectionLimits;
+use std::sync::atomic::{AtomicBool, AtomicU32};
+use std::sync::Arc;
+
+/// Mutable state of one run, created by the entry point and passed beside `&Args`
+///
+/// Keeping it off [`Args`](crate::cli::Args) means per-job copies of the
+/// options never share it by accident.
+#[derive(Debug, Default)]
+pub struct RunContext {
+    /// Set to abort in-flight downloads (used by the daemon to pause and remove jobs)
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Retries spent so far, so --retry-budget applies to every job
+    pub retries_spent: Arc<AtomicU32>,
+    /// Connection limits for --max-connections and --jobs auto
+    pub connections: Arc<ConnectionLimits>,
+    /// HEAD results for this run's URLs
+    pub probes: ProbeCache,
+}
+
+impl RunContext {
+    /// Context for one daemon job: its own cancel flag and probes, with the
+    /// retry budget and connection limits of the daemon
+    pub fn job(&self, cancel: Arc<AtomicBool>) -> Self {
+        Self {
+            cancel: Some(cancel),
+            retries_spent: Arc::clone(&self.retries_spent),
+            connections: Arc::clone(&self.connections),
+            probes: ProbeCache::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::RemoteInfo;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_job_shares_budget_but_not_probes() {
+        let daemon = RunContext::default();
+        daemon.probes.insert("http://example.com/a", &[], RemoteInfo::default());
+
+        let job = daemon.job(Arc::new(AtomicBool::new(false)));
+        job.retries_spent.fetch_add(1, Ordering::Relaxed);
+
+        assert_eq!(daemon.retries_spent.load(Ordering::Relaxed), 1);
+        assert!(Arc::ptr_eq(&daemon.connections, &job.connections));
+        assert!(job.probes.get("http://example.com/a", &[]).is_none());
+        assert!(daemon.cancel.is_none() && job.cancel.is_some());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::adaptive::{self, AdaptiveConcurrency, Slot};
+use crate::cli::Args;
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError};
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+/// Worker pool for whole files, sized by --jobs (0 keeps rayon's default of one per CPU)
+///
+/// Each run gets its own pool rather than the global one, and chunks of a split
+/// file never run on it, so file workers cannot end up waiting on chunk work
+/// that has no thread left to run on.
+pub fn file_pool(args: &Args) -> Result<rayon::ThreadPool> {
+    // Under --jobs auto the per-host limits decide how many of the workers are transferring
+    let threads = if adaptive::is_auto(args) { adaptive::AUTO_THREADS } else { args.jobs };
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| RuGetError::new(
+            ErrorCode::E505,
+            ErrorKind::Io,
+            format!("Failed to start {} download workers: {}", threads, e),
+        ))
+}
+
+/// Connections per file when --split is not given
+pub const DEFAULT_SPLIT: usize = 1;
+
+/// Connections per file: --split, independent of how many files --jobs runs at once
+///
+/// Only --jobs auto, which tunes connections per host, splits files by default.
+pub fn split(args: &Args) -> usize {
+    match args.split {
+        Some(split) => split.max(1),
+        None if adaptive::is_auto(args) => adaptive::MAX_CONNECTIONS,
+        None => DEFAULT_SPLIT,
+    }
+}
+
+/// Run `download` for every chunk of one file, each on its own thread
+///
+/// Results come back in chunk order.
+pub fn run_chunks<C, T, F>(chunks: Vec<C>, download: F) -> Vec<Result<T>>
+where
+    C: Send,
+    T: Send,
+    F: Fn(C) -> Result<T> + Sync,
+{
+    thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(|| download(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|_| Err(RuGetError::network(
+                "Chunk download thread panicked".to_string(),
+            ))))
+            .collect()
+    })
+}
+
+/// Connection limits shared by every worker of a run: the --max-connections
+/// cap over all hosts and, under --jobs auto, the per-host limits
+#[derive(Debug, Default)]
+pub struct ConnectionLimits {
+    open: Mutex<usize>,
+    closed: Condvar,
+    hosts: AdaptiveConcurrency,
+}
+
+impl ConnectionLimits {
+    /// Wait until a connection to `url` may be opened
+    ///
+    /// The host's slot is taken first, so a connection waiting on a throttled
+    /// host does not hold one of the global connections meanwhile.
+    pub fn acquire(&self, args: &Args, url: &str) -> Connection<'_> {
+        let slot = self.hosts.acquire(args, url);
+        if args.max_connections == 0 {
+            return Connection { slot, limits: None };
+        }
+
+        let mut open = self.open.lock().unwrap();
+        while *open >= args.max_connections {
+            open = self.closed.wait(open).unwrap();
+        }
+        *open += 1;
+        Connection { slot, limits: Some(self) }
+    }
+}
+
+/// An open connection's share of the limits, released when dropped
+#[derive(Debug)]
+pub struct Connection<'a> {
+    slot: Slot,
+    limits: Option<&'a ConnectionLimits>,
+}
+
+impl Deref for Connection<'_> {
+    type Target = Slot;
+
+    fn deref(&self) -> &Slot {
+        &self.slot
+    }
+}
+
+impl Drop for Connection<'_> {
+    fn drop(&mut self) {
+        if let Some(limits) = self.limits {
+            *limits.open.lock().unwrap() -= 1;
+            limits.closed.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_split_is_independent_of_jobs() {
+        let split_of = |argv: &[&str]| split(&Args::parse_from([&["ruget", "http://example.com/a"], argv].concat()));
+        assert_eq!(split_of(&[]), DEFAULT_SPLIT);
+        assert_eq!(split_of(&["--jobs", "4"]), DEFAULT_SPLIT);
+        assert_eq!(split_of(&["--jobs", "200"]), DEFAULT_SPLIT);
+        assert_eq!(split_of(&["--jobs", "4", "--split", "2"]), 2);
+        assert_eq!(split_of(&["--jobs", "1", "--split", "8"]), 8);
+        assert_eq!(split_of(&["--jobs", "auto"]), adaptive::MAX_CONNECTIONS);
+    }
+
+    #[test]
+    fn test_max_connections_caps_concurrent_chunks() {
+        let args = Args::parse_from(["ruget", "--max-connections", "2", "http://example.com/a"]);
+        let limits = ConnectionLimits::default();
+        let (open, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+
+        let results = run_chunks((0..6).collect(), |_chunk: usize| {
+            let _connection = limits.acquire(&args, "http://example.com/a");
+            let now = open.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            open.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        });
+
+        assert_eq!(results.len(), 6);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::error::{Result, RuGetError, WithContext};
+use crate::multithreaded_download::{check_range_reply, restart_error, truncated_body_error, RangeReply};
+use crate::output::Logger;
+use crate::probe::unsatisfied_range_total;
+use crate::resume::ResumeState;
+use crate::retry::{http_status_error, RetryPolicy, RetryState, StatusClass};
+use reqwest::header::{HeaderMap, IF_RANGE, RANGE};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[cfg(feature = "context")]
+use crate::error::AnyhowContextExt;
+
+/// What a response to a single-stream GET means for the transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    /// The partial file already holds the whole remote file
+    Complete,
+    /// The range was rejected; empty the file and ask again from the start
+    Restart,
+    /// Wait this long, then send the request again
+    Retry(Duration),
+    /// Read the body into the file
+    Body {
+        /// The body starts a new copy of the file, with new validators
+        fresh: bool,
+        /// The server sent the whole file, so what is on disk must go first
+        truncate: bool,
+    },
+}
+
+/// The decisions of a single-stream download, shared by the threaded and async engines
+///
+/// The engines do the I/O: they send each request with `request_headers`, pass
+/// the response to `on_response`, count body bytes with `advance` and pass
+/// failures to `on_error`. Everything about ranges, restarts and retries is
+/// decided here, so both engines behave the same.
+pub struct SingleStream<'a> {
+    url: &'a str,
+    policy: &'a RetryPolicy,
+    retry: RetryState<'a>,
+    /// Bytes of the file already in place, where the next request continues from
+    offset: u64,
+    /// Where the request in flight asked the body to start
+    requested: Option<u64>,
+    /// Bytes of the current body received so far
+    received: u64,
+    validators: Option<ResumeState>,
+    /// Bytes already passed on somewhere a restart cannot take them back from
+    irreversible: Option<&'static str>,
+}
+
+impl<'a> SingleStream<'a> {
+    pub fn new(url: &'a str, policy: &'a RetryPolicy) -> Self {
+        Self {
+            url,
+            policy,
+            retry: policy.start(),
+            offset: 0,
+            requested: None,
+            received: 0,
+            validators: None,
+            irreversible: None,
+        }
+    }
+
+    /// Continue a partial file of `from` bytes, checked against the validators it was saved with
+    pub fn resume(&mut self, from: u64, validators: Option<ResumeState>, output_path: &str, logger: &Logger) {
+        let reason = match validators.as_ref().and_then(ResumeState::if_range) {
+            Some(_) => "If-Range restarts the download if the remote file changed",
+            None => "no validators were saved, trusting the local length",
+        };
+        logger.download_resume(output_path, from, "resume", reason);
+        self.offset = from;
+        self.validators = validators;
+    }
+
+    /// Refuse to restart once bytes have gone somewhere they cannot be taken back from
+    pub fn irreversible(&mut self, doing: &'static str) {
+        self.irreversible = Some(doing);
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Headers for the next request: `base`, plus a range after the bytes already in place
+    pub fn request_headers(&mut self, base: &HeaderMap) -> Result<HeaderMap> {
+        let mut headers = base.clone();
+        self.requested = (self.offset > 0).then_some(self.offset);
+        if let Some(from) = self.requested {
+            headers.insert(RANGE, format!("bytes={}-", from).parse()
+                .with_context(|| format!("creating range header to continue at byte {}", from))?);
+            if let Some(validator) = self.validators.as_ref().and_then(ResumeState::if_range) {
+                headers.insert(IF_RANGE, validator.parse()
+                    .with_context(|| format!("creating If-Range header for {}", self.url))?);
+            }
+        }
+        Ok(headers)
+    }
+
+    /// Decide what a response means before its body is read
+    ///
+    /// `remote_total` is the probed length of the remote file, if the engine has one.
+    pub fn on_response(
+        &mut self,
+        status: StatusCode,
+        headers: &HeaderMap,
+        output_path: &str,
+        remote_total: impl FnOnce() -> Option<u64>,
+        logger: &Logger,
+    ) -> Result<Reply> {
+        let url = self.url;
+        if status == StatusCode::RANGE_NOT_SATISFIABLE && let Some(from) = self.requested {
+            let total = unsatisfied_range_total(headers).or_else(remote_total);
+            if total == Some(from) {
+                logger.download_resume(output_path, from, "complete", "range starts at the end of the remote file");
+                return Ok(Reply::Complete);
+            }
+            if let Some(doing) = self.irreversible {
+                return Err(restart_error(url, doing));
+            }
+            logger.download_resume(output_path, from, "restart", "range was rejected and the local size does not match");
+            self.offset = 0;
+            return Ok(Reply::Restart);
+        }
+        match self.policy.classify(status) {
+            StatusClass::Success => {}
+            StatusClass::Fatal => return Err(http_status_error(url, status, "")),
+            StatusClass::Retry => match self.retry.next(Some(headers)) {
+                Ok(delay) => {
+                    logger.retry_attempt(url, &format!("HTTP {}", status), delay);
+                    return Ok(Reply::Retry(delay));
+                }
+                Err(stop) => return Err(http_status_error(url, status, &format!(" {}", stop))),
+            },
+        }
+
+        let truncate = match self.requested {
+            Some(from) => {
+                // An empty file has no last byte, so a zero length says nothing about the range
+                let total = remote_total().filter(|&total| total > 0);
+                let last = total.and_then(|total| total.checked_sub(1));
+                let full = check_range_reply(url, status, headers, from, last, total)? == RangeReply::Full;
+                if full {
+                    if let Some(doing) = self.irreversible {
+                        return Err(restart_error(url, doing));
+                    }
+                    // Appending the whole body to the partial file would corrupt it
+                    if self.validators.as_ref().and_then(ResumeState::if_range).is_some() {
+                        logger.download_resume(output_path, from, "restart", "If-Range did not match, the remote file changed");
+                    } else {
+                        logger.warn(&format!("{} ignored the resume range, downloading {} from scratch", url, output_path));
+                    }
+                    self.offset = 0;
+                }
+                full
+            }
+            None => false,
+        };
+        let fresh = truncate || self.requested.is_none();
+        if fresh {
+            self.validators = Some(ResumeState::from_headers(url, headers));
+        }
+        self.received = 0;
+
+        logger.status(url, &status.to_string());
+        logger.headers(headers);
+        Ok(Reply::Body { fresh, truncate })
+    }
+
+    /// Keep the validators of a fresh body beside the output, or forget stale ones
+    pub fn save_validators(&self, output_path: &str, keep: bool) -> Result<()> {
+        match &self.validators {
+            Some(state) if keep && state.has_validators() => state.save(output_path),
+            _ => {
+                ResumeState::remove(output_path);
+                Ok(())
+            }
+        }
+    }
+
+    /// Count body bytes that are now in place
+    pub fn advance(&mut self, n: usize) {
+        self.received += n as u64;
+        self.offset += n as u64;
+    }
+
+    /// The error for a body that ended before the length the server announced
+    pub fn end_of_body(&self, expected: Option<u64>) -> Option<RuGetError> {
+        expected
+            .filter(|&expected| self.received < expected)
+            .map(|expected| truncated_body_error(self.url, self.received, expected))
+    }
+
+    /// Decide whether a failed attempt is tried again, returning the delay before it
+    pub fn on_error(&mut self, error: RuGetError, logger: &Logger) -> Result<Duration> {
+        if !self.policy.retries_error(&error) {
+            return Err(error);
+        }
+        let delay = match self.retry.next(None) {
+            Ok(delay) => delay,
+            Err(stop) => return Err(error.with_context(&format!("downloading {} {}", self.url, stop))),
+        };
+        logger.retry_attempt(self.url, &error.to_string(), delay);
+        if self.offset > 0 {
+            logger.verbose(&format!("Continuing {} from byte {}", self.url, self.offset));
+        }
+        Ok(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use crate::cli::Args;
+    use crate::run::RunContext;
+    use reqwest::header::{HeaderValue, CONTENT_RANGE, ETAG};
+
+    fn policy(argv: &[&str]) -> RetryPolicy {
+        RetryPolicy::from_args(&Args::parse_from([&["ruget", "http://example.com/a"], argv].concat()), &RunContext::default())
+    }
+
+    #[test]
+    fn test_interrupted_body_continues_with_if_range() {
+        let policy = policy(&["--backoff-base-ms", "1"]);
+        let logger = Logger::new(true, false);
+        let mut stream = SingleStream::new("http://example.com/a", &policy);
+
+        assert!(!stream.request_headers(&HeaderMap::new()).unwrap().contains_key(RANGE));
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        let reply = stream.on_response(StatusCode::OK, &headers, "a", || None, &logger).unwrap();
+        assert_eq!(reply, Reply::Body { fresh: true, truncate: false });
+
+        stream.advance(4);
+        let error = stream.end_of_body(Some(10)).unwrap();
+        assert!(stream.on_error(error, &logger).is_ok());
+
+        let retry = stream.request_headers(&HeaderMap::new()).unwrap();
+        assert_eq!(retry[RANGE], "bytes=4-");
+        assert_eq!(retry[IF_RANGE], "\"v1\"");
+    }
+
+    #[test]
+    fn test_rejected_and_ignored_ranges() {
+        let policy = policy(&[]);
+        let logger = Logger::new(true, false);
+        let mut stream = SingleStream::new("http://example.com/a", &policy);
+        stream.resume(4, None, "a", &logger);
+        stream.request_headers(&HeaderMap::new()).unwrap();
+
+        // A range starting at the end of the file means it is already complete
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_RANGE, HeaderValue::from_static("bytes */4"));
+        let reply = stream.on_response(StatusCode::RANGE_NOT_SATISFIABLE, &headers, "a", || None, &logger).unwrap();
+        assert_eq!(reply, Reply::Complete);
+
+        // A whole body for a ranged request replaces the partial file
+        let reply = stream.on_response(StatusCode::OK, &HeaderMap::new(), "a", || Some(10), &logger).unwrap();
+        assert_eq!(reply, Reply::Body { fresh: true, truncate: true });
+        assert_eq!(stream.offset(), 0);
+
+        // ...unless the bytes already went somewhere they cannot be taken back from
+        let mut stream = SingleStream::new("http://example.com/a", &policy);
+        stream.resume(4, None, "a", &logger);
+        stream.irreversible("extracted");
+        stream.request_headers(&HeaderMap::new()).unwrap();
+        assert!(stream.on_response(StatusCode::OK, &HeaderMap::new(), "a", || None, &logger).is_err());
+    }
+}
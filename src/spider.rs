@@ -0,0 +1,380 @@
+use crate::cli::{Args, ReportFormat};
+use crate::scheduler::file_pool;
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError, WithContext};
+use crate::file_utils::write_report;
+use crate::http::{add_netrc_auth, build_headers};
+use crate::manifest::JobStream;
+use crate::output::Logger;
+use crate::retry::{RetryPolicy, StatusClass};
+use crate::run::RunContext;
+use crate::transport::{build_transport, Response, Transport};
+use rayon::prelude::*;
+use regex::Regex;
+use reqwest::cookie::Jar;
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Read;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::Instant;
+use url::Url;
+
+#[cfg(feature = "context")]
+use crate::error::AnyhowContextExt;
+
+/// Largest HTML page read when looking for links with --recursive
+const MAX_PAGE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Result of checking one URL
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkReport {
+    pub url: String,
+    /// HTTP status of the final response, if one was received
+    pub status: Option<u16>,
+    /// URL after following redirects
+    pub final_url: Option<String>,
+    pub size: Option<u64>,
+    pub content_type: Option<String>,
+    /// Request method that produced the result (`HEAD` or `GET`)
+    pub method: &'static str,
+    pub alive: bool,
+    pub error: Option<String>,
+    /// Page the link was found on, when crawling
+    pub referrer: Option<String>,
+    pub elapsed_ms: u128,
+}
+
+/// Check every job's URL without downloading bodies and report dead links
+///
+/// Returns an error, and so a non-zero exit, if any URL is dead.
+pub fn spider(args: Args, jobs: JobStream, logger: &Logger) -> Result<()> {
+    let transport = build_transport(&args, Arc::new(Jar::default()), logger)?;
+    let client = transport.as_ref();
+    let pool = file_pool(&args)?;
+    let run = RunContext::default();
+
+    let seeds: Vec<(String, Option<String>)> = jobs
+        .filter_map(|job| match job {
+            Ok(spec) => Some((spec.url, None)),
+            Err(e) => {
+                logger.error_from_ruget_error(&e);
+                None
+            }
+        })
+        .collect();
+
+    let mut visited: HashSet<String> = seeds.iter().map(|(url, _)| url.clone()).collect();
+    let hosts: HashSet<String> = seeds
+        .iter()
+        .filter_map(|(url, _)| Url::parse(url).ok()?.host_str().map(str::to_string))
+        .collect();
+
+    let mut reports = Vec::new();
+    let mut frontier = seeds;
+    let mut depth = 0;
+
+    // Breadth-first so each level is checked in parallel
+    while !frontier.is_empty() {
+        let crawl = args.recursive && depth < args.level;
+        let level: Vec<(LinkReport, Vec<String>)> = pool.install(|| frontier
+            .into_par_iter()
+            .map(|(url, referrer)| {
+                let mut report = check_link(client, &url, &args, &run, logger);
+                report.referrer = referrer;
+                let links = if crawl && should_crawl(&report, &hosts) {
+                    page_links(client, &report, &args, logger)
+                } else {
+                    Vec::new()
+                };
+                (report, links)
+            })
+            .collect());
+
+        frontier = Vec::new();
+        for (report, links) in level {
+            for link in links {
+                if visited.insert(link.clone()) {
+                    frontier.push((link, Some(report.url.clone())));
+                }
+            }
+            reports.push(report);
+        }
+        depth += 1;
+    }
+
+    let rendered = match args.report_format {
+        ReportFormat::Json => render_json(&reports)?,
+        ReportFormat::Text => render_text(&reports),
+    };
+    write_report(&rendered, &args)?;
+
+    let broken = reports.iter().filter(|r| !r.alive).count();
+    logger.summary(reports.len() - broken, reports.len());
+    if broken > 0 {
+        return Err(RuGetError::new(
+            ErrorCode::E200,
+            ErrorKind::Http,
+            format!("{} of {} links are broken", broken, reports.len()),
+        ));
+    }
+    Ok(())
+}
+
+/// Check one URL with HEAD, retrying transient failures
+///
+/// Servers that refuse or mishandle HEAD are asked for the first byte with a
+/// ranged GET instead; only 404 and 410 are trusted from HEAD alone.
+pub fn check_link(client: &dyn Transport, url: &str, args: &Args, run: &RunContext, logger: &Logger) -> LinkReport {
+    let start = Instant::now();
+    let head = request_with_retry(client, Method::HEAD, url, args, run, logger);
+
+    let head_conclusive = matches!(
+        &head,
+        Ok(resp) if resp.status().is_success()
+            || resp.status() == StatusCode::NOT_FOUND
+            || resp.status() == StatusCode::GONE
+    );
+    let (method, result) = if head_conclusive {
+        ("HEAD", head)
+    } else {
+        logger.verbose(&format!("HEAD not usable for {}, retrying with a ranged GET", url));
+        ("GET", request_with_retry(client, Method::GET, url, args, run, logger))
+    };
+
+    let elapsed_ms = start.elapsed().as_millis();
+    match result {
+        Ok(resp) => {
+            let status = resp.status();
+            LinkReport {
+                url: url.to_string(),
+                status: Some(status.as_u16()),
+                final_url: Some(resp.url().to_string()),
+                size: response_size(&resp),
+                content_type: resp.headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string),
+                method,
+                alive: status.is_success(),
+                error: (!status.is_success()).then(|| format!("HTTP {}", status)),
+                referrer: None,
+                elapsed_ms,
+            }
+        }
+        Err(e) => LinkReport {
+            url: url.to_string(),
+            status: None,
+            final_url: None,
+            size: None,
+            content_type: None,
+            method,
+            alive: false,
+            error: Some(e.message),
+            referrer: None,
+            elapsed_ms,
+        },
+    }
+}
+
+/// Send a body-less probe, retrying rate limits, server errors and transient network failures
+fn request_with_retry(
+    client: &dyn Transport,
+    method: Method,
+    url: &str,
+    args: &Args,
+    run: &RunContext,
+    logger: &Logger,
+) -> Result<Response> {
+    let mut headers = build_headers(&args.headers, logger);
+    add_netrc_auth(&mut headers, url).with_context(|| format!("adding netrc auth for {}", url))?;
+    if method == Method::GET {
+        headers.insert(RANGE, "bytes=0-0".parse().unwrap());
+    }
+
+    let retry_policy = RetryPolicy::from_args(args, run);
+    let mut retry = retry_policy.start();
+
+    loop {
+        let (retry_reason, delay) = match client.send(method.clone(), url, headers.clone()) {
+            Ok(resp) if retry_policy.classify(resp.status()) == StatusClass::Retry => match retry.next(Some(resp.headers())) {
+                Ok(delay) => (format!("HTTP {}", resp.status()), delay),
+                Err(_) => return Ok(resp),
+            },
+            Ok(resp) => return Ok(resp),
+            Err(e) => {
+                let error = e.with_context(&format!("checking {}", url));
+                if !retry_policy.retries_error(&error) {
+                    return Err(error);
+                }
+                match retry.next(None) {
+                    Ok(delay) => (error.to_string(), delay),
+                    Err(stop) => return Err(error.with_context(&stop.to_string())),
+                }
+            }
+        };
+
+        logger.retry_attempt(url, &retry_reason, delay);
+        thread::sleep(delay);
+    }
+}
+
+/// Full size of the resource, read from Content-Range for partial responses
+fn response_size(resp: &Response) -> Option<u64> {
+    if resp.status() == StatusCode::PARTIAL_CONTENT {
+        return resp.headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit_once('/'))
+            .and_then(|(_, total)| total.trim().parse().ok());
+    }
+    resp.content_length().filter(|_| resp.status().is_success())
+}
+
+/// Only live HTML pages on one of the starting hosts are crawled
+fn should_crawl(report: &LinkReport, hosts: &HashSet<String>) -> bool {
+    let is_html = report.content_type
+        .as_deref()
+        .is_some_and(|ct| ct.starts_with("text/html") || ct.starts_with("application/xhtml"));
+    let on_site = report.final_url
+        .as_deref()
+        .and_then(|u| Url::parse(u).ok())
+        .and_then(|u| u.host_str().map(|h| hosts.contains(h)))
+        .unwrap_or(false);
+    report.alive && is_html && on_site
+}
+
+/// Fetch an HTML page and return the absolute http(s) links it contains
+fn page_links(client: &dyn Transport, report: &LinkReport, args: &Args, logger: &Logger) -> Vec<String> {
+    let Some(page_url) = report.final_url.as_deref().and_then(|u| Url::parse(u).ok()) else {
+        return Vec::new();
+    };
+
+    let mut body = String::new();
+    let fetched = client.get(page_url.as_str(), build_headers(&args.headers, logger))
+        .ok()
+        .filter(|resp| resp.status().is_success())
+        .map(|resp| resp.take(MAX_PAGE_SIZE).read_to_string(&mut body));
+    if !matches!(fetched, Some(Ok(_))) {
+        logger.warn(&format!("Could not read {} to follow its links", page_url));
+        return Vec::new();
+    }
+
+    extract_links(&page_url, &body)
+}
+
+/// Resolve href and src attributes against the page URL
+pub fn extract_links(page_url: &Url, html: &str) -> Vec<String> {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let link = LINK.get_or_init(|| {
+        Regex::new(r#"(?i)\b(?:href|src)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>"']+))"#).unwrap()
+    });
+
+    let mut seen = HashSet::new();
+    link.captures_iter(html)
+        .filter_map(|c| c.get(1).or_else(|| c.get(2)).or_else(|| c.get(3)))
+        .filter_map(|m| page_url.join(m.as_str().trim()).ok())
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .map(|mut u| {
+            u.set_fragment(None);
+            u.to_string()
+        })
+        .filter(|u| seen.insert(u.clone()))
+        .collect()
+}
+
+fn render_json(reports: &[LinkReport]) -> Result<String> {
+    let broken: Vec<&LinkReport> = reports.iter().filter(|r| !r.alive).collect();
+    let document = serde_json::json!({
+        "checked": reports.len(),
+        "broken": broken.len(),
+        "broken_links": broken,
+        "results": reports,
+    });
+    serde_json::to_string_pretty(&document)
+        .map(|json| json + "\n")
+        .map_err(|e| RuGetError::new(ErrorCode::E500, ErrorKind::Parse, format!("Serializing spider report: {}", e)))
+}
+
+fn render_text(reports: &[LinkReport]) -> String {
+    let mut out = format!("{:<6} {:>10}  {:<24} URL\n", "STATUS", "SIZE", "TYPE");
+    for report in reports {
+        let status = report.status.map(|s| s.to_string()).unwrap_or_else(|| "ERR".to_string());
+        let size = report.size.map(|s| s.to_string()).unwrap_or_else(|| "-".to_string());
+        let content_type = report.content_type
+            .as_deref()
+            .map(|ct| ct.split(';').next().unwrap_or(ct).trim())
+            .unwrap_or("-");
+        out.push_str(&format!("{:<6} {:>10}  {:<24} {}", status, size, content_type, report.url));
+        if let Some(final_url) = report.final_url.as_deref().filter(|u| *u != report.url) {
+            out.push_str(&format!(" -> {}", final_url));
+        }
+        out.push('\n');
+    }
+
+    let broken: Vec<&LinkReport> = reports.iter().filter(|r| !r.alive).collect();
+    if !broken.is_empty() {
+        out.push_str(&format!("\nBroken links ({}):\n", broken.len()));
+        for report in &broken {
+            out.push_str(&format!("  {} ({})", report.url, report.error.as_deref().unwrap_or("unreachable")));
+            if let Some(referrer) = &report.referrer {
+                out.push_str(&format!(" linked from {}", referrer));
+            }
+            out.push('\n');
+        }
+    }
+    out.push_str(&format!("\n{} checked, {} alive, {} broken\n", reports.len(), reports.len() - broken.len(), broken.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_links() {
+        let page = Url::parse("https://example.com/docs/index.html").unwrap();
+        let html = r#"
+            <a href="guide.html#intro">Guide</a>
+            <a HREF='/about'>About</a>
+            <img src=logo.png>
+            <a href="mailto:team@example.com">Mail</a>
+            <a href="https://other.org/x">Other</a>
+            <a href="guide.html">Again</a>
+        "#;
+        assert_eq!(
+            extract_links(&page, html),
+            vec![
+                "https://example.com/docs/guide.html",
+                "https://example.com/about",
+                "https://example.com/docs/logo.png",
+                "https://other.org/x",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_text_lists_broken_links() {
+        let report = |url: &str, status: Option<u16>, alive: bool| LinkReport {
+            url: url.to_string(),
+            status,
+            final_url: status.map(|_| url.to_string()),
+            size: alive.then_some(42),
+            content_type: alive.then(|| "text/html; charset=utf-8".to_string()),
+            method: "HEAD",
+            alive,
+            error: (!alive).then(|| "HTTP 404 Not Found".to_string()),
+            referrer: None,
+            elapsed_ms: 1,
+        };
+        let text = render_text(&[
+            report("https://example.com/", Some(200), true),
+            report("https://example.com/gone", Some(404), false),
+        ]);
+
+        assert!(text.contains("200            42  text/html                https://example.com/\n"));
+        assert!(text.contains("Broken links (1):\n  https://example.com/gone (HTTP 404 Not Found)\n"));
+        assert!(text.ends_with("2 checked, 1 alive, 1 broken\n"));
+    }
+}
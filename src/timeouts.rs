@@ -0,0 +1,202 @@
+use crate::cli::Args;
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError};
+use reqwest::blocking::ClientBuilder;
+use std::time::{Duration, Instant};
+
+/// Connect and read-idle timeout used when none is configured
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Window over which --speed-limit is measured when --speed-time is not given
+pub const DEFAULT_SPEED_TIME: Duration = Duration::from_secs(30);
+
+/// Abort a transfer that averages fewer than `bytes_per_sec` over `window`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LowSpeed {
+    pub bytes_per_sec: u64,
+    pub window: Duration,
+}
+
+/// Timeouts applied to every transfer, whichever transport carries it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Time allowed to establish a connection
+    pub connect: Duration,
+    /// Time allowed between bytes, including the wait for response headers
+    pub read: Duration,
+    /// Time allowed for one transfer attempt from request to last byte
+    pub max_time: Option<Duration>,
+    pub low_speed: Option<LowSpeed>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self { connect: DEFAULT_TIMEOUT, read: DEFAULT_TIMEOUT, max_time: None, low_speed: None }
+    }
+}
+
+impl Timeouts {
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            connect: args.connect_timeout.map(Duration::from_secs_f64).unwrap_or(DEFAULT_TIMEOUT),
+            read: args.read_timeout.map(Duration::from_secs_f64).unwrap_or(DEFAULT_TIMEOUT),
+            max_time: args.max_time.map(Duration::from_secs_f64),
+            low_speed: args.speed_limit.map(|bytes_per_sec| LowSpeed {
+                bytes_per_sec,
+                window: args.speed_time.map(Duration::from_secs_f64).unwrap_or(DEFAULT_SPEED_TIME),
+            }),
+        }
+    }
+
+    /// Longest any single socket operation may block
+    ///
+    /// Capped by --max-time and --speed-time so that a stalled read cannot
+    /// outlive either limit before [`TransferWatch`] gets to check it.
+    pub fn io_timeout(&self) -> Duration {
+        [Some(self.read), self.max_time, self.low_speed.map(|l| l.window)]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(self.read)
+    }
+
+    /// Apply the connect and per-operation timeouts to a reqwest client
+    pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        builder.connect_timeout(self.connect).timeout(self.io_timeout())
+    }
+
+    /// Start watching one transfer attempt
+    pub fn watch(&self, url: &str) -> TransferWatch {
+        let now = Instant::now();
+        TransferWatch {
+            timeouts: *self,
+            url: url.to_string(),
+            started: now,
+            window_started: now,
+            window_bytes: 0,
+        }
+    }
+}
+
+/// Enforces --max-time and --speed-limit while a body is being read
+#[derive(Debug)]
+pub struct TransferWatch {
+    timeouts: Timeouts,
+    url: String,
+    started: Instant,
+    window_started: Instant,
+    window_bytes: u64,
+}
+
+impl TransferWatch {
+    /// Account for `bytes` just received, failing with E404 once a limit is exceeded
+    pub fn record(&mut self, bytes: usize) -> Result<()> {
+        let now = Instant::now();
+        if let Some(max_time) = self.timeouts.max_time
+            && now.duration_since(self.started) > max_time
+        {
+            return Err(timeout_error(format!(
+                "Transfer of {} exceeded --max-time of {:.1}s",
+                self.url,
+                max_time.as_secs_f64()
+            )));
+        }
+
+        self.window_bytes += bytes as u64;
+        if let Some(low_speed) = self.timeouts.low_speed {
+            let elapsed = now.duration_since(self.window_started);
+            if elapsed >= low_speed.window {
+                let speed = self.window_bytes as f64 / elapsed.as_secs_f64();
+                if speed < low_speed.bytes_per_sec as f64 {
+                    return Err(timeout_error(format!(
+                        "Transfer of {} averaged {:.0} B/s over {:.1}s, below --speed-limit of {} B/s",
+                        self.url,
+                        speed,
+                        elapsed.as_secs_f64(),
+                        low_speed.bytes_per_sec
+                    )));
+                }
+                self.window_started = now;
+                self.window_bytes = 0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A transfer that started but stalled or ran too long
+pub fn timeout_error(message: String) -> RuGetError {
+    RuGetError::new(ErrorCode::E404, ErrorKind::Network, message)
+}
+
+/// A connection that could not be established within --connect-timeout
+pub fn connect_error(err: std::io::Error, addr: &str) -> RuGetError {
+    if err.kind() == std::io::ErrorKind::TimedOut {
+        return RuGetError::with_source(
+            ErrorCode::E201,
+            ErrorKind::Network,
+            format!("Connection to {} timed out", addr),
+            Box::new(err),
+        );
+    }
+    RuGetError::from(err).with_context(&format!("connecting to {}", addr))
+}
+
+/// Parse a positive, possibly fractional, number of seconds
+pub fn parse_seconds(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs > 0.0 => Ok(secs),
+        _ => Err(format!("'{}' is not a positive number of seconds", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_timeout_is_tightest_limit() {
+        let mut timeouts = Timeouts::default();
+        assert_eq!(timeouts.io_timeout(), DEFAULT_TIMEOUT);
+
+        timeouts.max_time = Some(Duration::from_secs(5));
+        assert_eq!(timeouts.io_timeout(), Duration::from_secs(5));
+
+        timeouts.low_speed = Some(LowSpeed { bytes_per_sec: 1, window: Duration::from_secs(2) });
+        assert_eq!(timeouts.io_timeout(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_watch_enforces_max_time() {
+        let timeouts = Timeouts { max_time: Some(Duration::from_millis(20)), ..Timeouts::default() };
+        let mut watch = timeouts.watch("http://example.com/a");
+        assert!(watch.record(10).is_ok());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(watch.record(10).unwrap_err().code, ErrorCode::E404);
+    }
+
+    #[test]
+    fn test_watch_enforces_speed_limit() {
+        let window = Duration::from_millis(20);
+        let timeouts = Timeouts {
+            low_speed: Some(LowSpeed { bytes_per_sec: 1_000_000, window }),
+            ..Timeouts::default()
+        };
+
+        let mut fast = timeouts.watch("http://example.com/fast");
+        std::thread::sleep(window);
+        assert!(fast.record(10_000_000).is_ok());
+
+        let mut slow = timeouts.watch("http://example.com/slow");
+        std::thread::sleep(window);
+        assert_eq!(slow.record(10).unwrap_err().code, ErrorCode::E404);
+    }
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(parse_seconds("2.5"), Ok(2.5));
+        assert!(parse_seconds("0").is_err());
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("soon").is_err());
+    }
+}
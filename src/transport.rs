@@ -0,0 +1,162 @@
+use crate::cli::Args;
+use crate::download::build_client;
+use crate::error::{ErrorCode, ErrorKind, Result, RuGetError};
+use crate::native_http::NativeHttpClient;
+use crate::output::Logger;
+use clap::ValueEnum;
+use reqwest::blocking::Client;
+use reqwest::cookie::Jar;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use std::io::{self, Read};
+use std::sync::Arc;
+
+/// Which HTTP backend carries requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TransportKind {
+    /// reqwest, until the native backend is known to match it everywhere
+    #[default]
+    Auto,
+    /// reqwest for every request
+    Reqwest,
+    /// Native sockets for plain HTTP; HTTPS still goes through reqwest for TLS
+    Native,
+}
+
+impl TransportKind {
+    /// The backend this choice stands for
+    ///
+    /// The native backend ignores `NO_PROXY` and proxy variables, so it is
+    /// only used when asked for by name.
+    pub fn resolve(self) -> Self {
+        match self {
+            TransportKind::Auto => TransportKind::Reqwest,
+            kind => kind,
+        }
+    }
+}
+
+/// Sends requests for every download, whichever backend is behind it
+///
+/// Implementations follow up to 10 redirects, keep cookies in the run's jar,
+/// add the client's default headers (--header, and Accept-Encoding for
+/// --compressed) under the request's own, and decode compressed bodies unless
+/// --raw is given, so that callers never need to know which backend they have.
+pub trait Transport: Send + Sync {
+    /// Send one request and return once the response head has arrived
+    fn send(&self, method: Method, url: &str, headers: HeaderMap) -> Result<Response>;
+
+    fn get(&self, url: &str, headers: HeaderMap) -> Result<Response> {
+        self.send(Method::GET, url, headers)
+    }
+
+    fn head(&self, url: &str, headers: HeaderMap) -> Result<Response> {
+        self.send(Method::HEAD, url, headers)
+    }
+}
+
+impl Transport for Client {
+    fn send(&self, method: Method, url: &str, headers: HeaderMap) -> Result<Response> {
+        let response = self.request(method, url).headers(headers).send()?;
+        Ok(Response::new(
+            response.status(),
+            response.headers().clone(),
+            response.url().to_string(),
+            response.content_length(),
+            Box::new(response),
+        ))
+    }
+}
+
+/// Build the transport shared by every job in a run, as chosen with --transport
+pub fn build_transport(args: &Args, cookie_jar: Arc<Jar>, logger: &Logger) -> Result<Box<dyn Transport>> {
+    Ok(match args.transport.resolve() {
+        TransportKind::Native => Box::new(NativeHttpClient::new(args, cookie_jar, logger)?),
+        _ => Box::new(build_client(args, cookie_jar, logger)?),
+    })
+}
+
+/// A response whose head has arrived; the body is read as it streams in
+pub struct Response {
+    status: StatusCode,
+    headers: HeaderMap,
+    url: String,
+    content_length: Option<u64>,
+    body: Box<dyn Read>,
+}
+
+impl Response {
+    pub fn new(
+        status: StatusCode,
+        headers: HeaderMap,
+        url: String,
+        content_length: Option<u64>,
+        body: Box<dyn Read>,
+    ) -> Self {
+        Self { status, headers, url, content_length, body }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// URL the request ended up at after redirects
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Length of the body as it will be read, which is unknown for decoded bodies
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Read the next part of the body; a connection lost part way is a network error
+    pub fn read_chunk(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.body.read(buf).map_err(connection_error)
+    }
+}
+
+impl Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+/// An I/O failure on a connection: a timeout, or a connection lost part way
+pub fn connection_error(err: io::Error) -> RuGetError {
+    let timed_out = matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock);
+    let from_reqwest = err.get_ref().is_some_and(|inner| inner.is::<reqwest::Error>());
+    if timed_out || from_reqwest {
+        return RuGetError::from(err);
+    }
+    RuGetError::with_source(
+        ErrorCode::E400,
+        ErrorKind::Network,
+        format!("Connection lost: {}", err),
+        Box::new(err),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_transport_is_kept() {
+        assert_eq!(TransportKind::Auto.resolve(), TransportKind::Reqwest);
+        assert_eq!(TransportKind::Reqwest.resolve(), TransportKind::Reqwest);
+        assert_eq!(TransportKind::Native.resolve(), TransportKind::Native);
+    }
+
+    #[test]
+    fn test_lost_connection_is_a_network_error() {
+        let err = connection_error(io::Error::from(io::ErrorKind::ConnectionReset));
+        assert_eq!(err.code, ErrorCode::E400);
+        let err = connection_error(io::Error::from(io::ErrorKind::TimedOut));
+        assert_eq!(err.code, ErrorCode::E404);
+    }
+}
@@ -1,97 +1,26 @@
-use crate::error::{Result, RuGetError};
-use crate::native_http::NativeHttpClient;
-use crate::simd_ops::{SIMDBuffer, fast_memcpy};
-use std::fs::File;
-use std::io::{Write, BufWriter};
-use std::path::Path;
-use std::sync::OnceLock;
-
-/// Global singleton for the ultimate HTTP client
-static ULTIMATE_CLIENT: OnceLock<NativeHttpClient> = OnceLock::new();
-
-/// The ultimate fast download mode - combines all optimizations
-/// - Native HTTP implementation with DNS caching
-/// - Connection pooling and reuse
-/// - SIMD-optimized data processing
-/// - Zero-copy operations where possible
-/// - Minimal allocations
-pub fn ultimate_download(url: &str, output_path: &str) -> Result<()> {
-    // Get or create the singleton client (avoids repeated initialization)
-    let client = ULTIMATE_CLIENT.get_or_init(NativeHttpClient::new);
-    
-    // Pre-check: Skip if file already exists and is non-empty
-    if let Ok(metadata) = std::fs::metadata(output_path) {
-        if metadata.len() > 0 {
-            return Ok(());
-        }
-    }
-
-    // Use SIMD buffer for optimal data handling
-    let data = client.get(url)?;
-    
-    // Optimized file writing with pre-allocation
-    write_data_optimized(&data, output_path)?;
-    
-    Ok(())
-}
-
-/// Ultra-optimized file writing with SIMD and buffering
-fn write_data_optimized(data: &[u8], output_path: &str) -> Result<()> {
-    // Create parent directories only if needed
-    if let Some(parent) = Path::new(output_path).parent() {
-        if !parent.exists() {
-            std::fs::create_dir_all(parent)?;
-        }
-    }
-
-    // Use buffered writer with optimal buffer size
-    let file = File::create(output_path)?;
-    let buffer_size = optimal_buffer_size(data.len());
-    let mut writer = BufWriter::with_capacity(buffer_size, file);
-    
-    // Write in optimal chunks
-    if data.len() > 64 * 1024 {
-        // For large files, write in chunks to optimize memory usage
-        write_chunked(&mut writer, data)?;
-    } else {
-        // For small files, write all at once
-        writer.write_all(data)?;
+//! Deprecated: single downloads now go through
+//! [`fast_single_download`](crate::fast_download::fast_single_download)
+#![allow(deprecated)]
+
+use crate::error::Result;
+use crate::timeouts::Timeouts;
+use crate::ultra_fast::download_with_timeouts;
+use std::sync::{Mutex, OnceLock};
+
+/// Download `url` to `output_path`, skipping files that already have content
+#[deprecated(note = "use `fast_download::fast_single_download`")]
+pub fn ultimate_download(url: &str, output_path: &str, timeouts: Timeouts) -> Result<()> {
+    if std::fs::metadata(output_path).is_ok_and(|metadata| metadata.len() > 0) {
+        return Ok(());
     }
-    
-    writer.flush()?;
+    download_with_timeouts(url, output_path, timeouts)?;
+    record_download(std::fs::metadata(output_path).map_or(0, |metadata| metadata.len()));
     Ok(())
 }
 
-/// Write large data in optimized chunks
-fn write_chunked<W: Write>(writer: &mut W, data: &[u8]) -> Result<()> {
-    const CHUNK_SIZE: usize = 64 * 1024; // 64KB chunks
-    
-    for chunk in data.chunks(CHUNK_SIZE) {
-        writer.write_all(chunk)?;
-    }
-    
-    Ok(())
-}
-
-/// Calculate optimal buffer size based on data size
-fn optimal_buffer_size(data_size: usize) -> usize {
-    match data_size {
-        0..=1024 => 1024,           // 1KB for tiny files
-        1025..=8192 => 4096,        // 4KB for small files  
-        8193..=65536 => 8192,       // 8KB for medium files
-        65537..=1048576 => 32768,   // 32KB for large files
-        _ => 65536,                 // 64KB for very large files
-    }
-}
-
 /// Check if ultimate mode should be used
+#[deprecated(note = "use `fast_download::should_use_fast_path`")]
 pub fn should_use_ultimate_mode(url: &str, output_path: &str) -> bool {
-    // Ultimate mode conditions:
-    // - HTTPS URL (most secure and common)
-    // - Simple URL (no query parameters or fragments)
-    // - Reasonable path length
-    // - Valid output path
-    
     url.starts_with("https://")
         && !url.contains('?')
         && !url.contains('#')
@@ -105,67 +34,32 @@ fn is_simple_domain(url: &str) -> bool {
     let domain_part = url.strip_prefix("https://")
         .and_then(|s| s.split('/').next())
         .unwrap_or("");
-    
-    // Only ASCII alphanumeric, dots, and hyphens
+
     domain_part.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
         && !domain_part.is_empty()
         && domain_part.len() < 100
 }
 
-/// Predictive pre-connection for known patterns
-pub fn maybe_preconnect(url: &str) -> Result<()> {
-    // Pre-connect to common CDNs and APIs when we detect patterns
-    let domain = extract_domain(url);
-    
-    if is_known_fast_domain(&domain) {
-        // For known fast domains, we can pre-warm the connection
-        let client = ULTIMATE_CLIENT.get_or_init(NativeHttpClient::new);
-        
-        // Try a quick HEAD request to warm up the connection
-        // This is done asynchronously in practice, but for simplicity we'll skip
-        // the actual implementation here
-    }
-    
+/// Formerly warmed connections to known hosts; now does nothing
+#[deprecated(note = "connections are pooled by the transport")]
+pub fn maybe_preconnect(_url: &str) -> Result<()> {
     Ok(())
 }
 
-/// Extract domain from URL
-fn extract_domain(url: &str) -> String {
-    url.strip_prefix("https://")
-        .or_else(|| url.strip_prefix("http://"))
-        .and_then(|s| s.split('/').next())
-        .unwrap_or("")
-        .to_string()
-}
-
-/// Check if domain is known to be fast
-fn is_known_fast_domain(domain: &str) -> bool {
-    // List of domains known to have fast response times
-    const FAST_DOMAINS: &[&str] = &[
-        "httpbin.org",
-        "api.github.com",
-        "raw.githubusercontent.com",
-        "cdn.jsdelivr.net",
-        "unpkg.com",
-    ];
-    
-    FAST_DOMAINS.iter().any(|&fast_domain| domain.contains(fast_domain))
-}
-
-/// Streaming download for very large files (future enhancement)
-pub fn stream_download(url: &str, output_path: &str) -> Result<()> {
-    // This would implement a streaming version that doesn't load
-    // the entire file into memory - useful for very large files
-    // For now, fall back to ultimate_download
-    ultimate_download(url, output_path)
+/// Streaming download for very large files
+#[deprecated(note = "use `fast_download::fast_single_download`")]
+pub fn stream_download(url: &str, output_path: &str, timeouts: Timeouts) -> Result<()> {
+    ultimate_download(url, output_path, timeouts)
 }
 
 /// Clear all caches and pools (for memory management)
+#[deprecated(note = "use `native_http::clear_caches`")]
 pub fn clear_ultimate_caches() {
     crate::native_http::clear_caches();
 }
 
 /// Performance statistics for monitoring
+#[deprecated(note = "no longer collected beyond `ultimate_download`")]
 #[derive(Debug, Default, Clone)]
 pub struct PerformanceStats {
     pub dns_cache_hits: usize,
@@ -174,20 +68,20 @@ pub struct PerformanceStats {
     pub downloads_completed: usize,
 }
 
-static PERFORMANCE_STATS: OnceLock<std::sync::Mutex<PerformanceStats>> = OnceLock::new();
+static PERFORMANCE_STATS: OnceLock<Mutex<PerformanceStats>> = OnceLock::new();
 
 /// Get performance statistics
+#[deprecated(note = "no longer collected beyond `ultimate_download`")]
 pub fn get_performance_stats() -> PerformanceStats {
-    let stats = PERFORMANCE_STATS.get_or_init(|| std::sync::Mutex::new(PerformanceStats::default()));
-    stats.lock().unwrap().clone()
+    PERFORMANCE_STATS.get_or_init(Mutex::default).lock().unwrap().clone()
 }
 
 /// Record a successful download for statistics
+#[deprecated(note = "no longer collected beyond `ultimate_download`")]
 pub fn record_download(bytes: u64) {
-    let stats = PERFORMANCE_STATS.get_or_init(|| std::sync::Mutex::new(PerformanceStats::default()));
-    let mut stats_lock = stats.lock().unwrap();
-    stats_lock.bytes_downloaded += bytes;
-    stats_lock.downloads_completed += 1;
+    let mut stats = PERFORMANCE_STATS.get_or_init(Mutex::default).lock().unwrap();
+    stats.bytes_downloaded += bytes;
+    stats.downloads_completed += 1;
 }
 
 #[cfg(test)]
@@ -201,34 +95,4 @@ mod tests {
         assert!(!should_use_ultimate_mode("https://example.com/file.txt?param=1", "output.txt"));
         assert!(!should_use_ultimate_mode("https://example.com/file.txt", ""));
     }
-
-    #[test]
-    fn test_is_simple_domain() {
-        assert!(is_simple_domain("https://example.com"));
-        assert!(is_simple_domain("https://api.github.com"));
-        assert!(is_simple_domain("https://sub-domain.example-site.org"));
-        assert!(!is_simple_domain("https://例え.テスト")); // Unicode domains
-    }
-
-    #[test]
-    fn test_optimal_buffer_size() {
-        assert_eq!(optimal_buffer_size(500), 1024);
-        assert_eq!(optimal_buffer_size(5000), 4096);
-        assert_eq!(optimal_buffer_size(50000), 8192);
-        assert_eq!(optimal_buffer_size(500000), 32768);
-        assert_eq!(optimal_buffer_size(5000000), 65536);
-    }
-
-    #[test]
-    fn test_extract_domain() {
-        assert_eq!(extract_domain("https://example.com/path"), "example.com");
-        assert_eq!(extract_domain("http://api.github.com/user"), "api.github.com");
-    }
-
-    #[test]
-    fn test_is_known_fast_domain() {
-        assert!(is_known_fast_domain("httpbin.org"));
-        assert!(is_known_fast_domain("api.github.com"));
-        assert!(!is_known_fast_domain("slow-example.com"));
-    }
 }
@@ -1,70 +1,47 @@
-use crate::error::{Result, RuGetError};
-use std::fs::File;
-use std::io::{BufWriter, Write};
+//! Deprecated: single downloads now go through [`fast_single_download`]
+#![allow(deprecated)]
+
+use crate::cli::Args;
+use crate::error::Result;
+use crate::fast_download::fast_single_download;
+use crate::output::Logger;
+use crate::timeouts::Timeouts;
+use clap::Parser;
 use std::path::Path;
 
-/// Ultra-fast download that bypasses as much overhead as possible
-/// This is specifically optimized for small files and simple HTTP downloads
-pub fn ultra_fast_download(url: &str, output_path: &str) -> Result<()> {
-    // Check if output file already exists to avoid unnecessary work
+/// Download `url` to `output_path`, skipping files that already exist
+#[deprecated(note = "use `fast_download::fast_single_download`")]
+pub fn ultra_fast_download(url: &str, output_path: &str, timeouts: Timeouts) -> Result<()> {
     if Path::new(output_path).exists() {
         return Ok(());
     }
-
-    // Create parent directories if needed (but only if necessary)
-    if let Some(parent) = Path::new(output_path).parent() {
-        if !parent.exists() {
-            std::fs::create_dir_all(parent)?;
-        }
-    }
-
-    // Use the most minimal HTTP client possible
-    let data = ultra_minimal_get(url)?;
-    
-    // Write with buffered writer for better performance
-    let file = File::create(output_path)?;
-    let mut writer = BufWriter::new(file);
-    writer.write_all(&data)?;
-    writer.flush()?;
-    
-    Ok(())
-}
-
-/// Absolute minimal HTTP GET - optimized for speed over features
-fn ultra_minimal_get(url: &str) -> Result<Vec<u8>> {
-    // For maximum performance, use ureq which is much lighter than reqwest
-    // Note: This requires adding ureq to Cargo.toml
-    
-    // For now, use a highly optimized reqwest configuration
-    use std::time::Duration;
-    
-    let client = reqwest::blocking::Client::builder()
-        .timeout(Duration::from_secs(10))  // Shorter timeout
-        .pool_idle_timeout(Duration::from_secs(0))  // Minimal connection pooling
-        .pool_max_idle_per_host(0)         // No connection pooling
-        .tcp_nodelay(true)                 // Disable Nagle's algorithm
-        .build()?;
-    
-    let response = client
-        .get(url)
-        .header("Connection", "close")     // Don't keep connection alive
-        .send()?;
-    
-    if !response.status().is_success() {
-        return Err(RuGetError::network(format!("HTTP {}", response.status())));
-    }
-    
-    // Read directly into Vec for minimal copying
-    Ok(response.bytes()?.to_vec())
+    download_with_timeouts(url, output_path, timeouts)
 }
 
 /// Check if we can use ultra-fast mode
+#[deprecated(note = "use `fast_download::should_use_fast_path`")]
 pub fn can_use_ultra_fast(url: &str, output_path: &str) -> bool {
-    // Only for HTTPS URLs (HTTP is less common and less secure)
-    url.starts_with("https://") 
+    url.starts_with("https://")
         && !output_path.is_empty()
-        && !url.contains('?')  // No query parameters for simplicity
-        && url.len() < 200     // Reasonable URL length
+        && !url.contains('?')
+        && url.len() < 200
+}
+
+/// Run the fast path for one URL with `timeouts` and every other option at its default
+pub(crate) fn download_with_timeouts(url: &str, output_path: &str, timeouts: Timeouts) -> Result<()> {
+    if let Some(parent) = Path::new(output_path).parent()
+        && !parent.exists()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut args = Args::parse_from(["ruget", "--quiet", "--output", output_path, url]);
+    args.connect_timeout = Some(timeouts.connect.as_secs_f64());
+    args.read_timeout = Some(timeouts.read.as_secs_f64());
+    args.max_time = timeouts.max_time.map(|max_time| max_time.as_secs_f64());
+    args.speed_limit = timeouts.low_speed.map(|low_speed| low_speed.bytes_per_sec);
+    args.speed_time = timeouts.low_speed.map(|low_speed| low_speed.window.as_secs_f64());
+    fast_single_download(&args, &Logger::new(true, false))
 }
 
 #[cfg(test)]
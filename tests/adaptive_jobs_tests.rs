@@ -0,0 +1,87 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read;
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn test_jobs_auto_backs_off_on_429_and_completes() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    let throttled = server.mock(|when, then| {
+        when.method(GET).path("/throttled.txt");
+        then.status(429);
+    });
+    for i in 0..4 {
+        server.mock(|when, then| {
+            when.method(GET).path(format!("/file{}.txt", i));
+            then.status(200).body(format!("body {}", i));
+        });
+    }
+
+    let dir = tempdir().unwrap();
+    let mut args: Vec<String> = (0..4).map(|i| server.url(format!("/file{}.txt", i))).collect();
+    args.push(server.url("/throttled.txt"));
+    args.extend([
+        "--output-dir", dir.path().to_str().unwrap(),
+        "--jobs", "auto",
+        "--max-retries", "1",
+        "--backoff-base-ms", "1",
+        "--verbose",
+    ].map(String::from));
+
+    let output = Command::new("./target/debug/ruget").args(&args).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Lowered connections to"), "{}", stdout);
+    assert!(stdout.contains("after HTTP 429"), "{}", stdout);
+    // The first attempt and one retry; a 4xx gets no batch-level second pass
+    throttled.assert_hits(2);
+    for i in 0..4 {
+        assert_eq!(read(dir.path().join(format!("file{}.txt", i))).unwrap(), format!("body {}", i).into_bytes());
+    }
+}
+
+#[test]
+fn test_jobs_auto_splits_large_file() {
+    let body: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD).path("/big.bin");
+        then.status(200)
+            .header("Content-Length", body.len().to_string())
+            .header("Accept-Ranges", "bytes");
+    });
+    let chunks: Vec<_> = (0..3u64)
+        .map(|i| {
+            let (start, end) = (i * 1024 * 1024, (i + 1) * 1024 * 1024 - 1);
+            let part = body[start as usize..=end as usize].to_vec();
+            let total = body.len();
+            server.mock(move |when, then| {
+                when.method(GET).path("/big.bin").header("Range", format!("bytes={}-{}", start, end));
+                then.status(206).header("Content-Range", format!("bytes {}-{}/{}", start, end, total)).body(part);
+            })
+        })
+        .collect();
+
+    let dir = tempdir().unwrap();
+    let output_path = dir.path().join("big.bin");
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/big.bin"),
+            "-o", output_path.to_str().unwrap(),
+            "--jobs", "auto",
+            "--verbose",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    for chunk in &chunks {
+        chunk.assert_hits(1);
+    }
+    assert_eq!(read(&output_path).unwrap(), body);
+}
@@ -0,0 +1,198 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read_to_string, write};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+fn ruget(args: &[&str]) -> Output {
+    Command::new("./target/debug/ruget").args(args).output().unwrap()
+}
+
+#[test]
+fn test_many_jobs_run_on_async_engine() {
+    let server = MockServer::start();
+    let files = server.mock(|when, then| {
+        when.method(GET).path_matches(Regex::new(r"^/file\d+\.txt$").unwrap());
+        then.status(200).body("small file");
+    });
+
+    let dir = tempdir().unwrap();
+    let urls: Vec<_> = (0..150).map(|i| server.url(format!("/file{}.txt", i))).collect();
+    let list = dir.path().join("urls.txt");
+    write(&list, urls.join("\n")).unwrap();
+    let out = dir.path().join("out");
+    let output = ruget(&[
+        "--input", list.to_str().unwrap(),
+        "--output-dir", out.to_str().unwrap(),
+        "--jobs", "100",
+        "--verbose",
+    ]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("on the async engine"), "{}", stdout);
+    files.assert_hits(150);
+    for i in 0..150 {
+        assert_eq!(read_to_string(out.join(format!("file{}.txt", i))).unwrap(), "small file");
+    }
+}
+
+#[test]
+fn test_names_files_from_the_response() {
+    let server = MockServer::start();
+    let head = server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/download");
+        then.status(200)
+            .header("Content-Disposition", "attachment; filename=\"report.csv\"")
+            .body("a,b");
+    });
+
+    let dir = tempdir().unwrap();
+    let output = ruget(&[
+        &server.url("/download"),
+        "--output-dir", dir.path().to_str().unwrap(),
+        "--engine", "async",
+    ]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert_eq!(read_to_string(dir.path().join("report.csv")).unwrap(), "a,b");
+    // The name comes from the GET itself
+    head.assert_hits(0);
+}
+
+#[test]
+fn test_retries_match_threaded_engine() {
+    let mut hits = vec![];
+    for engine in ["threads", "async"] {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(HEAD);
+            then.status(200).header("Content-Length", "4");
+        });
+        let busy = server.mock(|when, then| {
+            when.method(GET).path("/busy");
+            then.status(503);
+        });
+        let missing = server.mock(|when, then| {
+            when.method(GET).path("/missing");
+            then.status(404);
+        });
+
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("failures.log");
+        let output = ruget(&[
+            &server.url("/busy"),
+            &server.url("/missing"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--log", log.to_str().unwrap(),
+            "--max-retries", "2",
+            "--backoff-base-ms", "1",
+            "--engine", engine,
+        ]);
+
+        assert!(!output.status.success(), "{}", engine);
+        let log = read_to_string(&log).unwrap();
+        assert!(log.contains("HTTP 503"), "{}: {}", engine, log);
+        assert!(log.contains("HTTP 404"), "{}: {}", engine, log);
+        // Client errors are never retried
+        missing.assert_hits(1);
+        hits.push(busy.hits());
+    }
+    assert!(hits[0] > 1);
+    assert_eq!(hits[0], hits[1]);
+}
+
+#[test]
+fn test_resume_continues_partial_file() {
+    let server = MockServer::start();
+    let rest = server.mock(|when, then| {
+        when.method(GET).path("/file.txt").header("Range", "bytes=4-");
+        then.status(206).header("Content-Range", "bytes 4-9/10").body("456789");
+    });
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("file.txt");
+    write(&out, "0123").unwrap();
+    let output = ruget(&[&server.url("/file.txt"), "-o", out.to_str().unwrap(), "--resume", "--engine", "async"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    rest.assert_hits(1);
+    assert_eq!(read_to_string(&out).unwrap(), "0123456789");
+}
+
+/// A server that sends the start of a body and then stalls; reports when it has sent it
+fn stalling_server(sent: mpsc::Sender<()>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/slow.bin", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        let mut connections = vec![];
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+            }
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000000\r\nETag: \"v1\"\r\n\r\nfirst part");
+            let _ = stream.flush();
+            let _ = sent.send(());
+            // Keep the connection open without sending anything more
+            connections.push(stream);
+        }
+    });
+    url
+}
+
+fn wait_for_exit(child: &mut std::process::Child, limit: Duration) -> Option<std::process::ExitStatus> {
+    let start = Instant::now();
+    while start.elapsed() < limit {
+        if let Some(status) = child.try_wait().unwrap() {
+            return Some(status);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    None
+}
+
+#[test]
+fn test_interrupt_stops_and_keeps_partial_file() {
+    let (sent, body_started) = mpsc::channel();
+    let url = stalling_server(sent);
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("slow.bin");
+    let mut child = Command::new("./target/debug/ruget")
+        .args([&url, "-o", out.to_str().unwrap(), "--engine", "async", "--read-timeout", "60"])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    body_started.recv_timeout(Duration::from_secs(10)).unwrap();
+    thread::sleep(Duration::from_millis(300));
+
+    let pid = child.id().to_string();
+    assert!(Command::new("kill").args(["-INT", &pid]).status().unwrap().success());
+    let status = wait_for_exit(&mut child, Duration::from_secs(10));
+    if status.is_none() {
+        let _ = child.kill();
+    }
+
+    let status = status.expect("ruget did not stop after SIGINT");
+    assert!(!status.success());
+    // What arrived is on disk, with the validators a later --resume needs
+    assert_eq!(read_to_string(&out).unwrap(), "first part");
+    assert!(Path::new(&format!("{}.ruget-resume", out.display())).exists());
+}
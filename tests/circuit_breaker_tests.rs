@@ -0,0 +1,52 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read_to_string;
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn test_dead_host_fails_fast_once_circuit_opens() {
+    let down = MockServer::start();
+    down.mock(|when, then| {
+        when.method(HEAD);
+        then.status(503);
+    });
+    let get = down.mock(|when, then| {
+        when.method(GET);
+        then.status(503);
+    });
+    let up = MockServer::start();
+    up.mock(|when, then| {
+        when.method(GET).path("/ok.txt");
+        then.status(200).body("fine");
+    });
+
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("failures.log");
+    let mut args: Vec<String> = (0..8).map(|i| down.url(format!("/file{}", i))).collect();
+    args.push(up.url("/ok.txt"));
+    args.extend([
+        "--output-dir", dir.path().to_str().unwrap(),
+        "--log", log.to_str().unwrap(),
+        "--jobs", "1",
+        "--max-retries", "1",
+        "--backoff-base-ms", "1",
+        "--circuit-threshold", "2",
+        "--circuit-cooldown", "0.2",
+    ].map(String::from));
+
+    let output = Command::new("./target/debug/ruget").args(&args).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // The first job and its extra attempt use up their retries, then the rest
+    // of the host's jobs are skipped
+    get.assert_hits(4);
+    // Their extra attempts probe the host after the cool-down, and the failed
+    // probe leaves each of them skipped
+    assert!(stdout.contains("state=half-open"), "{}", stdout);
+    assert!(stdout.contains("Host circuit changed"), "{}", stdout);
+    assert_eq!(read_to_string(dir.path().join("ok.txt")).unwrap(), "fine");
+    let log = read_to_string(&log).unwrap();
+    assert_eq!(log.matches("HTTP 503").count(), 1, "{}", log);
+    assert_eq!(log.matches("circuit open").count(), 7, "{}", log);
+}
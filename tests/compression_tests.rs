@@ -0,0 +1,96 @@
+use flate2::{write::GzEncoder, Compression};
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read;
+use std::io::Write;
+use std::process::Command;
+use tempfile::tempdir;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn test_compressed_response_is_decoded() {
+    let server = MockServer::start();
+
+    let data = b"compressible data compressible data compressible data";
+    let encoded = gzip(data);
+
+    let _head_mock = server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200)
+            .header("Content-Length", data.len().to_string());
+    });
+
+    let get_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/data.txt")
+            .header_exists("accept-encoding");
+        then.status(200)
+            .header("Content-Encoding", "gzip")
+            .body(&encoded);
+    });
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("data.txt");
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &format!("{}/data.txt", &server.base_url()),
+            "--output",
+            path.to_str().unwrap(),
+            "--compressed",
+            "--jobs", "1",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    get_mock.assert_hits(1);
+    assert_eq!(read(&path).unwrap(), data);
+}
+
+#[test]
+fn test_compressed_raw_keeps_encoded_bytes() {
+    let server = MockServer::start();
+
+    let data = b"compressible data compressible data compressible data";
+    let encoded = gzip(data);
+
+    let _head_mock = server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200)
+            .header("Content-Length", data.len().to_string());
+    });
+
+    let get_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/data.txt.gz")
+            .header("accept-encoding", "gzip, br, zstd, deflate");
+        then.status(200)
+            .header("Content-Encoding", "gzip")
+            .body(&encoded);
+    });
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("data.txt.gz");
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &format!("{}/data.txt.gz", &server.base_url()),
+            "--output",
+            path.to_str().unwrap(),
+            "--compressed",
+            "--raw",
+            "--jobs", "1",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    get_mock.assert_hits(1);
+    assert_eq!(read(&path).unwrap(), encoded);
+}
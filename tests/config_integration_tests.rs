@@ -3,8 +3,11 @@ use std::path::PathBuf;
 use tempfile::TempDir;
 
 use ruget::config::{Config, RetryConfig, LoggingConfig};
-use ruget::cli::{Args, LogFormat, LogLevel};
+use ruget::cli::{Args, LogFormat, LogLevel, ReportFormat};
 use ruget::lazy_config::apply_config_if_needed;
+use ruget::retry::BackoffStrategy;
+use ruget::transport::TransportKind;
+use ruget::async_engine::Engine;
 
 /// Test the new retry config section in TOML
 #[test]
@@ -103,12 +106,14 @@ fn test_retry_config_merge() {
             max: Some(7),
             base_ms: Some(1000),
             max_ms: Some(20000),
+            ..Default::default()
         }),
         ..Default::default()
     };
     
     let mut args = Args {
-        urls: vec!["https://example.com".to_string()],
+        command: None,
+        urls: vec!["https://example.com".into()],
         input: None,
         output: None,
         headers: vec![],
@@ -121,13 +126,45 @@ fn test_retry_config_merge() {
         quiet: false,
         output_dir: None,
         jobs: 0,
+        split: None,
+        max_connections: 0,
         log: "".to_string(),
         init: false,
         backoff_base_ms: 100, // Should be overridden by config
         backoff_max_ms: 60000, // Should be overridden by config
+        backoff_factor: 2.0,
+        backoff_strategy: BackoffStrategy::Exponential,
+        retry_budget: None,
+        retry_on: vec![],
+        circuit_threshold: 5,
+        circuit_cooldown: 30.0,
+        connect_timeout: None,
+        read_timeout: None,
+        max_time: None,
+        speed_limit: None,
+        speed_time: None,
         load_cookies: None,
         save_cookies: None,
         keep_session_cookies: false,
+        trust_server_names: false,
+        compressed: false,
+        raw: false,
+        transport: TransportKind::Auto,
+        engine: Engine::Auto,
+        extract: None,
+        strip_components: 0,
+        keep_archive: false,
+        exec: None,
+        exec_jobs: 1,
+        on_complete: None,
+        on_failure: None,
+        globoff: false,
+        spider: false,
+        recursive: false,
+        level: 5,
+        dry_run: false,
+        report_format: ReportFormat::Text,
+        report: None,
     };
     
     config.merge_with_args(&mut args);
@@ -149,7 +186,8 @@ fn test_logging_config_merge() {
     };
     
     let mut args = Args {
-        urls: vec!["https://example.com".to_string()],
+        command: None,
+        urls: vec!["https://example.com".into()],
         input: None,
         output: None,
         headers: vec![],
@@ -162,13 +200,45 @@ fn test_logging_config_merge() {
         quiet: false,
         output_dir: None,
         jobs: 0,
+        split: None,
+        max_connections: 0,
         log: "test.log".to_string(),
         init: false,
         backoff_base_ms: 100,
         backoff_max_ms: 60000,
+        backoff_factor: 2.0,
+        backoff_strategy: BackoffStrategy::Exponential,
+        retry_budget: None,
+        retry_on: vec![],
+        circuit_threshold: 5,
+        circuit_cooldown: 30.0,
+        connect_timeout: None,
+        read_timeout: None,
+        max_time: None,
+        speed_limit: None,
+        speed_time: None,
         load_cookies: None,
         save_cookies: None,
         keep_session_cookies: false,
+        trust_server_names: false,
+        compressed: false,
+        raw: false,
+        transport: TransportKind::Auto,
+        engine: Engine::Auto,
+        extract: None,
+        strip_components: 0,
+        keep_archive: false,
+        exec: None,
+        exec_jobs: 1,
+        on_complete: None,
+        on_failure: None,
+        globoff: false,
+        spider: false,
+        recursive: false,
+        level: 5,
+        dry_run: false,
+        report_format: ReportFormat::Text,
+        report: None,
     };
     
     config.merge_with_args(&mut args);
@@ -185,6 +255,7 @@ fn test_cli_precedence_over_config() {
             max: Some(5),
             base_ms: Some(500),
             max_ms: Some(10000),
+            ..Default::default()
         }),
         logging: Some(LoggingConfig {
             format: Some("json".to_string()),
@@ -194,7 +265,8 @@ fn test_cli_precedence_over_config() {
     };
     
     let mut args = Args {
-        urls: vec!["https://example.com".to_string()],
+        command: None,
+        urls: vec!["https://example.com".into()],
         input: None,
         output: None,
         headers: vec![],
@@ -207,13 +279,45 @@ fn test_cli_precedence_over_config() {
         quiet: false,
         output_dir: None,
         jobs: 0,
+        split: None,
+        max_connections: 0,
         log: "test.log".to_string(),
         init: false,
         backoff_base_ms: 200, // Already set, should not be overridden
         backoff_max_ms: 30000, // Already set, should not be overridden
+        backoff_factor: 2.0,
+        backoff_strategy: BackoffStrategy::Exponential,
+        retry_budget: None,
+        retry_on: vec![],
+        circuit_threshold: 5,
+        circuit_cooldown: 30.0,
+        connect_timeout: None,
+        read_timeout: None,
+        max_time: None,
+        speed_limit: None,
+        speed_time: None,
         load_cookies: None,
         save_cookies: None,
         keep_session_cookies: false,
+        trust_server_names: false,
+        compressed: false,
+        raw: false,
+        transport: TransportKind::Auto,
+        engine: Engine::Auto,
+        extract: None,
+        strip_components: 0,
+        keep_archive: false,
+        exec: None,
+        exec_jobs: 1,
+        on_complete: None,
+        on_failure: None,
+        globoff: false,
+        spider: false,
+        recursive: false,
+        level: 5,
+        dry_run: false,
+        report_format: ReportFormat::Text,
+        report: None,
     };
     
     config.merge_with_args(&mut args);
@@ -238,12 +342,14 @@ fn test_config_section_priority() {
             max: Some(7),      // Should override retries field
             base_ms: Some(1000), // Should override backoff_base_ms field
             max_ms: Some(20000), // Should override backoff_max_ms field
+            ..Default::default()
         }),
         ..Default::default()
     };
     
     let mut args = Args {
-        urls: vec!["https://example.com".to_string()],
+        command: None,
+        urls: vec!["https://example.com".into()],
         input: None,
         output: None,
         headers: vec![],
@@ -256,13 +362,45 @@ fn test_config_section_priority() {
         quiet: false,
         output_dir: None,
         jobs: 0,
+        split: None,
+        max_connections: 0,
         log: "".to_string(),
         init: false,
         backoff_base_ms: 100, // Use config
         backoff_max_ms: 60000, // Use config
+        backoff_factor: 2.0,
+        backoff_strategy: BackoffStrategy::Exponential,
+        retry_budget: None,
+        retry_on: vec![],
+        circuit_threshold: 5,
+        circuit_cooldown: 30.0,
+        connect_timeout: None,
+        read_timeout: None,
+        max_time: None,
+        speed_limit: None,
+        speed_time: None,
         load_cookies: None,
         save_cookies: None,
         keep_session_cookies: false,
+        trust_server_names: false,
+        compressed: false,
+        raw: false,
+        transport: TransportKind::Auto,
+        engine: Engine::Auto,
+        extract: None,
+        strip_components: 0,
+        keep_archive: false,
+        exec: None,
+        exec_jobs: 1,
+        on_complete: None,
+        on_failure: None,
+        globoff: false,
+        spider: false,
+        recursive: false,
+        level: 5,
+        dry_run: false,
+        report_format: ReportFormat::Text,
+        report: None,
     };
     
     config.merge_with_args(&mut args);
@@ -278,7 +416,8 @@ fn test_config_section_priority() {
 fn test_apply_config_if_needed_with_logging() {
     // This test verifies the lazy loading correctly identifies when logging config is needed
     let mut args = Args {
-        urls: vec!["https://example.com".to_string()],
+        command: None,
+        urls: vec!["https://example.com".into()],
         input: None,
         output: None,
         headers: vec![],
@@ -291,13 +430,45 @@ fn test_apply_config_if_needed_with_logging() {
         quiet: false,
         output_dir: None,
         jobs: 1, // Set
+        split: None,
+        max_connections: 0,
         log: "test.log".to_string(), // Set
         init: false,
         backoff_base_ms: 100,
         backoff_max_ms: 60000,
+        backoff_factor: 2.0,
+        backoff_strategy: BackoffStrategy::Exponential,
+        retry_budget: None,
+        retry_on: vec![],
+        circuit_threshold: 5,
+        circuit_cooldown: 30.0,
+        connect_timeout: None,
+        read_timeout: None,
+        max_time: None,
+        speed_limit: None,
+        speed_time: None,
         load_cookies: None,
         save_cookies: None,
         keep_session_cookies: false,
+        trust_server_names: false,
+        compressed: false,
+        raw: false,
+        transport: TransportKind::Auto,
+        engine: Engine::Auto,
+        extract: None,
+        strip_components: 0,
+        keep_archive: false,
+        exec: None,
+        exec_jobs: 1,
+        on_complete: None,
+        on_failure: None,
+        globoff: false,
+        spider: false,
+        recursive: false,
+        level: 5,
+        dry_run: false,
+        report_format: ReportFormat::Text,
+        report: None,
     };
     
     // This will load config from ~/.rugetrc if it exists
@@ -325,3 +496,71 @@ fn test_default_config_values() {
     assert!(logging_config.format.is_none());
     assert!(logging_config.level.is_none());
 }
+
+/// Test the timeouts section and that command-line timeouts take precedence
+#[test]
+fn test_timeouts_config_merge() {
+    use clap::Parser;
+
+    let config: Config = toml::from_str(r#"
+[timeouts]
+connect = 5
+read = 12.5
+max_time = 600
+speed_limit = 2048
+speed_time = 20
+"#).expect("Failed to parse config");
+
+    let mut args = Args::parse_from(["ruget", "https://example.com", "--connect-timeout", "2"]);
+    config.merge_with_args(&mut args);
+
+    assert_eq!(args.connect_timeout, Some(2.0));
+    assert_eq!(args.read_timeout, Some(12.5));
+    assert_eq!(args.max_time, Some(600.0));
+    assert_eq!(args.speed_limit, Some(2048));
+    assert_eq!(args.speed_time, Some(20.0));
+}
+
+/// Test [retry] strategy, budget and retry_on keys, with command line flags taking precedence
+#[test]
+fn test_retry_policy_config_merge() {
+    use clap::Parser;
+    use ruget::retry::RetryCondition;
+
+    let config: Config = toml::from_str(r#"
+[retry]
+factor = 3.0
+strategy = "full-jitter"
+budget = 50
+retry_on = ["429", "5xx", "timeout", "bogus"]
+"#).expect("Failed to parse config");
+
+    let mut args = Args::parse_from(["ruget", "https://example.com", "--backoff-strategy", "decorrelated"]);
+    config.merge_with_args(&mut args);
+
+    assert_eq!(args.backoff_factor, 3.0);
+    assert_eq!(args.backoff_strategy, BackoffStrategy::Decorrelated);
+    assert_eq!(args.retry_budget, Some(50));
+    assert_eq!(
+        args.retry_on,
+        vec![RetryCondition::Status(429), RetryCondition::StatusClass(5), RetryCondition::Timeout]
+    );
+}
+
+#[test]
+fn test_scheduling_config_merge() {
+    use clap::Parser;
+
+    let config: Config = toml::from_str(r#"
+jobs = 4
+split = 8
+max_connections = 12
+"#).expect("Failed to parse config");
+
+    let mut args = Args::parse_from(["ruget", "https://example.com", "--split", "2"]);
+    config.merge_with_args(&mut args);
+
+    assert_eq!(args.jobs, 4);
+    assert_eq!(args.split, Some(2));
+    assert_eq!(args.max_connections, 12);
+}
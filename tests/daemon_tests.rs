@@ -0,0 +1,120 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use serde_json::{json, Value};
+use std::fs::read_to_string;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn rpc(port: u16, method: &str, params: Value) -> Value {
+    let body = json!({ "jsonrpc": "2.0", "id": "test", "method": method, "params": params }).to_string();
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        stream,
+        "POST /jsonrpc HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    ).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(body).unwrap()
+}
+
+fn start_daemon(home: &std::path::Path, port: u16) -> Daemon {
+    let child = Command::new("./target/debug/ruget")
+        .env("HOME", home)
+        .args([
+            "daemon",
+            "--rpc-listen-port", &port.to_string(),
+            "--dir", home.to_str().unwrap(),
+        ])
+        .spawn()
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(Instant::now() < deadline, "daemon did not start");
+        sleep(Duration::from_millis(50));
+    }
+    Daemon(child)
+}
+
+#[test]
+fn test_daemon_downloads_and_persists_queue() {
+    let server = MockServer::start();
+    let data = "queued download";
+    server.mock(|when, then| {
+        when.method(HEAD).path("/file.txt");
+        then.status(200)
+            .header("Content-Length", data.len().to_string());
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/file.txt");
+        then.status(200).body(data);
+    });
+
+    let home = tempdir().unwrap();
+    let port = free_port();
+    let daemon = start_daemon(home.path(), port);
+
+    let added = rpc(port, "aria2.addUri", json!([[format!("{}/file.txt", server.base_url())]]));
+    let gid = added["result"].as_str().unwrap().to_string();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        let status = rpc(port, "aria2.tellStatus", json!([gid, ["status", "completedLength", "files"]]));
+        if status["result"]["status"] == "complete" {
+            break status;
+        }
+        assert!(Instant::now() < deadline, "download did not complete: {}", status);
+        sleep(Duration::from_millis(50));
+    };
+
+    assert_eq!(status["result"]["completedLength"], json!(data.len().to_string()));
+    let path = status["result"]["files"][0]["path"].as_str().unwrap().to_string();
+    assert_eq!(read_to_string(&path).unwrap(), data);
+
+    let stat = rpc(port, "aria2.getGlobalStat", json!([]));
+    assert_eq!(stat["result"]["numStopped"], json!("1"));
+    drop(daemon);
+
+    // The completed job is still known after a restart
+    let port = free_port();
+    let _daemon = start_daemon(home.path(), port);
+    let status = rpc(port, "aria2.tellStatus", json!([gid, ["status"]]));
+    assert_eq!(status["result"]["status"], json!("complete"));
+}
+
+#[test]
+fn test_daemon_rejects_paths_outside_dir() {
+    let home = tempdir().unwrap();
+    let port = free_port();
+    let _daemon = start_daemon(home.path(), port);
+
+    for (option, value) in [("out", "../x"), ("out", "/tmp/x"), ("dir", "../elsewhere")] {
+        let added = rpc(port, "aria2.addUri", json!([["http://127.0.0.1:1/file.txt"], { option: value }]));
+        assert!(added["error"]["message"].as_str().unwrap().contains("relative path"), "{}: {}", value, added);
+    }
+
+    let stat = rpc(port, "aria2.getGlobalStat", json!([]));
+    assert_eq!(stat["result"]["numWaiting"], json!("0"));
+    assert_eq!(stat["result"]["numActive"], json!("0"));
+}
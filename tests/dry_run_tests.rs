@@ -0,0 +1,103 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read_to_string, write};
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn test_dry_run_plans_resume_skip_and_create_without_writing() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD).path("/partial.bin");
+        then.status(200).header("content-length", "100").header("accept-ranges", "bytes");
+    });
+    server.mock(|when, then| {
+        when.method(HEAD).path("/complete.bin");
+        then.status(200).header("content-length", "10");
+    });
+    server.mock(|when, then| {
+        when.method(HEAD).path("/named");
+        then.status(200)
+            .header("content-length", "5")
+            .header("content-disposition", "attachment; filename=\"report.csv\"");
+    });
+    let get_mock = server.mock(|when, then| {
+        when.method(GET);
+        then.status(200).body("should not be fetched");
+    });
+
+    let dir = tempdir().unwrap();
+    write(dir.path().join("partial.bin"), [0u8; 40]).unwrap();
+    write(dir.path().join("complete.bin"), [0u8; 10]).unwrap();
+
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/partial.bin"),
+            &server.url("/complete.bin"),
+            &server.url("/named"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--resume",
+            "--jobs", "1",
+            "--dry-run",
+            "--report-format", "json",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    get_mock.assert_hits(0);
+    assert!(!dir.path().join("report.csv").exists(), "dry run must not create files");
+    assert_eq!(std::fs::metadata(dir.path().join("partial.bin")).unwrap().len(), 40);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let json: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json["jobs"], 3);
+    assert_eq!(json["transfer_bytes"], 65);
+
+    let plans = json["plans"].as_array().unwrap();
+    let plan_for = |suffix: &str| plans.iter().find(|p| p["url"].as_str().unwrap().ends_with(suffix)).unwrap();
+    assert_eq!(plan_for("/partial.bin")["action"], "resume");
+    assert_eq!(plan_for("/partial.bin")["resume_from"], 40);
+    assert_eq!(plan_for("/complete.bin")["action"], "skip");
+    assert_eq!(plan_for("/named")["action"], "create");
+    assert!(plan_for("/named")["output_path"].as_str().unwrap().ends_with("report.csv"));
+}
+
+#[test]
+fn test_dry_run_shows_chunked_strategy() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD).path("/big.iso");
+        then.status(200).header("content-length", "4194304").header("accept-ranges", "bytes");
+    });
+    server.mock(|when, then| {
+        when.method(HEAD).path("/no-ranges.iso");
+        then.status(200).header("content-length", "4194304");
+    });
+
+    let dir = tempdir().unwrap();
+    let report = dir.path().join("plan.txt");
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/big.iso"),
+            &server.url("/no-ranges.iso"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--jobs", "4",
+            "--split", "4",
+            "--dry-run",
+            "--report", report.to_str().unwrap(),
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    let text = read_to_string(&report).unwrap();
+    let line_for = |suffix: &str| text.lines().find(|l| l.ends_with(suffix)).unwrap().to_string();
+    let big: Vec<_> = line_for("/big.iso").split_whitespace().take(4).map(str::to_string).collect();
+    assert_eq!(big, ["create", "4194304", "yes", "4"]);
+    let single: Vec<_> = line_for("/no-ranges.iso").split_whitespace().take(4).map(str::to_string).collect();
+    assert_eq!(single, ["create", "4194304", "no", "1"]);
+    assert!(text.ends_with("2 jobs, 8388608 bytes to transfer\n"), "{}", text);
+}
@@ -0,0 +1,136 @@
+use flate2::{write::GzEncoder, Compression};
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read_to_string;
+use std::io::Write;
+use std::process::Command;
+use tempfile::tempdir;
+
+fn tar_gz(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data.as_bytes()).unwrap();
+    }
+    let tar = builder.into_inner().unwrap();
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&tar).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn zip_archive(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, data) in entries {
+        writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(data.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn mock_archive(server: &MockServer, path: &str, body: Vec<u8>) {
+    let len = body.len();
+    server.mock(|when, then| {
+        when.method(HEAD).path(path);
+        then.status(200)
+            .header("Content-Length", len.to_string());
+    });
+    server.mock(|when, then| {
+        when.method(GET).path(path);
+        then.status(200)
+            .header("Content-Length", len.to_string())
+            .body(body);
+    });
+}
+
+#[test]
+fn test_extract_tar_gz_while_streaming() {
+    let server = MockServer::start();
+    mock_archive(&server, "/pkg.tar.gz", tar_gz(&[
+        ("pkg-1.0/README", "readme"),
+        ("pkg-1.0/data/values.csv", "1,2,3"),
+    ]));
+
+    let dir = tempdir().unwrap();
+    let archive = dir.path().join("pkg.tar.gz");
+    let dest = dir.path().join("unpacked");
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &format!("{}/pkg.tar.gz", &server.base_url()),
+            "--output",
+            archive.to_str().unwrap(),
+            &format!("--extract={}", dest.to_str().unwrap()),
+            "--strip-components", "1",
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!(read_to_string(dest.join("README")).unwrap(), "readme");
+    assert_eq!(read_to_string(dest.join("data/values.csv")).unwrap(), "1,2,3");
+    assert!(!archive.exists(), "archive should not be written without --keep-archive");
+}
+
+#[test]
+fn test_extract_zip_keeps_archive() {
+    let server = MockServer::start();
+    mock_archive(&server, "/bundle.zip", zip_archive(&[("notes.txt", "zipped")]));
+
+    let dir = tempdir().unwrap();
+    let archive = dir.path().join("bundle.zip");
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &format!("{}/bundle.zip", &server.base_url()),
+            "--output",
+            archive.to_str().unwrap(),
+            "--extract",
+            "--keep-archive",
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!(read_to_string(dir.path().join("notes.txt")).unwrap(), "zipped");
+    assert!(archive.exists());
+}
+
+#[test]
+fn test_extract_rejects_path_traversal() {
+    let server = MockServer::start();
+
+    // tar::Builder refuses ".." paths, so write the header name directly
+    let mut header = tar::Header::new_gnu();
+    header.as_gnu_mut().unwrap().name[..13].copy_from_slice(b"../escape.txt");
+    header.set_size(4);
+    header.set_mode(0o644);
+    header.set_cksum();
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.append(&header, &b"evil"[..]).unwrap();
+    let tar = builder.into_inner().unwrap();
+    mock_archive(&server, "/evil.tar", tar);
+
+    let dir = tempdir().unwrap();
+    let dest = dir.path().join("out");
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &format!("{}/evil.tar", &server.base_url()),
+            "--output",
+            dir.path().join("evil.tar").to_str().unwrap(),
+            &format!("--extract={}", dest.to_str().unwrap()),
+            "--max-retries", "0",
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(!status.success());
+    assert!(!dir.path().join("escape.txt").exists());
+}
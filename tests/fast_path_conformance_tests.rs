@@ -0,0 +1,244 @@
+//! Every scenario runs once on the fast path and once on the full downloader,
+//! which `--verbose` forces, and both must end the same way.
+
+use httpmock::prelude::*;
+use httpmock::Method::GET;
+use std::fs::{read, read_to_string, write};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::thread;
+use tempfile::tempdir;
+
+const PATHS: [(&str, &[&str]); 2] = [("fast", &[]), ("full", &["--verbose"])];
+
+/// Run ruget with `home` as HOME, so no user config or .netrc leaks in
+fn ruget(home: &Path, args: &[&str], path: &[&str]) -> Output {
+    Command::new("./target/debug/ruget")
+        .args(args)
+        .args(path)
+        .env("HOME", home)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_saves_to_output_path() {
+    for (name, path) in PATHS {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/file.txt");
+            then.status(200)
+                .header("Content-Disposition", "attachment; filename=\"other.txt\"")
+                .body("hello");
+        });
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("saved.txt");
+        let output = ruget(dir.path(), &[&server.url("/file.txt"), "--output", out.to_str().unwrap()], path);
+
+        assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(read_to_string(&out).unwrap(), "hello", "{}", name);
+        assert!(!dir.path().join("other.txt").exists(), "{}", name);
+    }
+}
+
+#[test]
+fn test_prints_to_stdout() {
+    for (name, path) in PATHS {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/page");
+            then.status(200).body("body only");
+        });
+
+        let dir = tempdir().unwrap();
+        let output = ruget(dir.path(), &[&server.url("/page")], path);
+
+        assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+        // Log lines go to stderr so they never mix with the body
+        assert_eq!(output.stdout, b"body only", "{}", name);
+    }
+}
+
+#[test]
+fn test_client_error_fails_without_retrying() {
+    for (name, path) in PATHS {
+        let server = MockServer::start();
+        let get = server.mock(|when, then| {
+            when.method(GET).path("/missing");
+            then.status(404).body("not here");
+        });
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("missing.txt");
+        write(&out, "earlier download").unwrap();
+        let log = dir.path().join("failures.log");
+        let output = ruget(
+            dir.path(),
+            &[&server.url("/missing"), "--output", out.to_str().unwrap(), "--log", log.to_str().unwrap()],
+            path,
+        );
+
+        assert!(!output.status.success(), "{}", name);
+        get.assert_hits(1);
+        assert!(read_to_string(&log).unwrap().contains("E202"), "{}", name);
+        // The error page is not saved, and the file already there is left untouched
+        assert_eq!(read(&out).unwrap(), b"earlier download", "{}", name);
+    }
+}
+
+#[test]
+fn test_server_error_is_retried_the_same_number_of_times() {
+    let mut hits = vec![];
+    for (name, path) in PATHS {
+        let server = MockServer::start();
+        let get = server.mock(|when, then| {
+            when.method(GET).path("/busy");
+            then.status(503);
+        });
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("busy.txt");
+        let log = dir.path().join("failures.log");
+        let output = ruget(
+            dir.path(),
+            &[
+                &server.url("/busy"),
+                "--output", out.to_str().unwrap(),
+                "--log", log.to_str().unwrap(),
+                "--max-retries", "2",
+                "--backoff-base-ms", "10",
+            ],
+            path,
+        );
+
+        assert!(!output.status.success(), "{}", name);
+        assert!(read_to_string(&log).unwrap().contains("503"), "{}", name);
+        hits.push(get.hits());
+    }
+    assert!(hits[0] > 1);
+    assert_eq!(hits[0], hits[1]);
+}
+
+#[test]
+fn test_follows_redirects() {
+    for (name, path) in PATHS {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/old");
+            then.status(302).header("Location", server.url("/new"));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/new");
+            then.status(200).body("moved");
+        });
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("moved.txt");
+        let output = ruget(dir.path(), &[&server.url("/old"), "--output", out.to_str().unwrap()], path);
+
+        assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(read_to_string(&out).unwrap(), "moved", "{}", name);
+    }
+}
+
+#[test]
+fn test_overwrites_existing_file() {
+    for (name, path) in PATHS {
+        let server = MockServer::start();
+        let get = server.mock(|when, then| {
+            when.method(GET).path("/file.txt");
+            then.status(200).body("new");
+        });
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("file.txt");
+        write(&out, "an older and longer file").unwrap();
+        let output = ruget(dir.path(), &[&server.url("/file.txt"), "--output", out.to_str().unwrap()], path);
+
+        assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+        get.assert_hits(1);
+        assert_eq!(read_to_string(&out).unwrap(), "new", "{}", name);
+    }
+}
+
+#[test]
+fn test_sends_netrc_credentials() {
+    for (name, path) in PATHS {
+        let server = MockServer::start();
+        let get = server.mock(|when, then| {
+            // user:secret
+            when.method(GET).path("/private").header("Authorization", "Basic dXNlcjpzZWNyZXQ=");
+            then.status(200).body("secret");
+        });
+
+        let dir = tempdir().unwrap();
+        write(dir.path().join(".netrc"), "machine 127.0.0.1 login user password secret\n").unwrap();
+        let out = dir.path().join("private.txt");
+        let output = ruget(dir.path(), &[&server.url("/private"), "--output", out.to_str().unwrap()], path);
+
+        assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+        get.assert_hits(1);
+        assert_eq!(read_to_string(&out).unwrap(), "secret", "{}", name);
+    }
+}
+
+/// A server for `body` whose first GET stops after `cut` bytes; ranged GETs are answered in full
+fn dropping_server(body: &'static [u8], cut: usize) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/file.bin", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut start = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                    start = value.trim().trim_end_matches('-').parse::<usize>().ok();
+                }
+            }
+
+            let response = match (request_line.starts_with("HEAD"), start) {
+                (true, _) => format!("HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).into_bytes(),
+                (false, Some(start)) => [
+                    format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                        body.len() - start, start, body.len() - 1, body.len(),
+                    ).as_bytes(),
+                    &body[start..],
+                ].concat(),
+                (false, None) => [
+                    format!("HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).as_bytes(),
+                    &body[..cut],
+                ].concat(),
+            };
+            let _ = stream.write_all(&response);
+        }
+    });
+    url
+}
+
+#[test]
+fn test_dropped_connection_continues() {
+    static BODY: [u8; 50_000] = [b'c'; 50_000];
+    for (name, path) in PATHS {
+        let url = dropping_server(&BODY, 20_000);
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("file.bin");
+        let output = ruget(dir.path(), &[&url, "--output", out.to_str().unwrap(), "--backoff-base-ms", "10"], path);
+
+        assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(read(&out).unwrap(), BODY, "{}", name);
+    }
+}
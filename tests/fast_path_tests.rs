@@ -0,0 +1,78 @@
+use httpmock::prelude::*;
+use httpmock::Method::GET;
+use std::fs::{read, read_to_string};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+use tempfile::tempdir;
+
+/// Every byte value, repeated: bytes that are not valid UTF-8 must survive untouched
+fn binary_body(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 256) as u8).collect()
+}
+
+#[test]
+fn test_fast_path_streams_binary_file() {
+    let body = binary_body(3 * 1024 * 1024);
+    let server = MockServer::start();
+    let get = server.mock(|when, then| {
+        when.method(GET).path("/blob.bin");
+        then.status(200).body(&body);
+    });
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("blob.bin");
+    let output = Command::new("./target/debug/ruget")
+        .args([&server.url("/blob.bin"), "--output", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    get.assert_hits(1);
+    assert_eq!(read(&path).unwrap(), body);
+}
+
+#[test]
+fn test_fast_path_streams_to_stdout() {
+    let body = binary_body(1024 * 1024);
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/blob.bin");
+        then.status(200).body(&body);
+    });
+
+    let output = Command::new("./target/debug/ruget")
+        .arg(server.url("/blob.bin"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, body);
+}
+
+#[test]
+fn test_fast_path_reports_short_body() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/file.bin", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let _ = stream.read(&mut [0u8; 4096]);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n");
+            let _ = stream.write_all(&[b's'; 400]);
+        }
+    });
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("file.bin");
+    let log = dir.path().join("failures.log");
+    let output = Command::new("./target/debug/ruget")
+        .args([&url, "--output", path.to_str().unwrap(), "--log", log.to_str().unwrap(), "--retry-on", "5xx"])
+        .output()
+        .unwrap();
+
+    // A body that ends early is a failed download, not a complete one
+    assert!(!output.status.success());
+    assert!(read_to_string(&log).unwrap().contains("E400"));
+}
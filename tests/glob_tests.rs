@@ -0,0 +1,75 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read_to_string;
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn test_glob_expands_urls_and_output_names() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    let mut mocks = Vec::new();
+    for year in ["2020", "2021"] {
+        for day in ["001", "002"] {
+            let path = format!("/data/{}/day{}.nc", year, day);
+            let body = format!("{}-{}", year, day);
+            mocks.push(server.mock(|when, then| {
+                when.method(GET).path(path);
+                then.status(200).body(body);
+            }));
+        }
+    }
+
+    let dir = tempdir().unwrap();
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &format!("{}/data/{{2020,2021}}/day[001-002].nc", server.base_url()),
+            "--output",
+            dir.path().join("#1_#2.nc").to_str().unwrap(),
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    for mock in &mocks {
+        mock.assert_hits(1);
+    }
+    assert_eq!(read_to_string(dir.path().join("2020_001.nc")).unwrap(), "2020-001");
+    assert_eq!(read_to_string(dir.path().join("2021_002.nc")).unwrap(), "2021-002");
+}
+
+#[test]
+fn test_globoff_keeps_brackets() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    let literal = server.mock(|when, then| {
+        when.method(GET).path("/file[1].txt");
+        then.status(200).body("literal");
+    });
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("out.txt");
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &format!("{}/file[1].txt", server.base_url()),
+            "--globoff",
+            "--output",
+            path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    literal.assert_hits(1);
+    assert_eq!(read_to_string(&path).unwrap(), "literal");
+}
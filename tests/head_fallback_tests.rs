@@ -0,0 +1,109 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read, read_to_string};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+use tempfile::tempdir;
+
+#[test]
+fn test_ranged_get_probe_when_head_is_refused() {
+    let body: Vec<u8> = (0..2 * 1024 * 1024).map(|i| (i % 253) as u8).collect();
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(405);
+    });
+    let probe = server.mock(|when, then| {
+        when.method(GET).path("/signed.bin").header("Range", "bytes=0-0");
+        then.status(206)
+            .header("Content-Range", format!("bytes 0-0/{}", body.len()))
+            .body(&body[..1]);
+    });
+    let half = body.len() / 2;
+    let chunks: Vec<_> = [(0, half - 1), (half, body.len() - 1)]
+        .into_iter()
+        .map(|(start, end)| {
+            let part = body[start..=end].to_vec();
+            let total = body.len();
+            server.mock(move |when, then| {
+                when.method(GET).path("/signed.bin").header("Range", format!("bytes={}-{}", start, end));
+                then.status(206).header("Content-Range", format!("bytes {}-{}/{}", start, end, total)).body(part);
+            })
+        })
+        .collect();
+
+    let dir = tempdir().unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/signed.bin"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--split", "2",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    probe.assert_hits(1);
+    for chunk in &chunks {
+        chunk.assert_hits(1);
+    }
+    assert_eq!(read(dir.path().join("signed.bin")).unwrap(), body);
+}
+
+/// A server that answers HEAD with 403 and GET with a body of unknown length,
+/// ending it by closing the connection
+fn unknown_length_server(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            let _ = reader.read_line(&mut request_line);
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                line.clear();
+            }
+
+            let response = if request_line.starts_with("HEAD") {
+                "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+            } else {
+                format!("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{}", body)
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    format!("http://{}/stream.txt", addr)
+}
+
+#[test]
+fn test_unknown_length_streams_single_threaded() {
+    let url = unknown_length_server("no length here");
+    let dir = tempdir().unwrap();
+    let report = dir.path().join("plan.txt");
+
+    let plan = Command::new("./target/debug/ruget")
+        .args([
+            &url,
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--split", "4",
+            "--dry-run",
+            "--report-format", "json",
+            "--report", report.to_str().unwrap(),
+        ])
+        .status()
+        .unwrap();
+    assert!(plan.success());
+    assert!(read_to_string(&report).unwrap().contains("server did not report the file size"));
+
+    let output = Command::new("./target/debug/ruget")
+        .args([&url, "--output-dir", dir.path().to_str().unwrap(), "--split", "4"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(read_to_string(dir.path().join("stream.txt")).unwrap(), "no length here");
+}
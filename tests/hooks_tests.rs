@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read_to_string;
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn test_exec_and_batch_hooks() {
+    let server = MockServer::start();
+    let data = "hook data";
+
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200)
+            .header("Content-Length", data.len().to_string());
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/ok.txt");
+        then.status(200).body(data);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/missing.txt");
+        then.status(404);
+    });
+
+    let dir = tempdir().unwrap();
+    let exec_log = dir.path().join("exec.log");
+    let batch_log = dir.path().join("batch.log");
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &format!("{}/ok.txt", &server.base_url()),
+            &format!("{}/missing.txt", &server.base_url()),
+            "--output-dir",
+            dir.path().to_str().unwrap(),
+            "--max-retries", "0",
+            "--backoff-base-ms", "1",
+            "--log",
+            dir.path().join("failures.log").to_str().unwrap(),
+            "--exec",
+            &format!("echo {{path}} $RUGET_SIZE $RUGET_STATUS >> {}", exec_log.display()),
+            "--on-complete",
+            &format!("echo complete $RUGET_SUCCEEDED/$RUGET_TOTAL >> {}", batch_log.display()),
+            "--on-failure",
+            &format!("echo failed {{failed}} >> {}", batch_log.display()),
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    // One of two downloads succeeded, so the batch as a whole succeeds
+    assert!(status.success());
+
+    let exec_output = read_to_string(&exec_log).unwrap();
+    let expected_path = dir.path().join("ok.txt");
+    assert_eq!(exec_output.trim(), format!("{} {} success", expected_path.display(), data.len()));
+
+    let batch_output = read_to_string(&batch_log).unwrap();
+    assert_eq!(batch_output, "failed 1\ncomplete 1/2\n");
+}
@@ -0,0 +1,119 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read_to_string;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+/// Serve `first` to the first GET and `200 OK` with `body` afterwards; HEAD always succeeds
+fn scripted_server(first: &'static str, body: &'static str) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let gets = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&gets);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            let _ = reader.read_line(&mut request_line);
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                line.clear();
+            }
+
+            let response = if request_line.starts_with("HEAD") {
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len())
+            } else if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                format!("{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", first)
+            } else {
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (format!("http://{}/file.txt", addr), gets)
+}
+
+#[test]
+fn test_retry_after_seconds_is_honoured() {
+    let (url, gets) = scripted_server("HTTP/1.1 503 Service Unavailable\r\nRetry-After: 1", "ready");
+    let dir = tempdir().unwrap();
+
+    let start = Instant::now();
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &url,
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--backoff-base-ms", "1",
+            "--max-retries", "2",
+            "--verbose",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(start.elapsed() >= Duration::from_secs(1), "Retry-After was not honoured");
+    assert_eq!(gets.load(Ordering::SeqCst), 2);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("delay_ms=1000"));
+    assert_eq!(read_to_string(dir.path().join("file.txt")).unwrap(), "ready");
+}
+
+#[test]
+fn test_retry_after_is_capped_by_backoff_max() {
+    let (url, gets) = scripted_server("HTTP/1.1 429 Too Many Requests\r\nRetry-After: 120", "ready");
+    let dir = tempdir().unwrap();
+
+    let start = Instant::now();
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &url,
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--backoff-max-ms", "200",
+            "--max-retries", "2",
+            "--verbose",
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(gets.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_client_errors_fail_fast() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    let missing = server.mock(|when, then| {
+        when.method(GET).path("/missing.txt");
+        then.status(404);
+    });
+
+    let dir = tempdir().unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/missing.txt"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--log", dir.path().join("failures.log").to_str().unwrap(),
+            "--max-retries", "3",
+            "--verbose",
+            "--backoff-base-ms", "1",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    missing.assert_hits(1);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[E202]"));
+    assert!(read_to_string(dir.path().join("failures.log")).unwrap().contains("HTTP 404"));
+}
@@ -0,0 +1,198 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read_to_string, write};
+use std::process::Command;
+use tempfile::tempdir;
+
+fn sha256_hex(data: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_text_manifest_per_entry_options() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    let auth_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/private.txt")
+            .header("authorization", "Bearer abc");
+        then.status(200).body("private data");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/broken.txt");
+        then.status(500);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/mirror.txt");
+        then.status(200).body("mirrored data");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/checked.txt");
+        then.status(200).body("checked data");
+    });
+
+    let dir = tempdir().unwrap();
+    let manifest = dir.path().join("jobs.txt");
+    let base = server.base_url();
+    write(&manifest, format!(
+        "{base}/private.txt\n  out=renamed.txt\n  dir={sub}\n  header=Authorization: Bearer abc\n\
+         {base}/broken.txt\n  mirror={base}/mirror.txt\n  out=fallback.txt\n\
+         {base}/checked.txt\n  checksum=sha-256={sum}\n",
+        sub = dir.path().join("sub").display(),
+        sum = sha256_hex("checked data"),
+    )).unwrap();
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            "--input",
+            manifest.to_str().unwrap(),
+            "--output-dir",
+            dir.path().to_str().unwrap(),
+            "--max-retries", "0",
+            "--backoff-base-ms", "1",
+            "--jobs", "1",
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    auth_mock.assert_hits(1);
+    assert_eq!(read_to_string(dir.path().join("sub/renamed.txt")).unwrap(), "private data");
+    assert_eq!(read_to_string(dir.path().join("fallback.txt")).unwrap(), "mirrored data");
+    assert_eq!(read_to_string(dir.path().join("checked.txt")).unwrap(), "checked data");
+}
+
+#[test]
+fn test_jsonl_manifest_checksum_mismatch_fails() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/checked.txt");
+        then.status(200).body("tampered data");
+    });
+
+    let dir = tempdir().unwrap();
+    let manifest = dir.path().join("jobs.jsonl");
+    write(&manifest, format!(
+        "{{\"url\": \"{}/checked.txt\", \"out\": \"checked.txt\", \"checksum\": \"sha-256={}\"}}\n",
+        server.base_url(),
+        sha256_hex("checked data"),
+    )).unwrap();
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            "--input",
+            manifest.to_str().unwrap(),
+            "--output-dir",
+            dir.path().to_str().unwrap(),
+            "--log",
+            dir.path().join("failures.log").to_str().unwrap(),
+            "--max-retries", "0",
+            "--backoff-base-ms", "1",
+            "--jobs", "1",
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(!status.success());
+    assert!(!dir.path().join("checked.txt").exists(), "corrupt file should be removed");
+    assert!(read_to_string(dir.path().join("failures.log")).unwrap().contains("Checksum mismatch"));
+}
+
+#[test]
+fn test_csv_manifest() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/a.txt");
+        then.status(200).body("csv data");
+    });
+
+    let dir = tempdir().unwrap();
+    let manifest = dir.path().join("jobs.csv");
+    write(&manifest, format!("url,out\n{}/a.txt,from-csv.txt\n", server.base_url())).unwrap();
+
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            "--input",
+            manifest.to_str().unwrap(),
+            "--output-dir",
+            dir.path().to_str().unwrap(),
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!(read_to_string(dir.path().join("from-csv.txt")).unwrap(), "csv data");
+}
+
+#[test]
+fn test_stdin_input_streams_jobs_before_eof() {
+    use std::io::Write;
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/first.txt");
+        then.status(200).body("first");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/second.txt");
+        then.status(200).body("second");
+    });
+
+    let dir = tempdir().unwrap();
+    let mut child = Command::new("./target/debug/ruget")
+        .args([
+            "--input", "-",
+            "--output-dir",
+            dir.path().to_str().unwrap(),
+            "--jobs", "2",
+            "--quiet",
+        ])
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    // The next URL line completes the first entry
+    writeln!(stdin, "{}/first.txt\n{}/second.txt", server.base_url(), server.base_url()).unwrap();
+    stdin.flush().unwrap();
+
+    // The first download completes while stdin is still open
+    let first = dir.path().join("first.txt");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while read_to_string(&first).map(|s| s != "first").unwrap_or(true) {
+        assert!(Instant::now() < deadline, "first job did not start before EOF");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    // An option line that arrives late still applies to the entry before it
+    writeln!(stdin, "  out=renamed.txt").unwrap();
+    drop(stdin);
+
+    assert!(child.wait().unwrap().success());
+    assert_eq!(read_to_string(dir.path().join("renamed.txt")).unwrap(), "second");
+}
@@ -0,0 +1,124 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read, write};
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn test_one_head_per_url_for_split_download() {
+    let body = vec![b'x'; 2 * 1024 * 1024];
+    let server = MockServer::start();
+    let head = server.mock(|when, then| {
+        when.method(HEAD).path("/download");
+        then.status(200)
+            .header("Content-Length", body.len().to_string())
+            .header("Accept-Ranges", "bytes")
+            .header("Content-Disposition", "attachment; filename=\"named.bin\"");
+    });
+    let half = body.len() / 2;
+    for (start, end) in [(0, half - 1), (half, body.len() - 1)] {
+        let part = body[start..=end].to_vec();
+        let total = body.len();
+        server.mock(move |when, then| {
+            when.method(GET).path("/download").header("Range", format!("bytes={}-{}", start, end));
+            then.status(206).header("Content-Range", format!("bytes {}-{}/{}", start, end, total)).body(part);
+        });
+    }
+    let other_head = server.mock(|when, then| {
+        when.method(HEAD).path("/small.txt");
+        then.status(200).header("Content-Length", "5");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/small.txt");
+        then.status(200).body("small");
+    });
+
+    let dir = tempdir().unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/download"),
+            &server.url("/small.txt"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--jobs", "2",
+            "--split", "2",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // Sizing the progress bar, naming the file and planning its chunks share one probe
+    head.assert_hits(1);
+    other_head.assert_hits(1);
+    assert_eq!(read(dir.path().join("named.bin")).unwrap(), body);
+    assert_eq!(read(dir.path().join("small.txt")).unwrap(), b"small");
+}
+
+#[test]
+fn test_resume_reuses_probe() {
+    let server = MockServer::start();
+    let head = server.mock(|when, then| {
+        when.method(HEAD).path("/file.txt");
+        then.status(200).header("Content-Length", "10");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/file.txt").header("Range", "bytes=4-");
+        then.status(206).header("Content-Range", "bytes 4-9/10").body("efghij");
+    });
+
+    let dir = tempdir().unwrap();
+    let output_path = dir.path().join("file.txt");
+    write(&output_path, "abcd").unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/file.txt"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--resume",
+            "--jobs", "1",
+            "--verbose",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    head.assert_hits(1);
+    assert_eq!(read(&output_path).unwrap(), b"abcdefghij");
+}
+
+#[test]
+fn test_entries_with_different_headers_probe_separately() {
+    let server = MockServer::start();
+    let mut heads = vec![];
+    for (user, name) in [("alice", "alice.bin"), ("bob", "bob.bin")] {
+        heads.push(server.mock(move |when, then| {
+            when.method(HEAD).path("/report").header("X-User", user);
+            then.status(200)
+                .header("Content-Length", "5")
+                .header("Content-Disposition", format!("attachment; filename=\"{}\"", name));
+        }));
+        server.mock(move |when, then| {
+            when.method(GET).path("/report").header("X-User", user);
+            then.status(200).body(user);
+        });
+    }
+
+    let dir = tempdir().unwrap();
+    let manifest = dir.path().join("jobs.txt");
+    let url = server.url("/report");
+    write(&manifest, format!("{url}\n  header=X-User: alice\n{url}\n  header=X-User: bob\n")).unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            "--input", manifest.to_str().unwrap(),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--jobs", "1",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // Each entry is named from the answer to its own headers
+    for head in &heads {
+        head.assert_hits(1);
+    }
+    assert_eq!(read(dir.path().join("alice.bin")).unwrap(), b"alice");
+    assert_eq!(read(dir.path().join("bob.bin")).unwrap(), b"bob");
+}
@@ -0,0 +1,118 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read, read_to_string, write};
+use std::process::Command;
+use tempfile::tempdir;
+
+fn serve_head(server: &MockServer, path: &str, length: usize) {
+    server.mock(|when, then| {
+        when.method(HEAD).path(path.to_string());
+        then.status(200)
+            .header("Content-Length", length.to_string())
+            .header("Accept-Ranges", "bytes");
+    });
+}
+
+#[test]
+fn test_chunked_download_restarts_when_ranges_are_ignored() {
+    let body = vec![b'r'; 2 * 1024 * 1024];
+    let server = MockServer::start();
+    serve_head(&server, "/big.bin", body.len());
+    let get = server.mock(|when, then| {
+        when.method(GET).path("/big.bin");
+        then.status(200).body(&body);
+    });
+
+    let dir = tempdir().unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([&server.url("/big.bin"), "--output-dir", dir.path().to_str().unwrap(), "--split", "2", "--verbose"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("ignored the byte range"));
+    // Two ranged requests, then one plain GET for the restart
+    get.assert_hits(3);
+    assert_eq!(read(dir.path().join("big.bin")).unwrap().len(), body.len());
+}
+
+#[test]
+fn test_chunk_with_wrong_content_range_is_corruption() {
+    let body = vec![b'w'; 2 * 1024 * 1024];
+    let server = MockServer::start();
+    serve_head(&server, "/big.bin", body.len());
+    server.mock(|when, then| {
+        when.method(GET).path("/big.bin");
+        then.status(206)
+            .header("Content-Range", format!("bytes 0-{}/{}", body.len() - 1, body.len()))
+            .body(&body);
+    });
+
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("failures.log");
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/big.bin"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--log", log.to_str().unwrap(),
+            "--split", "2",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(read_to_string(&log).unwrap().contains("E504"));
+    assert!(!dir.path().join("big.bin").exists());
+}
+
+#[test]
+fn test_resume_restarts_when_range_is_ignored() {
+    let server = MockServer::start();
+    serve_head(&server, "/file.txt", 10);
+    let get = server.mock(|when, then| {
+        when.method(GET).path("/file.txt");
+        then.status(200).body("abcdefghij");
+    });
+
+    let dir = tempdir().unwrap();
+    let output_path = dir.path().join("file.txt");
+    write(&output_path, "abcd").unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([&server.url("/file.txt"), "--output-dir", dir.path().to_str().unwrap(), "--resume", "--verbose"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    get.assert_hits(1);
+    assert_eq!(read_to_string(&output_path).unwrap(), "abcdefghij");
+}
+
+#[test]
+fn test_resume_with_wrong_content_range_is_corruption() {
+    let server = MockServer::start();
+    serve_head(&server, "/file.txt", 10);
+    server.mock(|when, then| {
+        when.method(GET).path("/file.txt").header("Range", "bytes=4-");
+        then.status(206).header("Content-Range", "bytes 0-9/10").body("abcdefghij");
+    });
+
+    let dir = tempdir().unwrap();
+    let output_path = dir.path().join("file.txt");
+    let log = dir.path().join("failures.log");
+    write(&output_path, "abcd").unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/file.txt"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--log", log.to_str().unwrap(),
+            "--resume",
+            "--verbose",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(read_to_string(&log).unwrap().contains("E504"));
+    // The partial file is left as it was
+    assert_eq!(read_to_string(&output_path).unwrap(), "abcd");
+}
@@ -0,0 +1,150 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read_to_string, write};
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::tempdir;
+
+fn without_range(req: &HttpMockRequest) -> bool {
+    !req.headers.iter().flatten().any(|(name, _)| name.eq_ignore_ascii_case("range"))
+}
+
+/// Leave a partial file behind as if an earlier attempt saw `etag`
+fn partial_download(dir: &Path, content: &str, etag: &str) -> std::path::PathBuf {
+    let output_path = dir.join("file.txt");
+    write(&output_path, content).unwrap();
+    write(
+        dir.join("file.txt.ruget-resume"),
+        format!(r#"{{"url":"","etag":"{}","last_modified":null}}"#, etag.replace('"', "\\\"")),
+    )
+    .unwrap();
+    output_path
+}
+
+fn resume(server: &MockServer, dir: &Path) -> Output {
+    Command::new("./target/debug/ruget")
+        .args([&server.url("/file.txt"), "--output-dir", dir.to_str().unwrap(), "--resume", "--verbose"])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_resume_sends_if_range_with_saved_etag() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD).path("/file.txt");
+        then.status(200).header("Content-Length", "10").header("ETag", "\"v1\"");
+    });
+    let ranged = server.mock(|when, then| {
+        when.method(GET).path("/file.txt").header("Range", "bytes=4-").header("If-Range", "\"v1\"");
+        then.status(206).header("Content-Range", "bytes 4-9/10").body("efghij");
+    });
+
+    let dir = tempdir().unwrap();
+    let output_path = partial_download(dir.path(), "abcd", "\"v1\"");
+    let output = resume(&server, dir.path());
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    ranged.assert_hits(1);
+    assert_eq!(read_to_string(&output_path).unwrap(), "abcdefghij");
+    assert!(!dir.path().join("file.txt.ruget-resume").exists());
+}
+
+#[test]
+fn test_resume_restarts_when_etag_changed() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD).path("/file.txt");
+        then.status(200).header("Content-Length", "10").header("ETag", "\"v2\"");
+    });
+    let get = server.mock(|when, then| {
+        when.method(GET).path("/file.txt").matches(without_range);
+        then.status(200).header("ETag", "\"v2\"").body("0123456789");
+    });
+
+    let dir = tempdir().unwrap();
+    let output_path = partial_download(dir.path(), "abcd", "\"v1\"");
+    let output = resume(&server, dir.path());
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("remote file changed"));
+    get.assert_hits(1);
+    assert_eq!(read_to_string(&output_path).unwrap(), "0123456789");
+}
+
+#[test]
+fn test_resume_restarts_when_if_range_does_not_match() {
+    let server = MockServer::start();
+    // The probe cannot tell the file changed, so the server's If-Range check does
+    server.mock(|when, then| {
+        when.method(HEAD).path("/file.txt");
+        then.status(200).header("Content-Length", "10");
+    });
+    let get = server.mock(|when, then| {
+        when.method(GET).path("/file.txt").header("If-Range", "\"v1\"");
+        then.status(200).header("ETag", "\"v2\"").body("0123456789");
+    });
+
+    let dir = tempdir().unwrap();
+    let output_path = partial_download(dir.path(), "abcd", "\"v1\"");
+    let output = resume(&server, dir.path());
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("If-Range did not match"));
+    get.assert_hits(1);
+    assert_eq!(read_to_string(&output_path).unwrap(), "0123456789");
+}
+
+/// A server that refuses HEAD and the ranged probe, so the size is only
+/// learned from the 416 answering the resume request
+fn serve_without_size(server: &MockServer) {
+    server.mock(|when, then| {
+        when.method(HEAD).path("/file.txt");
+        then.status(405);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/file.txt").header("Range", "bytes=0-0");
+        then.status(403);
+    });
+}
+
+#[test]
+fn test_range_not_satisfiable_with_matching_size_is_complete() {
+    let server = MockServer::start();
+    serve_without_size(&server);
+    let ranged = server.mock(|when, then| {
+        when.method(GET).path("/file.txt").header("Range", "bytes=10-");
+        then.status(416).header("Content-Range", "bytes */10");
+    });
+
+    let dir = tempdir().unwrap();
+    let output_path = partial_download(dir.path(), "abcdefghij", "\"v1\"");
+    let output = resume(&server, dir.path());
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    ranged.assert_hits(1);
+    assert_eq!(read_to_string(&output_path).unwrap(), "abcdefghij");
+    assert!(!dir.path().join("file.txt.ruget-resume").exists());
+}
+
+#[test]
+fn test_range_not_satisfiable_with_other_size_restarts() {
+    let server = MockServer::start();
+    serve_without_size(&server);
+    server.mock(|when, then| {
+        when.method(GET).path("/file.txt").header("Range", "bytes=12-");
+        then.status(416).header("Content-Range", "bytes */10");
+    });
+    let get = server.mock(|when, then| {
+        when.method(GET).path("/file.txt").matches(without_range);
+        then.status(200).body("0123456789");
+    });
+
+    let dir = tempdir().unwrap();
+    let output_path = partial_download(dir.path(), "abcdefghijkl", "\"v1\"");
+    let output = resume(&server, dir.path());
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    get.assert_hits(1);
+    assert_eq!(read_to_string(&output_path).unwrap(), "0123456789");
+}
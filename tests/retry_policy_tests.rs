@@ -0,0 +1,83 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read_to_string;
+use std::process::Command;
+use tempfile::tempdir;
+
+fn run_ruget(args: &[&str]) -> bool {
+    Command::new("./target/debug/ruget").args(args).output().unwrap().status.success()
+}
+
+#[test]
+fn test_retry_on_replaces_default_conditions() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    let missing = server.mock(|when, then| {
+        when.method(GET).path("/missing");
+        then.status(404);
+    });
+    let unavailable = server.mock(|when, then| {
+        when.method(GET).path("/unavailable");
+        then.status(503);
+    });
+
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("failures.log");
+    let success = run_ruget(&[
+        &server.url("/missing"),
+        &server.url("/unavailable"),
+        "--output-dir", dir.path().to_str().unwrap(),
+        "--log", log.to_str().unwrap(),
+        "--retry-on", "404,timeout",
+        "--max-retries", "2",
+        "--backoff-base-ms", "1",
+        "--backoff-strategy", "full-jitter",
+    ]);
+
+    assert!(!success);
+    // 404 is retried until --max-retries runs out; 503 only gets the batch-level second pass
+    missing.assert_hits(3);
+    unavailable.assert_hits(2);
+    assert!(read_to_string(&log).unwrap().contains("after 2 retries"));
+}
+
+#[test]
+fn test_retry_budget_limits_whole_run() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD);
+        then.status(200);
+    });
+    let failing = server.mock(|when, then| {
+        when.method(GET);
+        then.status(503);
+    });
+
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("failures.log");
+    let success = run_ruget(&[
+        &server.url("/a"),
+        &server.url("/b"),
+        &server.url("/c"),
+        "--output-dir", dir.path().to_str().unwrap(),
+        "--log", log.to_str().unwrap(),
+        "--max-retries", "10",
+        "--retry-budget", "2",
+        "--backoff-base-ms", "1",
+        "--jobs", "1",
+    ]);
+
+    assert!(!success);
+    // One attempt per URL plus the two retries the budget allows
+    failing.assert_hits(5);
+    assert!(read_to_string(&log).unwrap().contains("retry budget of 2 was used up"));
+}
+
+#[test]
+fn test_invalid_retry_on_rejected() {
+    let success = run_ruget(&["http://127.0.0.1:1/x", "--retry-on", "sometimes"]);
+    assert!(!success);
+}
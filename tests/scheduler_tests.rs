@@ -0,0 +1,89 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read, read_to_string};
+use std::process::Command;
+use tempfile::tempdir;
+
+const MIB: usize = 1024 * 1024;
+
+/// Serve `body` at `path` as four ranges of equal size
+fn serve_in_ranges<'a>(server: &'a MockServer, path: &str, body: &[u8]) -> Vec<httpmock::Mock<'a>> {
+    server.mock(|when, then| {
+        when.method(HEAD).path(path.to_string());
+        then.status(200)
+            .header("Content-Length", body.len().to_string())
+            .header("Accept-Ranges", "bytes");
+    });
+    let size = body.len() / 4;
+    (0..4)
+        .map(|i| {
+            let (start, end) = (i * size, (i + 1) * size - 1);
+            let part = body[start..=end].to_vec();
+            let total = body.len();
+            server.mock(move |when, then| {
+                when.method(GET).path(path.to_string()).header("Range", format!("bytes={}-{}", start, end));
+                then.status(206).header("Content-Range", format!("bytes {}-{}/{}", start, end, total)).body(part);
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_split_files_in_parallel_under_connection_cap() {
+    let server = MockServer::start();
+    let bodies: Vec<Vec<u8>> = (0..3u8).map(|n| vec![b'a' + n; 2 * MIB]).collect();
+    let ranges: Vec<_> = bodies
+        .iter()
+        .enumerate()
+        .flat_map(|(i, body)| serve_in_ranges(&server, &format!("/file{}.bin", i), body))
+        .collect();
+
+    let dir = tempdir().unwrap();
+    let mut args: Vec<String> = (0..3).map(|i| server.url(format!("/file{}.bin", i))).collect();
+    args.extend([
+        "--output-dir", dir.path().to_str().unwrap(),
+        "--jobs", "3",
+        "--split", "4",
+        "--max-connections", "2",
+        "--verbose",
+    ].map(String::from));
+
+    let output = Command::new("./target/debug/ruget").args(&args).output().unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    for range in &ranges {
+        range.assert_hits(1);
+    }
+    for (i, body) in bodies.iter().enumerate() {
+        assert_eq!(&read(dir.path().join(format!("file{}.bin", i))).unwrap(), body);
+    }
+}
+
+#[test]
+fn test_split_is_independent_of_jobs() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(HEAD).path("/big.iso");
+        then.status(200).header("content-length", "4194304").header("accept-ranges", "bytes");
+    });
+
+    let dir = tempdir().unwrap();
+    let report = dir.path().join("plan.txt");
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            &server.url("/big.iso"),
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--jobs", "1",
+            "--split", "3",
+            "--dry-run",
+            "--report", report.to_str().unwrap(),
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    let text = read_to_string(&report).unwrap();
+    let line = text.lines().find(|l| l.ends_with("/big.iso")).unwrap();
+    let columns: Vec<_> = line.split_whitespace().take(4).collect();
+    assert_eq!(columns, ["create", "4194304", "yes", "3"]);
+}
@@ -0,0 +1,126 @@
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::read_to_string;
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn test_spider_falls_back_to_ranged_get() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD).path("/no-head.bin");
+        then.status(405);
+    });
+    let ranged = server.mock(|when, then| {
+        when.method(GET).path("/no-head.bin").header("range", "bytes=0-0");
+        then.status(206)
+            .header("content-range", "bytes 0-0/1234")
+            .header("content-type", "application/octet-stream")
+            .body("x");
+    });
+
+    let dir = tempdir().unwrap();
+    let report = dir.path().join("report.json");
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            "--spider",
+            &server.url("/no-head.bin"),
+            "--report-format", "json",
+            "--report", report.to_str().unwrap(),
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    ranged.assert_hits(1);
+
+    let json: serde_json::Value = serde_json::from_str(&read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(json["checked"], 1);
+    assert_eq!(json["broken"], 0);
+    let result = &json["results"][0];
+    assert_eq!(result["method"], "GET");
+    assert_eq!(result["status"], 206);
+    assert_eq!(result["size"], 1234);
+    assert_eq!(result["content_type"], "application/octet-stream");
+    assert!(!dir.path().join("no-head.bin").exists(), "spider must not save files");
+}
+
+#[test]
+fn test_spider_reports_broken_links_with_nonzero_exit() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(HEAD).path("/ok.txt");
+        then.status(200).header("content-type", "text/plain").header("content-length", "5");
+    });
+    server.mock(|when, then| {
+        when.method(HEAD).path("/missing.txt");
+        then.status(404);
+    });
+
+    let output = Command::new("./target/debug/ruget")
+        .args(["--spider", &server.url("/ok.txt"), &server.url("/missing.txt")])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Broken links (1):"), "{}", stdout);
+    assert!(stdout.contains(&format!("{} (HTTP 404 Not Found)", server.url("/missing.txt"))), "{}", stdout);
+    assert!(stdout.contains("2 checked, 1 alive, 1 broken"), "{}", stdout);
+}
+
+#[test]
+fn test_spider_recursive_crawl() {
+    let server = MockServer::start();
+
+    let page = r#"<a href="/about.html">About</a> <a href="dead.html">Dead</a> <img src="/logo.png">"#;
+    server.mock(|when, then| {
+        when.method(HEAD).path("/");
+        then.status(200).header("content-type", "text/html");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/");
+        then.status(200).header("content-type", "text/html").body(page);
+    });
+    server.mock(|when, then| {
+        when.method(HEAD).path("/about.html");
+        then.status(200).header("content-type", "text/html");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/about.html");
+        then.status(200).header("content-type", "text/html").body(r#"<a href="/">Home</a>"#);
+    });
+    server.mock(|when, then| {
+        when.method(HEAD).path("/logo.png");
+        then.status(200).header("content-type", "image/png");
+    });
+    server.mock(|when, then| {
+        when.method(HEAD).path("/dead.html");
+        then.status(410);
+    });
+
+    let dir = tempdir().unwrap();
+    let report = dir.path().join("report.json");
+    let status = Command::new("./target/debug/ruget")
+        .args([
+            "--spider",
+            "--recursive",
+            "--level", "2",
+            &server.url("/"),
+            "--report-format", "json",
+            "--report", report.to_str().unwrap(),
+            "--quiet",
+        ])
+        .status()
+        .unwrap();
+
+    assert!(!status.success());
+    let json: serde_json::Value = serde_json::from_str(&read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(json["checked"], 4);
+    assert_eq!(json["broken"], 1);
+    assert_eq!(json["broken_links"][0]["url"], server.url("/dead.html"));
+    assert_eq!(json["broken_links"][0]["referrer"], server.url("/"));
+}
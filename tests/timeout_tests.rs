@@ -0,0 +1,207 @@
+use httpmock::prelude::*;
+use std::fs::read_to_string;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+/// Serve one response per connection whose body trickles out a byte every 100ms
+fn trickle_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            thread::spawn(move || {
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n");
+                for _ in 0..1000 {
+                    if stream.write_all(b"x").and_then(|_| stream.flush()).is_err() {
+                        return;
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+            });
+        }
+    });
+    format!("http://{}/slow.bin", addr)
+}
+
+/// Serve `body`, where a request without a Range sends `burst` bytes and then
+/// trickles a byte every 100ms; ranged requests are answered at full speed.
+/// Returns the URL and the Range header of every GET.
+fn stalling_server(body: &'static [u8], burst: usize) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/stall.bin", listener.local_addr().unwrap());
+    let ranges = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&ranges);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let seen = Arc::clone(&seen);
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                let _ = reader.read_line(&mut request_line);
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("range")
+                    {
+                        range = Some(value.trim().to_string());
+                    }
+                }
+                if request_line.starts_with("HEAD") {
+                    let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n\r\n", body.len());
+                    let _ = stream.write_all(head.as_bytes());
+                    return;
+                }
+                seen.lock().unwrap().push(range.clone());
+
+                let start = range
+                    .as_deref()
+                    .and_then(|r| r.strip_prefix("bytes="))
+                    .and_then(|r| r.strip_suffix('-'))
+                    .and_then(|r| r.parse::<usize>().ok());
+                let Some(start) = start else {
+                    let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+                    let _ = stream.write_all(&[head.as_bytes(), &body[..burst]].concat());
+                    for byte in body[burst..].chunks(1) {
+                        thread::sleep(Duration::from_millis(100));
+                        if stream.write_all(byte).and_then(|_| stream.flush()).is_err() {
+                            return;
+                        }
+                    }
+                    return;
+                };
+                let head = format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                    body.len() - start,
+                    start,
+                    body.len() - 1,
+                    body.len(),
+                );
+                let _ = stream.write_all(&[head.as_bytes(), &body[start..]].concat());
+            });
+        }
+    });
+    (url, ranges)
+}
+
+fn run_ruget(args: &[&str]) -> (bool, String, Duration) {
+    let start = Instant::now();
+    let output = Command::new("./target/debug/ruget").args(args).output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        start.elapsed(),
+    )
+}
+
+#[test]
+fn test_read_timeout_on_fast_path() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/stalled");
+        then.status(200).delay(Duration::from_secs(5)).body("late");
+    });
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("stalled");
+    let (success, stdout, elapsed) = run_ruget(&[
+        &server.url("/stalled"),
+        "--output", out.to_str().unwrap(),
+        "--read-timeout", "0.5",
+        "--max-retries", "1",
+    ]);
+
+    assert!(!success);
+    assert!(elapsed < Duration::from_secs(4), "took {:?}", elapsed);
+    assert!(stdout.contains("E404"), "{}", stdout);
+}
+
+#[test]
+fn test_speed_limit_aborts_slow_transfer() {
+    let url = trickle_server();
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("failures.log");
+    let (success, stdout, elapsed) = run_ruget(&[
+        &url,
+        "--output-dir", dir.path().to_str().unwrap(),
+        "--log", log.to_str().unwrap(),
+        "--speed-limit", "1000",
+        "--speed-time", "0.5",
+        "--max-retries", "0",
+        "--backoff-base-ms", "1",
+    ]);
+
+    assert!(!success);
+    assert!(elapsed < Duration::from_secs(10), "took {:?}", elapsed);
+    assert!(stdout.contains("E404"), "{}", stdout);
+    assert!(read_to_string(&log).unwrap().contains("--speed-limit"));
+}
+
+#[test]
+fn test_stalled_body_is_retried_from_current_offset() {
+    static BODY: [u8; 2000] = [b's'; 2000];
+    let (url, ranges) = stalling_server(&BODY, 100);
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("stall.bin");
+    let (success, stdout, elapsed) = run_ruget(&[
+        &url,
+        "--output", out.to_str().unwrap(),
+        "--speed-limit", "1000",
+        "--speed-time", "0.5",
+        "--max-retries", "1",
+        "--backoff-base-ms", "1",
+    ]);
+
+    assert!(success, "{}", stdout);
+    assert!(elapsed < Duration::from_secs(10), "took {:?}", elapsed);
+    assert_eq!(std::fs::read(&out).unwrap(), BODY);
+
+    // The retry asked for the rest of the body rather than starting over
+    let ranges = ranges.lock().unwrap();
+    assert_eq!(ranges.len(), 2, "{:?}", ranges);
+    assert_eq!(ranges[0], None);
+    let resumed_at: usize = ranges[1].as_deref().unwrap()
+        .trim_start_matches("bytes=")
+        .trim_end_matches('-')
+        .parse()
+        .unwrap();
+    assert!((100..BODY.len()).contains(&resumed_at), "{:?}", ranges);
+}
+
+#[test]
+fn test_max_time_aborts_long_transfer() {
+    let url = trickle_server();
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("failures.log");
+    let (success, _, elapsed) = run_ruget(&[
+        &url,
+        "--output-dir", dir.path().to_str().unwrap(),
+        "--log", log.to_str().unwrap(),
+        "--max-time", "0.5",
+        "--max-retries", "0",
+        "--backoff-base-ms", "1",
+    ]);
+
+    assert!(!success);
+    assert!(elapsed < Duration::from_secs(10), "took {:?}", elapsed);
+    assert!(read_to_string(&log).unwrap().contains("--max-time"));
+}
+
+#[test]
+fn test_invalid_timeout_rejected() {
+    let (success, _, _) = run_ruget(&["http://127.0.0.1:1/x", "--connect-timeout", "0"]);
+    assert!(!success);
+}
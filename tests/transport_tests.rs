@@ -0,0 +1,218 @@
+//! Features that depend on the HTTP backend, run once per `--transport`.
+
+use flate2::{write::GzEncoder, Compression};
+use httpmock::prelude::*;
+use httpmock::Method::{GET, HEAD};
+use std::fs::{read, read_to_string, write};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::thread;
+use tempfile::tempdir;
+
+const TRANSPORTS: [&str; 2] = ["reqwest", "native"];
+
+fn ruget(home: &Path, transport: &str, args: &[&str]) -> Output {
+    Command::new("./target/debug/ruget")
+        .args(args)
+        .args(["--transport", transport, "--verbose"])
+        .env("HOME", home)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_split_download() {
+    let body: Vec<u8> = (0..2 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    for transport in TRANSPORTS {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(HEAD).path("/big.bin");
+            then.status(200)
+                .header("Content-Length", body.len().to_string())
+                .header("Accept-Ranges", "bytes");
+        });
+        let half = body.len() / 2;
+        let ranges: Vec<_> = [(0, half - 1), (half, body.len() - 1)]
+            .into_iter()
+            .map(|(start, end)| {
+                let part = body[start..=end].to_vec();
+                let total = body.len();
+                server.mock(move |when, then| {
+                    when.method(GET).path("/big.bin").header("Range", format!("bytes={}-{}", start, end));
+                    then.status(206).header("Content-Range", format!("bytes {}-{}/{}", start, end, total)).body(part);
+                })
+            })
+            .collect();
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("big.bin");
+        let output = ruget(dir.path(), transport, &[&server.url("/big.bin"), "-o", out.to_str().unwrap(), "--split", "2"]);
+
+        assert!(output.status.success(), "{}: {}", transport, String::from_utf8_lossy(&output.stdout));
+        for range in ranges {
+            range.assert_hits(1);
+        }
+        assert!(read(&out).unwrap() == body, "{}", transport);
+    }
+}
+
+#[test]
+fn test_resume_sends_range() {
+    for transport in TRANSPORTS {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(HEAD).path("/file.txt");
+            then.status(200).header("Content-Length", "10").header("Accept-Ranges", "bytes");
+        });
+        let rest = server.mock(|when, then| {
+            when.method(GET).path("/file.txt").header("Range", "bytes=4-");
+            then.status(206).header("Content-Range", "bytes 4-9/10").body("456789");
+        });
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("file.txt");
+        write(&out, "0123").unwrap();
+        let output = ruget(dir.path(), transport, &[&server.url("/file.txt"), "-o", out.to_str().unwrap(), "--resume"]);
+
+        assert!(output.status.success(), "{}: {}", transport, String::from_utf8_lossy(&output.stdout));
+        rest.assert_hits(1);
+        assert_eq!(read_to_string(&out).unwrap(), "0123456789", "{}", transport);
+    }
+}
+
+#[test]
+fn test_compressed_body_is_decoded() {
+    let data = b"compressible data compressible data compressible data";
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    let encoded = encoder.finish().unwrap();
+
+    for transport in TRANSPORTS {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/data.txt").header_exists("accept-encoding");
+            then.status(200).header("Content-Encoding", "gzip").body(&encoded);
+        });
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("data.txt");
+        let output = ruget(dir.path(), transport, &[&server.url("/data.txt"), "-o", out.to_str().unwrap(), "--compressed"]);
+
+        assert!(output.status.success(), "{}: {}", transport, String::from_utf8_lossy(&output.stdout));
+        assert_eq!(read(&out).unwrap(), data, "{}", transport);
+    }
+}
+
+#[test]
+fn test_redirect_keeps_cookies_and_headers() {
+    for transport in TRANSPORTS {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/login");
+            then.status(302)
+                .header("Set-Cookie", "session=abc; Path=/")
+                .header("Location", server.url("/data"));
+        });
+        let data = server.mock(|when, then| {
+            when.method(GET).path("/data").header("Cookie", "session=abc").header("X-Token", "t1");
+            then.status(200).body("welcome");
+        });
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("data.txt");
+        let output = ruget(
+            dir.path(),
+            transport,
+            &[&server.url("/login"), "-o", out.to_str().unwrap(), "-H", "X-Token: t1"],
+        );
+
+        assert!(output.status.success(), "{}: {}", transport, String::from_utf8_lossy(&output.stdout));
+        data.assert_hits(1);
+        assert_eq!(read_to_string(&out).unwrap(), "welcome", "{}", transport);
+    }
+}
+
+fn without_authorization(req: &HttpMockRequest) -> bool {
+    !req.headers.iter().flatten().any(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+}
+
+#[test]
+fn test_url_credentials_are_sent_and_dropped_across_origins() {
+    for transport in TRANSPORTS {
+        let server = MockServer::start();
+        let other = MockServer::start();
+        let secret = server.mock(|when, then| {
+            when.method(GET).path("/secret").header("Authorization", "Basic dXNlcjpwYXNz");
+            then.status(200).body("authorized");
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/moved").header("Authorization", "Basic dXNlcjpwYXNz");
+            then.status(302).header("Location", other.url("/public"));
+        });
+        let public = other.mock(|when, then| {
+            when.method(GET).path("/public").matches(without_authorization);
+            then.status(200).body("public");
+        });
+
+        let dir = tempdir().unwrap();
+        let with_credentials = |path: &str| server.url(path).replacen("http://", "http://user:pass@", 1);
+        let out = dir.path().join("secret.txt");
+        let output = ruget(dir.path(), transport, &[&with_credentials("/secret"), "-o", out.to_str().unwrap()]);
+
+        assert!(output.status.success(), "{}: {}", transport, String::from_utf8_lossy(&output.stdout));
+        secret.assert_hits(1);
+        assert_eq!(read_to_string(&out).unwrap(), "authorized", "{}", transport);
+
+        let out = dir.path().join("public.txt");
+        let output = ruget(dir.path(), transport, &[&with_credentials("/moved"), "-o", out.to_str().unwrap()]);
+
+        assert!(output.status.success(), "{}: {}", transport, String::from_utf8_lossy(&output.stdout));
+        public.assert_hits(1);
+        assert_eq!(read_to_string(&out).unwrap(), "public", "{}", transport);
+    }
+}
+
+/// A server that answers every request with a chunked body, then closes the connection
+fn chunked_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/chunked.txt", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+            }
+            let body = if request_line.starts_with("HEAD") { "" } else { "5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n" };
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{}",
+                body,
+            );
+        }
+    });
+    url
+}
+
+#[test]
+fn test_chunked_body() {
+    for transport in TRANSPORTS {
+        let url = chunked_server();
+
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("chunked.txt");
+        let output = ruget(dir.path(), transport, &[&url, "-o", out.to_str().unwrap()]);
+
+        assert!(output.status.success(), "{}: {}", transport, String::from_utf8_lossy(&output.stdout));
+        assert_eq!(read_to_string(&out).unwrap(), "hello world", "{}", transport);
+    }
+}
@@ -0,0 +1,212 @@
+use std::fs::{read, read_to_string};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tempfile::tempdir;
+
+/// A server for `body` whose full responses stop after `cut` bytes, as if the
+/// connection dropped; ranged GETs are answered in full. Returns the URL and
+/// the Range header of every GET.
+fn flaky_server(body: &'static [u8], cut: usize) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/file.bin", listener.local_addr().unwrap());
+    let gets = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&gets);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut range = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("range")
+                {
+                    range = Some(value.trim().to_string());
+                }
+            }
+
+            let head = |status: &str, extra: String| {
+                format!("HTTP/1.1 {}\r\nAccept-Ranges: bytes\r\nETag: \"v1\"\r\nConnection: close\r\n{}\r\n", status, extra)
+            };
+            if request_line.starts_with("HEAD") {
+                let _ = stream.write_all(head("200 OK", format!("Content-Length: {}\r\n", body.len())).as_bytes());
+                continue;
+            }
+            seen.lock().unwrap().push(range.clone());
+            let start = range
+                .as_deref()
+                .and_then(|r| r.strip_prefix("bytes="))
+                .and_then(|r| r.strip_suffix('-'))
+                .and_then(|r| r.parse::<usize>().ok());
+            let response = match start {
+                Some(start) => {
+                    let extra = format!(
+                        "Content-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\n",
+                        body.len() - start,
+                        start,
+                        body.len() - 1,
+                        body.len(),
+                    );
+                    [head("206 Partial Content", extra).as_bytes(), &body[start..]].concat()
+                }
+                None => [head("200 OK", format!("Content-Length: {}\r\n", body.len())).as_bytes(), &body[..cut]].concat(),
+            };
+            let _ = stream.write_all(&response);
+        }
+    });
+    (url, gets)
+}
+
+#[test]
+fn test_dropped_connection_continues_from_last_byte() {
+    static BODY: [u8; 100_000] = [b'z'; 100_000];
+    let (url, gets) = flaky_server(&BODY, 40_000);
+
+    let dir = tempdir().unwrap();
+    let output = Command::new("./target/debug/ruget")
+        .args([&url, "--output-dir", dir.path().to_str().unwrap(), "--verbose", "--backoff-base-ms", "10"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(read(dir.path().join("file.bin")).unwrap(), BODY);
+    // The retry asks only for the bytes after the last one written
+    assert_eq!(*gets.lock().unwrap(), [None, Some("bytes=40000-".to_string())]);
+    assert!(!dir.path().join("file.bin.ruget-resume").exists());
+}
+
+#[test]
+fn test_truncated_body_is_not_a_success() {
+    static BODY: [u8; 1000] = [b't'; 1000];
+    let (url, _) = flaky_server(&BODY, 400);
+
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("failures.log");
+    let output = Command::new("./target/debug/ruget")
+        .args([
+            &url,
+            "--output-dir", dir.path().to_str().unwrap(),
+            "--log", log.to_str().unwrap(),
+            "--retry-on", "5xx",
+            "--verbose",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(read_to_string(&log).unwrap().contains("E400"));
+    // The validators stay behind so --resume can continue the file safely
+    assert!(read_to_string(dir.path().join("file.bin.ruget-resume")).unwrap().contains("v1"));
+}
+
+/// A server for `body` that accepts byte ranges and cuts the first response
+/// for each range halfway, keeping the Content-Length it announced. Returns
+/// the URL and the Range header of every GET.
+fn chunk_cutting_server(body: &'static [u8]) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/chunked.bin", listener.local_addr().unwrap());
+    let gets = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&gets);
+
+    thread::spawn(move || {
+        let cut_ends = Arc::new(Mutex::new(std::collections::HashSet::new()));
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let seen = Arc::clone(&seen);
+            let cut_ends = Arc::clone(&cut_ends);
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("range")
+                    {
+                        range = Some(value.trim().to_string());
+                    }
+                }
+
+                let head = |status: &str, extra: String| {
+                    format!("HTTP/1.1 {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n{}\r\n", status, extra)
+                };
+                let Some(range) = range else {
+                    let response = head("200 OK", format!("Content-Length: {}\r\n", body.len()));
+                    let _ = stream.write_all(response.as_bytes());
+                    if request_line.starts_with("GET") {
+                        let _ = stream.write_all(body);
+                    }
+                    return;
+                };
+                seen.lock().unwrap().push(range.clone());
+                let (start, end) = range
+                    .strip_prefix("bytes=")
+                    .and_then(|r| r.split_once('-'))
+                    .map(|(start, end)| (start.parse::<usize>().unwrap(), end.parse::<usize>().unwrap()))
+                    .unwrap();
+                let extra = format!(
+                    "Content-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\n",
+                    end - start + 1,
+                    start,
+                    end,
+                    body.len(),
+                );
+                let cut = cut_ends.lock().unwrap().insert(end);
+                let sent = if cut { start + (end - start + 1).div_ceil(2) } else { end + 1 };
+                let _ = stream.write_all(&[head("206 Partial Content", extra).as_bytes(), &body[start..sent]].concat());
+            });
+        }
+    });
+    (url, gets)
+}
+
+#[test]
+fn test_truncated_chunk_continues_from_last_byte() {
+    let body: &'static [u8] = Box::leak((0..2 * 1024 * 1024).map(|i| (i % 251) as u8).collect());
+
+    for transport in ["reqwest", "native"] {
+        let (url, gets) = chunk_cutting_server(body);
+        let dir = tempdir().unwrap();
+        let output = Command::new("./target/debug/ruget")
+            .args([
+                &url,
+                "--output-dir", dir.path().to_str().unwrap(),
+                "--split", "2",
+                "--transport", transport,
+                "--backoff-base-ms", "10",
+            ])
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "{}: {}", transport, String::from_utf8_lossy(&output.stdout));
+        assert!(read(dir.path().join("chunked.bin")).unwrap() == body, "{}: corrupt file", transport);
+
+        // Each chunk was cut once and then asked for only the bytes it was missing
+        let mut gets = gets.lock().unwrap().clone();
+        gets.sort();
+        let half = body.len() / 2;
+        let mut expected = [
+            format!("bytes=0-{}", half - 1),
+            format!("bytes={}-{}", half / 2, half - 1),
+            format!("bytes={}-{}", half, body.len() - 1),
+            format!("bytes={}-{}", half + half / 2, body.len() - 1),
+        ];
+        expected.sort();
+        assert_eq!(gets, expected, "{}", transport);
+    }
+}